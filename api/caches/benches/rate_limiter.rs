@@ -0,0 +1,67 @@
+//! Latency of the rate limiter path: one call of the token bucket script per request.
+//! Compares a new connection per command, as before the connection manager, with the
+//! shared connection of `RedisConnectionManager`. Needs the Redis server of the `.env`.
+//!
+//! cargo bench -p api-caches --bench rate_limiter
+
+use api_caches::{
+    rate_limit_algorithms::algorithm,
+    redis::get_redis_client,
+    token_buckets::{TokenBucketsCache, TokenBucketsCacheRedis},
+};
+use api_configs::{config::Config, rate_limit::RateLimitPolicy};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn policy() -> RateLimitPolicy {
+    // assez large pour ne jamais refuser pendant le benchmark
+    RateLimitPolicy {
+        name: "bench".to_string(),
+        path_prefix: String::new(),
+        methods: vec![],
+        caller: Default::default(),
+        algorithm: Default::default(),
+        capacity: u32::MAX as u64,
+        refill_amount: u32::MAX as u64,
+        refill_period: 1,
+        cost: 1,
+        opt_in: false,
+    }
+}
+
+fn consume_tokens(c: &mut Criterion) {
+    let config = Config::init();
+    let policy = policy();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("consume_tokens");
+
+    let client = redis::Client::open(config.redis_info.get_url()).unwrap();
+    let algorithm = algorithm(policy.algorithm);
+    let args = algorithm.args(&policy);
+    group.bench_function("connection_per_command", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let mut con = client.get_multiplexed_async_connection().await.unwrap();
+            let mut invocation = algorithm.script().prepare_invoke();
+            invocation.key("ratelimit:bench:connection_per_command");
+            for arg in &args {
+                invocation.arg(arg);
+            }
+            let _: Vec<i64> = invocation.invoke_async(&mut con).await.unwrap();
+        })
+    });
+
+    let cache = TokenBucketsCacheRedis::new(get_redis_client(&config));
+    group.bench_function("connection_manager", |b| {
+        b.to_async(&runtime).iter(|| async {
+            cache
+                .consume_tokens("bench:connection_manager", &policy)
+                .await
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, consume_tokens);
+criterion_main!(benches);
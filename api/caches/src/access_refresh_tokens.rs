@@ -1,10 +1,14 @@
-use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::{
+    cache::Cache,
+    clock::{Clock, SystemClock},
     errors::RedisRepositoryError,
+    memory::MemoryStore,
     redis::{RedisClient, RedisRepository, RedisRepositoryResult},
 };
 use api_configs::config::Config;
+use serde::{Deserialize, Serialize};
 
 /// Trait for managing refresh tokens in a Redis-based cache.
 #[async_trait::async_trait]
@@ -51,7 +55,7 @@ pub trait AccessRefreshTokensCache: Clone + Send + Sync + 'static {
 }
 
 /// Structure representing user metadata (ID and email) associated with a refresh token.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct UserMetaData {
     /// The unique identifier of the user.
     pub id: String,
@@ -59,85 +63,133 @@ pub struct UserMetaData {
     pub email: String,
 }
 
-impl UserMetaData {
-    /// Converts `UserMetaData` into a Redis-compatible value format.
-    ///
-    /// # Returns
-    /// A string formatted as "id:email" to be stored in Redis.
-    fn to_redis_value(&self) -> String {
-        format!("{}:{}", self.id, self.email)
-    }
+/// Redis-based implementation of the `AccessRefreshTokensCache` trait.
+/// The metadata are stored in JSON under `refresh_tokens:{refresh_token}`.
+#[derive(Clone)]
+pub struct AccessRefreshTokensCacheRedis<R: RedisRepository = RedisClient> {
+    /// The user metadata by refresh token, expiring after the ttl of the refresh tokens.
+    tokens: Cache<str, UserMetaData, R>,
 }
 
-impl FromStr for UserMetaData {
-    type Err = ();
-
-    /// Parses a string in the format "id:email" into a `UserMetaData` structure.
+impl<R: RedisRepository> AccessRefreshTokensCacheRedis<R> {
+    /// Creates a new instance of `AccessRefreshTokensCacheRedis`.
     ///
     /// # Arguments
-    /// * `s` - The string to parse, expected to be in the format "id:email".
+    /// * `client` - The Redis client instance to use.
+    /// * `config` - Configuration settings.
     ///
     /// # Returns
-    /// A `UserMetaData` instance parsed from the input string.
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let splited: Vec<&str> = s.split(":").collect();
-        Ok(UserMetaData {
-            id: splited[0].to_string(),
-            email: splited[1].to_string(),
-        })
+    /// A new `AccessRefreshTokensCacheRedis` instance.
+    pub fn new(client: R, config: Config) -> Self {
+        AccessRefreshTokensCacheRedis {
+            tokens: Cache::new(client, "refresh_tokens").ttl(config.refresh_token_ttl),
+        }
     }
 }
 
-/// Redis-based implementation of the `AccessRefreshTokensCache` trait.
+#[async_trait::async_trait]
+impl<R: RedisRepository> AccessRefreshTokensCache for AccessRefreshTokensCacheRedis<R> {
+    async fn save_refresh_token(
+        &self,
+        refresh_token: &str,
+        user_meta_data: UserMetaData,
+    ) -> RedisRepositoryResult<()> {
+        self.tokens.set(refresh_token, &user_meta_data).await
+    }
+
+    async fn get_meta_data_users_by_refresh_token(
+        &self,
+        refresh_token: &str,
+    ) -> RedisRepositoryResult<UserMetaData> {
+        self.tokens
+            .get(refresh_token)
+            .await?
+            .ok_or(RedisRepositoryError::NotFound)
+    }
+
+    async fn invalidate_and_save_token(
+        &self,
+        last_refresh_token: &str,
+        new_refresh_token: &str,
+    ) -> RedisRepositoryResult<()> {
+        let user_meta_data = self
+            .get_meta_data_users_by_refresh_token(last_refresh_token)
+            .await?;
+
+        // un refresh token ne peut être échangé qu'une fois, même par deux requêtes simultanées
+        if !self
+            .tokens
+            .compare_and_set(last_refresh_token, Some(&user_meta_data), None)
+            .await?
+        {
+            return Err(RedisRepositoryError::NotFound);
+        }
+
+        self.save_refresh_token(new_refresh_token, user_meta_data)
+            .await
+    }
+}
+
+/// In-process implementation of the `AccessRefreshTokensCache` trait, for the tests and the
+/// deployments with a single instance of the api.
 #[derive(Clone)]
-pub struct AccessRefreshTokensCacheRedis {
-    /// Redis client instance.
-    client: RedisClient,
+pub struct AccessRefreshTokensCacheMemory {
+    /// The user metadata by refresh token, shared by the clones of the cache.
+    store: Arc<MemoryStore<UserMetaData>>,
     /// Configuration settings.
     config: Config,
 }
 
-impl AccessRefreshTokensCacheRedis {
-    /// Creates a new instance of `AccessRefreshTokensCacheRedis`.
+impl AccessRefreshTokensCacheMemory {
+    /// Creates a new instance of `AccessRefreshTokensCacheMemory` using the system time.
     ///
     /// # Arguments
-    /// * `client` - The Redis client instance to use.
     /// * `config` - Configuration settings.
     ///
     /// # Returns
-    /// A new `AccessRefreshTokensCacheRedis` instance.
-    pub fn new(client: RedisClient, config: Config) -> Self {
-        AccessRefreshTokensCacheRedis { client, config }
+    /// A new `AccessRefreshTokensCacheMemory` instance.
+    pub fn new(config: Config) -> Self {
+        AccessRefreshTokensCacheMemory::with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// Creates a new instance of `AccessRefreshTokensCacheMemory`.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration settings.
+    /// * `clock` - The source of the time of the expirations.
+    ///
+    /// # Returns
+    /// A new `AccessRefreshTokensCacheMemory` instance.
+    pub fn with_clock(config: Config, clock: Arc<dyn Clock>) -> Self {
+        AccessRefreshTokensCacheMemory {
+            store: Arc::new(MemoryStore::new(clock)),
+            config,
+        }
     }
 }
 
 #[async_trait::async_trait]
-impl AccessRefreshTokensCache for AccessRefreshTokensCacheRedis {
+impl AccessRefreshTokensCache for AccessRefreshTokensCacheMemory {
     async fn save_refresh_token(
         &self,
         refresh_token: &str,
         user_meta_data: UserMetaData,
     ) -> RedisRepositoryResult<()> {
-        self.client
-            .update_ttl(
-                refresh_token,
-                &user_meta_data.to_redis_value(),
-                self.config.refresh_token_ttl,
-            )
-            .await
+        self.store.set(
+            refresh_token,
+            user_meta_data,
+            Some(self.config.refresh_token_ttl * 1000),
+        );
+        Ok(())
     }
 
     async fn get_meta_data_users_by_refresh_token(
         &self,
         refresh_token: &str,
     ) -> RedisRepositoryResult<UserMetaData> {
-        let user_meta_data = self.client.get(refresh_token).await?;
-
-        if user_meta_data.is_none() {
-            return Err(RedisRepositoryError::NotFound);
-        }
-
-        Ok(UserMetaData::from_str(&user_meta_data.unwrap()).unwrap())
+        self.store
+            .get(refresh_token)
+            .ok_or(RedisRepositoryError::NotFound)
     }
 
     async fn invalidate_and_save_token(
@@ -145,12 +197,109 @@ impl AccessRefreshTokensCache for AccessRefreshTokensCacheRedis {
         last_refresh_token: &str,
         new_refresh_token: &str,
     ) -> RedisRepositoryResult<()> {
+        // le retrait est atomique, un refresh token ne peut être échangé qu'une fois
         let user_meta_data = self
-            .get_meta_data_users_by_refresh_token(last_refresh_token)
-            .await?;
+            .store
+            .remove(last_refresh_token)
+            .ok_or(RedisRepositoryError::NotFound)?;
 
-        self.client.delete(last_refresh_token).await?;
         self.save_refresh_token(new_refresh_token, user_meta_data)
             .await
     }
 }
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::clock::ManualClock;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::time::Duration;
+
+    #[allow(dead_code)]
+    fn user() -> UserMetaData {
+        UserMetaData {
+            id: "1".to_string(),
+            email: "tester@test.com".to_string(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_refresh_tokens_are_rotated() {
+        let config = Config::init();
+        let cache = AccessRefreshTokensCacheRedis::new(
+            crate::redis::get_redis_client(&config),
+            config.clone(),
+        );
+        cache
+            .save_refresh_token("test_rotation_first", user())
+            .await
+            .unwrap();
+
+        cache
+            .invalidate_and_save_token("test_rotation_first", "test_rotation_second")
+            .await
+            .unwrap();
+        assert_eq!(
+            cache
+                .get_meta_data_users_by_refresh_token("test_rotation_second")
+                .await
+                .unwrap(),
+            user()
+        );
+
+        // l'ancien token ne peut plus servir
+        assert!(matches!(
+            cache
+                .invalidate_and_save_token("test_rotation_first", "test_rotation_third")
+                .await,
+            Err(RedisRepositoryError::NotFound)
+        ));
+
+        cache.tokens.delete("test_rotation_second").await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_refresh_tokens_are_rotated() {
+        let cache = AccessRefreshTokensCacheMemory::new(Config::init());
+        cache.save_refresh_token("first", user()).await.unwrap();
+
+        cache
+            .invalidate_and_save_token("first", "second")
+            .await
+            .unwrap();
+
+        let user_meta_data = cache
+            .get_meta_data_users_by_refresh_token("second")
+            .await
+            .unwrap();
+        assert_eq!(user_meta_data.email, "tester@test.com");
+
+        // l'ancien token ne peut plus servir
+        assert!(matches!(
+            cache.invalidate_and_save_token("first", "third").await,
+            Err(RedisRepositoryError::NotFound)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_refresh_tokens_expire() {
+        let config = Config::init();
+        let clock = ManualClock::default();
+        let cache =
+            AccessRefreshTokensCacheMemory::with_clock(config.clone(), Arc::new(clock.clone()));
+        cache.save_refresh_token("token", user()).await.unwrap();
+
+        clock.advance(Duration::from_secs(config.refresh_token_ttl as u64 - 1));
+        assert!(cache
+            .get_meta_data_users_by_refresh_token("token")
+            .await
+            .is_ok());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            cache.get_meta_data_users_by_refresh_token("token").await,
+            Err(RedisRepositoryError::NotFound)
+        ));
+    }
+}
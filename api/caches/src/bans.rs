@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+
+use api_types::ban::Ban;
+
+use crate::redis::{hash_tag, RedisClient, RedisRepository, RedisRepositoryResult};
+
+/// Trait for the temporary bans of the ips and callers.
+/// A target is identified like the buckets of the rate limiter: `ip:{ip}`, `user:{id}` or `client:{id}`.
+#[async_trait::async_trait]
+pub trait BansCache: Clone + Send + Sync + 'static {
+    /// Bans a target, replacing its current ban.
+    ///
+    /// # Arguments
+    /// * `duration` - Number of seconds before the ban expires.
+    async fn ban(&self, target: &str, reason: &str, duration: i64) -> RedisRepositoryResult<Ban>;
+
+    /// Returns the first current ban among the targets.
+    async fn find_ban(&self, targets: &[String]) -> RedisRepositoryResult<Option<Ban>>;
+
+    /// Returns every current ban.
+    async fn list_bans(&self) -> RedisRepositoryResult<Vec<Ban>>;
+
+    async fn unban(&self, target: &str) -> RedisRepositoryResult<()>;
+
+    /// Records a refused request (401 or 429) and returns the number of recent ones.
+    ///
+    /// # Arguments
+    /// * `window` - Number of seconds after which the refused requests are forgotten.
+    async fn record_strike(&self, target: &str, window: i64) -> RedisRepositoryResult<i64>;
+}
+
+/// Redis-based implementation of `BansCache`.
+/// Each ban is a hash expiring with the ban, the refused requests are counted in another hash.
+/// The two keys of a target share its hash tag, so they are on the same node of a cluster.
+#[derive(Clone)]
+pub struct BansCacheRedis {
+    /// Redis client instance.
+    client: RedisClient,
+}
+
+impl BansCacheRedis {
+    /// Creates a new instance of `BansCacheRedis`.
+    ///
+    /// # Arguments
+    /// * `client` - The Redis client instance.
+    ///
+    /// # Returns
+    /// A new `BansCacheRedis` instance.
+    pub fn new(client: RedisClient) -> Self {
+        BansCacheRedis { client }
+    }
+
+    fn ban_key(target: &str) -> String {
+        format!("bans:{}", hash_tag(target))
+    }
+
+    fn strikes_key(target: &str) -> String {
+        format!("ban_strikes:{}", hash_tag(target))
+    }
+
+    async fn get_ban(&self, target: &str) -> RedisRepositoryResult<Option<Ban>> {
+        let ban = self
+            .client
+            .hget_multiple(
+                &Self::ban_key(target),
+                vec!["reason".to_string(), "expires_at".to_string()],
+            )
+            .await?;
+
+        let expires_at = ban[1]
+            .as_ref()
+            .and_then(|el| el.parse().ok())
+            .and_then(|el| DateTime::from_timestamp(el, 0))
+            .filter(|el| *el > Utc::now());
+
+        Ok(expires_at.map(|expires_at| Ban {
+            target: target.to_string(),
+            reason: ban[0].clone().unwrap_or_default(),
+            expires_at,
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl BansCache for BansCacheRedis {
+    async fn ban(&self, target: &str, reason: &str, duration: i64) -> RedisRepositoryResult<Ban> {
+        let ban = Ban {
+            target: target.to_string(),
+            reason: reason.to_string(),
+            // stocké à la seconde
+            expires_at: DateTime::from_timestamp(Utc::now().timestamp() + duration, 0)
+                .unwrap_or_default(),
+        };
+
+        self.client
+            .hset_multiple_ttl(
+                &Self::ban_key(target),
+                vec![
+                    ("reason".to_string(), ban.reason.clone()),
+                    (
+                        "expires_at".to_string(),
+                        ban.expires_at.timestamp().to_string(),
+                    ),
+                ],
+                duration,
+            )
+            .await?;
+
+        // the strikes before the ban are forgiven
+        self.client.delete(&Self::strikes_key(target)).await?;
+
+        Ok(ban)
+    }
+
+    async fn find_ban(&self, targets: &[String]) -> RedisRepositoryResult<Option<Ban>> {
+        for target in targets {
+            if let Some(ban) = self.get_ban(target).await? {
+                return Ok(Some(ban));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn list_bans(&self) -> RedisRepositoryResult<Vec<Ban>> {
+        let mut bans = vec![];
+
+        for key in self.client.scan_keys(&Self::ban_key("*")).await? {
+            let Some(target) = key
+                .strip_prefix("bans:{")
+                .and_then(|key| key.strip_suffix('}'))
+            else {
+                continue;
+            };
+            if let Some(ban) = self.get_ban(target).await? {
+                bans.push(ban);
+            }
+        }
+
+        bans.sort_by_key(|ban| ban.expires_at);
+        Ok(bans)
+    }
+
+    async fn unban(&self, target: &str) -> RedisRepositoryResult<()> {
+        self.client
+            .delete_all(&[&Self::strikes_key(target), &Self::ban_key(target)])
+            .await
+    }
+
+    async fn record_strike(&self, target: &str, window: i64) -> RedisRepositoryResult<i64> {
+        self.client
+            .hincr(&Self::strikes_key(target), "count", window)
+            .await
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CONFIG: Lazy<api_configs::config::Config> = Lazy::new(api_configs::config::Config::init);
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CLIENT: Lazy<RedisClient> =
+        Lazy::new(|| crate::redis::get_redis_client(&CONFIG.clone()));
+
+    #[actix_rt::test]
+    async fn test_ban_and_unban() {
+        let cache = BansCacheRedis::new(CLIENT.clone());
+        let target = "ip:192.0.2.10";
+        cache.unban(target).await.unwrap();
+
+        let ban = cache.ban(target, "scraping", 60).await.unwrap();
+        assert_eq!(CLIENT.ttl("bans:{ip:192.0.2.10}").await.unwrap(), 60);
+
+        let targets = vec!["user:1".to_string(), target.to_string()];
+        assert_eq!(cache.find_ban(&targets).await.unwrap(), Some(ban.clone()));
+        assert!(cache.list_bans().await.unwrap().contains(&ban));
+
+        cache.unban(target).await.unwrap();
+        assert_eq!(cache.find_ban(&targets).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_strikes_are_forgiven_by_the_ban() {
+        let cache = BansCacheRedis::new(CLIENT.clone());
+        let target = "user:4242";
+        cache.unban(target).await.unwrap();
+
+        assert_eq!(cache.record_strike(target, 60).await.unwrap(), 1);
+        assert_eq!(cache.record_strike(target, 60).await.unwrap(), 2);
+
+        cache.ban(target, "too many errors", 60).await.unwrap();
+        assert_eq!(cache.record_strike(target, 60).await.unwrap(), 1);
+
+        cache.unban(target).await.unwrap();
+    }
+}
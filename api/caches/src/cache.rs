@@ -0,0 +1,348 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    errors::RedisRepositoryError,
+    redis::{RedisClient, RedisRepository, RedisRepositoryResult},
+};
+
+/// Replacement of a value only if it has not changed since it was read.
+static COMPARE_AND_SET_SCRIPT: Lazy<redis::Script> =
+    Lazy::new(|| redis::Script::new(include_str!("scripts/compare_and_set.lua")));
+
+/// Typed cache on top of a `RedisRepository`, the values are encoded in JSON.
+///
+/// The keys are `{namespace}:{key}`. Each value is prefixed by the version of its encoding,
+/// `v1:{"id":"1"}`: after a change of `V`, the version is bumped and the values written with
+/// the previous one are read as missing instead of failing to decode.
+pub struct Cache<K: ?Sized, V, R = RedisClient> {
+    /// The Redis repository.
+    repository: R,
+    /// Prefix of the keys.
+    namespace: String,
+    /// Version of the encoding of the values.
+    version: u32,
+    /// Ttl of the values in seconds, `None` to keep them.
+    ttl: Option<i64>,
+    _types: PhantomData<fn(&K) -> V>,
+}
+
+impl<K: ?Sized, V, R: Clone> Clone for Cache<K, V, R> {
+    fn clone(&self) -> Self {
+        Cache {
+            repository: self.repository.clone(),
+            namespace: self.namespace.clone(),
+            version: self.version,
+            ttl: self.ttl,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<K, V, R> Cache<K, V, R>
+where
+    K: Display + ?Sized,
+    V: Serialize + DeserializeOwned,
+    R: RedisRepository,
+{
+    /// Creates a cache of the version 1, without ttl.
+    ///
+    /// # Arguments
+    /// * `repository` - The Redis repository.
+    /// * `namespace` - The prefix of the keys.
+    pub fn new(repository: R, namespace: &str) -> Self {
+        Cache {
+            repository,
+            namespace: namespace.to_string(),
+            version: 1,
+            ttl: None,
+            _types: PhantomData,
+        }
+    }
+
+    /// Sets the version of the encoding of the values.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the ttl in seconds of the values written by `set`, `set_many` and `compare_and_set`.
+    pub fn ttl(mut self, ttl: i64) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The Redis key of a key of the cache.
+    pub fn key(&self, key: &K) -> String {
+        format!("{}:{}", self.namespace, key)
+    }
+
+    fn encode(&self, value: &V) -> RedisRepositoryResult<String> {
+        Ok(format!(
+            "v{}:{}",
+            self.version,
+            serde_json::to_string(value)?
+        ))
+    }
+
+    fn decode(&self, encoded: &str) -> RedisRepositoryResult<Option<V>> {
+        match encoded.strip_prefix(&format!("v{}:", self.version)) {
+            Some(json) => Ok(Some(serde_json::from_str(json)?)),
+            // écrit avec une autre version de l'encodage
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value of a key, `None` if it is missing or of another version.
+    pub async fn get(&self, key: &K) -> RedisRepositoryResult<Option<V>> {
+        match self.repository.get(&self.key(key)).await? {
+            Some(encoded) => self.decode(&encoded),
+            None => Ok(None),
+        }
+    }
+
+    /// Sets the value of a key with the ttl of the cache.
+    pub async fn set(&self, key: &K, value: &V) -> RedisRepositoryResult<()> {
+        match self.ttl {
+            Some(ttl) => self.set_with_ttl(key, value, ttl).await,
+            None => {
+                self.repository
+                    .set(&self.key(key), &self.encode(value)?)
+                    .await
+            }
+        }
+    }
+
+    /// Sets the value of a key, it expires after `ttl` seconds.
+    pub async fn set_with_ttl(&self, key: &K, value: &V, ttl: i64) -> RedisRepositoryResult<()> {
+        self.repository
+            .update_ttl(&self.key(key), &self.encode(value)?, ttl)
+            .await
+    }
+
+    pub async fn delete(&self, key: &K) -> RedisRepositoryResult<()> {
+        self.repository.delete(&self.key(key)).await
+    }
+
+    /// Returns the values of several keys in one round trip, in the order of the keys.
+    pub async fn get_many(&self, keys: &[&K]) -> RedisRepositoryResult<Vec<Option<V>>> {
+        let keys = keys.iter().map(|key| self.key(key)).collect::<Vec<_>>();
+        let values = self
+            .repository
+            .get_many(&keys.iter().map(String::as_str).collect::<Vec<_>>())
+            .await?;
+
+        values
+            .into_iter()
+            .map(|encoded| match encoded {
+                Some(encoded) => self.decode(&encoded),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Sets several keys in one round trip with the ttl of the cache, not atomically.
+    pub async fn set_many(&self, entries: &[(&K, &V)]) -> RedisRepositoryResult<()> {
+        let entries = entries
+            .iter()
+            .map(|(key, value)| Ok((self.key(key), self.encode(value)?)))
+            .collect::<RedisRepositoryResult<Vec<_>>>()?;
+
+        self.repository.set_many(entries, self.ttl).await
+    }
+
+    /// Replaces the value of a key only if it is still `expected`, atomically.
+    /// `expected: None` requires the key to be missing, `new: None` deletes the key.
+    /// The values are compared once encoded, so `V` must always encode the same way
+    /// (a struct, not a `HashMap`).
+    ///
+    /// # Returns
+    /// Whether the value has been replaced.
+    pub async fn compare_and_set(
+        &self,
+        key: &K,
+        expected: Option<&V>,
+        new: Option<&V>,
+    ) -> RedisRepositoryResult<bool> {
+        let expected = expected.map(|value| self.encode(value)).transpose()?;
+        let new = new.map(|value| self.encode(value)).transpose()?;
+
+        let result = self
+            .repository
+            .eval_ints(
+                &COMPARE_AND_SET_SCRIPT,
+                &[&self.key(key)],
+                &[
+                    (expected.is_some() as u8).to_string(),
+                    expected.unwrap_or_default(),
+                    (new.is_some() as u8).to_string(),
+                    new.unwrap_or_default(),
+                    self.ttl.unwrap_or(0).to_string(),
+                ],
+            )
+            .await?;
+
+        Ok(result.first() == Some(&1))
+    }
+
+    /// Returns a value stored as a hash, `None` if the hash is missing.
+    /// Each field of the hash is a field of `V` encoded in JSON, so the numbers can be
+    /// updated by Lua scripts. The hashes are not versioned: their fields are the format.
+    pub async fn get_fields(&self, key: &K) -> RedisRepositoryResult<Option<V>> {
+        let fields = self.repository.hget_all(&self.key(key)).await?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        let object = fields
+            .into_iter()
+            .map(|(field, value)| Ok((field, serde_json::from_str(&value)?)))
+            .collect::<RedisRepositoryResult<serde_json::Map<_, _>>>()?;
+
+        Ok(Some(serde_json::from_value(serde_json::Value::Object(
+            object,
+        ))?))
+    }
+
+    /// Stores a value as a hash, see `get_fields`, it expires after `ttl` seconds.
+    /// `V` must be encoded as a JSON object.
+    pub async fn set_fields(&self, key: &K, value: &V, ttl: i64) -> RedisRepositoryResult<()> {
+        let serde_json::Value::Object(object) = serde_json::to_value(value)? else {
+            return Err(RedisRepositoryError::SerializationError(
+                "only a JSON object can be stored as a hash".to_string(),
+            ));
+        };
+
+        let fields = object
+            .into_iter()
+            .map(|(field, value)| (field, value.to_string()))
+            .collect();
+        self.repository
+            .hset_multiple_ttl(&self.key(key), fields, ttl)
+            .await
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    use once_cell::sync::Lazy;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use serde::Deserialize;
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CONFIG: Lazy<api_configs::config::Config> = Lazy::new(api_configs::config::Config::init);
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CLIENT: Lazy<RedisClient> =
+        Lazy::new(|| crate::redis::get_redis_client(&CONFIG.clone()));
+
+    #[allow(dead_code)]
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Session {
+        id: u32,
+        email: String,
+    }
+
+    #[allow(dead_code)]
+    fn session(id: u32) -> Session {
+        Session {
+            id,
+            email: format!("user{}@test.com", id),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_set_get_with_ttl() {
+        let cache = Cache::<str, Session>::new(CLIENT.clone(), "test_cache").ttl(10);
+        cache.set("1", &session(1)).await.unwrap();
+
+        assert_eq!(cache.get("1").await.unwrap(), Some(session(1)));
+        assert_eq!(
+            CLIENT.get("test_cache:1").await.unwrap().unwrap(),
+            r#"v1:{"id":1,"email":"user1@test.com"}"#
+        );
+        assert_eq!(CLIENT.ttl("test_cache:1").await.unwrap(), 10);
+
+        cache.delete("1").await.unwrap();
+        assert_eq!(cache.get("1").await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_other_version_is_missing() {
+        let v1 = Cache::<str, Session>::new(CLIENT.clone(), "test_cache_version");
+        v1.set("1", &session(1)).await.unwrap();
+
+        let v2 = Cache::<str, Session>::new(CLIENT.clone(), "test_cache_version").version(2);
+        assert_eq!(v2.get("1").await.unwrap(), None);
+
+        // une valeur illisible de la bonne version est une erreur
+        CLIENT.set("test_cache_version:1", "v2:{}").await.unwrap();
+        assert!(matches!(
+            v2.get("1").await,
+            Err(RedisRepositoryError::SerializationError(_))
+        ));
+
+        v1.delete("1").await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_batch() {
+        let cache = Cache::<u32, Session>::new(CLIENT.clone(), "test_cache_batch").ttl(10);
+        cache
+            .set_many(&[(&1, &session(1)), (&2, &session(2))])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cache.get_many(&[&1, &3, &2]).await.unwrap(),
+            vec![Some(session(1)), None, Some(session(2))]
+        );
+        assert_eq!(cache.get_many(&[]).await.unwrap(), vec![]);
+
+        cache.delete(&1).await.unwrap();
+        cache.delete(&2).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_compare_and_set() {
+        let cache = Cache::<str, Session>::new(CLIENT.clone(), "test_cache_cas");
+        cache.delete("1").await.unwrap();
+
+        assert!(cache
+            .compare_and_set("1", None, Some(&session(1)))
+            .await
+            .unwrap());
+        // la clé existe déjà
+        assert!(!cache
+            .compare_and_set("1", None, Some(&session(2)))
+            .await
+            .unwrap());
+        // la valeur a changé
+        assert!(!cache
+            .compare_and_set("1", Some(&session(2)), Some(&session(3)))
+            .await
+            .unwrap());
+        assert!(cache
+            .compare_and_set("1", Some(&session(1)), None)
+            .await
+            .unwrap());
+        assert_eq!(cache.get("1").await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_fields() {
+        let cache = Cache::<str, Session>::new(CLIENT.clone(), "test_cache_fields");
+        cache.set_fields("1", &session(1), 10).await.unwrap();
+
+        assert_eq!(
+            CLIENT.hget("test_cache_fields:1", "id").await.unwrap(),
+            Some("1".to_string())
+        );
+        assert_eq!(cache.get_fields("1").await.unwrap(), Some(session(1)));
+        assert_eq!(cache.get_fields("2").await.unwrap(), None);
+
+        cache.delete("1").await.unwrap();
+    }
+}
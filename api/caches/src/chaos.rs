@@ -0,0 +1,335 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    errors::RedisRepositoryError,
+    redis::{RedisClient, RedisRepository, RedisRepositoryResult},
+};
+
+/// The faults injected by a `ChaosRedis`, none by default.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    /// Waited before each command.
+    pub delay: Duration,
+    /// Probability, between 0 and 1, that a command fails without reaching Redis.
+    pub error_rate: f64,
+    /// Probability, between 0 and 1, that a write succeeds without reaching Redis.
+    /// Only the writes without result can be dropped: `hincr` and `eval_ints` are not.
+    pub dropped_write_rate: f64,
+    /// The commands affected, named after the methods of `RedisRepository`, all if empty.
+    pub commands: Vec<&'static str>,
+}
+
+impl Faults {
+    fn applies_to(&self, command: &str) -> bool {
+        self.commands.is_empty() || self.commands.contains(&command)
+    }
+}
+
+/// What happens to one command.
+struct Fault {
+    delay: Duration,
+    error: bool,
+    dropped: bool,
+}
+
+/// `RedisRepository` decorator injecting delays, errors and dropped writes, to test the
+/// behavior of the services when Redis is slow or failing. The faults can be changed while
+/// the clones are used, they share them.
+#[derive(Clone)]
+pub struct ChaosRedis<R: RedisRepository = RedisClient> {
+    inner: R,
+    faults: Arc<Mutex<Faults>>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl<R: RedisRepository> ChaosRedis<R> {
+    /// Decorates a repository, without fault until `set_faults`.
+    pub fn new(inner: R) -> Self {
+        ChaosRedis {
+            inner,
+            faults: Arc::new(Mutex::new(Faults::default())),
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// Draws the faults from a seeded generator, so a failing test can be replayed.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// Replaces the injected faults, for every clone.
+    pub fn set_faults(&self, faults: Faults) {
+        *self.faults.lock().unwrap_or_else(|err| err.into_inner()) = faults;
+    }
+
+    fn draw(&self, command: &str) -> Fault {
+        let faults = self.faults.lock().unwrap_or_else(|err| err.into_inner());
+        if !faults.applies_to(command) {
+            return Fault {
+                delay: Duration::ZERO,
+                error: false,
+                dropped: false,
+            };
+        }
+
+        let mut rng = self.rng.lock().unwrap_or_else(|err| err.into_inner());
+        Fault {
+            delay: faults.delay,
+            error: rng.gen_bool(faults.error_rate.clamp(0.0, 1.0)),
+            dropped: rng.gen_bool(faults.dropped_write_rate.clamp(0.0, 1.0)),
+        }
+    }
+
+    async fn command<T>(
+        &self,
+        command: &'static str,
+        call: impl Future<Output = RedisRepositoryResult<T>>,
+    ) -> RedisRepositoryResult<T> {
+        let fault = self.draw(command);
+        if !fault.delay.is_zero() {
+            tokio::time::sleep(fault.delay).await;
+        }
+        if fault.error {
+            return Err(injected_error(command));
+        }
+
+        call.await
+    }
+
+    async fn write(
+        &self,
+        command: &'static str,
+        call: impl Future<Output = RedisRepositoryResult<()>>,
+    ) -> RedisRepositoryResult<()> {
+        let fault = self.draw(command);
+        if !fault.delay.is_zero() {
+            tokio::time::sleep(fault.delay).await;
+        }
+        if fault.error {
+            return Err(injected_error(command));
+        }
+        if fault.dropped {
+            log::debug!("Dropped the Redis command {}", command);
+            return Ok(());
+        }
+
+        call.await
+    }
+}
+
+/// The error of a failed command, like a lost connection.
+fn injected_error(command: &str) -> RedisRepositoryError {
+    RedisRepositoryError::RedisError(redis::RedisError::from((
+        redis::ErrorKind::IoError,
+        "Injected failure",
+        command.to_string(),
+    )))
+}
+
+#[async_trait::async_trait]
+impl<R: RedisRepository> RedisRepository for ChaosRedis<R> {
+    async fn ping(&self) -> RedisRepositoryResult<Option<String>> {
+        self.command("ping", self.inner.ping()).await
+    }
+
+    async fn exists(&self, key: &str) -> RedisRepositoryResult<bool> {
+        self.command("exists", self.inner.exists(key)).await
+    }
+
+    async fn get(&self, key: &str) -> RedisRepositoryResult<Option<String>> {
+        self.command("get", self.inner.get(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.write("set", self.inner.set(key, value)).await
+    }
+
+    async fn hset_multiple(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+    ) -> RedisRepositoryResult<()> {
+        self.write("hset_multiple", self.inner.hset_multiple(key, fields))
+            .await
+    }
+
+    async fn hset_multiple_ttl(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+        ttl: i64,
+    ) -> RedisRepositoryResult<()> {
+        self.write(
+            "hset_multiple_ttl",
+            self.inner.hset_multiple_ttl(key, fields, ttl),
+        )
+        .await
+    }
+
+    async fn hget_multiple(
+        &self,
+        key: &str,
+        fields: Vec<String>,
+    ) -> RedisRepositoryResult<Vec<Option<String>>> {
+        self.command("hget_multiple", self.inner.hget_multiple(key, fields))
+            .await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> RedisRepositoryResult<Vec<Option<String>>> {
+        self.command("get_many", self.inner.get_many(keys)).await
+    }
+
+    async fn set_many(
+        &self,
+        entries: Vec<(String, String)>,
+        ttl: Option<i64>,
+    ) -> RedisRepositoryResult<()> {
+        self.write("set_many", self.inner.set_many(entries, ttl))
+            .await
+    }
+
+    async fn hget_all(&self, key: &str) -> RedisRepositoryResult<Vec<(String, String)>> {
+        self.command("hget_all", self.inner.hget_all(key)).await
+    }
+
+    async fn hget(&self, key: &str, field: &str) -> RedisRepositoryResult<Option<String>> {
+        self.command("hget", self.inner.hget(key, field)).await
+    }
+
+    async fn hset(&self, key: &str, field: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.write("hset", self.inner.hset(key, field, value)).await
+    }
+
+    async fn hincr(&self, key: &str, field: &str, ttl: i64) -> RedisRepositoryResult<i64> {
+        self.command("hincr", self.inner.hincr(key, field, ttl))
+            .await
+    }
+
+    async fn expire(&self, key: &str, ttl: i64) -> RedisRepositoryResult<()> {
+        self.write("expire", self.inner.expire(key, ttl)).await
+    }
+
+    async fn ttl(&self, key: &str) -> RedisRepositoryResult<i64> {
+        self.command("ttl", self.inner.ttl(key)).await
+    }
+
+    async fn update(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.write("update", self.inner.update(key, value)).await
+    }
+
+    async fn update_ttl(&self, key: &str, value: &str, ttl: i64) -> RedisRepositoryResult<()> {
+        self.write("update_ttl", self.inner.update_ttl(key, value, ttl))
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> RedisRepositoryResult<()> {
+        self.write("delete", self.inner.delete(key)).await
+    }
+
+    async fn delete_all(&self, keys: &[&str]) -> RedisRepositoryResult<()> {
+        self.write("delete_all", self.inner.delete_all(keys)).await
+    }
+
+    async fn publish(&self, channel: &str, message: &str) -> RedisRepositoryResult<()> {
+        self.write("publish", self.inner.publish(channel, message))
+            .await
+    }
+
+    async fn scan_keys(&self, pattern: &str) -> RedisRepositoryResult<Vec<String>> {
+        self.command("scan_keys", self.inner.scan_keys(pattern))
+            .await
+    }
+
+    async fn eval_ints(
+        &self,
+        script: &redis::Script,
+        keys: &[&str],
+        args: &[String],
+    ) -> RedisRepositoryResult<Vec<i64>> {
+        self.command("eval_ints", self.inner.eval_ints(script, keys, args))
+            .await
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CLIENT: Lazy<RedisClient> =
+        Lazy::new(|| crate::redis::get_redis_client(&api_configs::config::Config::init()));
+
+    #[actix_rt::test]
+    async fn test_chaos_errors_and_dropped_writes() {
+        let redis = ChaosRedis::new(CLIENT.clone()).seed(46);
+        redis.set("test_chaos", "before").await.unwrap();
+
+        redis.set_faults(Faults {
+            dropped_write_rate: 1.0,
+            ..Default::default()
+        });
+        redis.set("test_chaos", "after").await.unwrap();
+        assert_eq!(
+            redis.get("test_chaos").await.unwrap(),
+            Some("before".to_string())
+        );
+
+        // seules les commandes listées échouent
+        redis.set_faults(Faults {
+            error_rate: 1.0,
+            commands: vec!["get"],
+            ..Default::default()
+        });
+        assert!(matches!(
+            redis.get("test_chaos").await,
+            Err(RedisRepositoryError::RedisError(_))
+        ));
+        assert!(redis.exists("test_chaos").await.unwrap());
+
+        redis.set_faults(Faults::default());
+        redis.delete("test_chaos").await.unwrap();
+        assert_eq!(redis.get("test_chaos").await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_chaos_delay() {
+        let redis = ChaosRedis::new(CLIENT.clone());
+        redis.set_faults(Faults {
+            delay: Duration::from_millis(50),
+            ..Default::default()
+        });
+
+        let start = std::time::Instant::now();
+        redis.ping().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[actix_rt::test]
+    async fn test_chaos_error_rate_is_reproducible() {
+        let failures = || async {
+            let redis = ChaosRedis::new(CLIENT.clone()).seed(7);
+            redis.set_faults(Faults {
+                error_rate: 0.5,
+                ..Default::default()
+            });
+
+            let mut failures = vec![];
+            for _ in 0..20 {
+                failures.push(redis.exists("test_chaos_rate").await.is_err());
+            }
+            failures
+        };
+
+        let first = failures().await;
+        assert_eq!(first, failures().await);
+        assert!(first.contains(&true) && first.contains(&false));
+    }
+}
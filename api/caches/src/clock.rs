@@ -0,0 +1,60 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::Utc;
+
+/// Source of the current time of the in-memory caches, Redis uses the time of its server.
+pub trait Clock: Send + Sync + 'static {
+    /// Milliseconds since the unix epoch.
+    fn now_millis(&self) -> i64;
+}
+
+/// The system time.
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// A clock moved forward by hand, to test the expirations and refills without waiting.
+/// The clones share the same time.
+#[derive(Clone)]
+pub struct ManualClock {
+    millis: Arc<AtomicI64>,
+}
+
+impl Default for ManualClock {
+    /// Starts at the current system time.
+    fn default() -> Self {
+        ManualClock::new(SystemClock.now_millis())
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at `millis` since the unix epoch.
+    pub fn new(millis: i64) -> Self {
+        ManualClock {
+            millis: Arc::new(AtomicI64::new(millis)),
+        }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        self.millis
+            .fetch_add(duration.as_millis() as i64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> i64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,279 @@
+use std::{sync::Arc, time::Duration};
+
+use api_configs::config::{RedisInfo, RedisMode};
+use redis::{
+    aio::{ConnectionLike, MultiplexedConnection, PubSub},
+    cluster::ClusterClient,
+    cluster_async::ClusterConnection,
+    cluster_routing::{RoutingInfo, SingleNodeRoutingInfo},
+    sentinel::{Sentinel, SentinelNodeConnectionInfo},
+    AsyncConnectionConfig, Cmd, FromRedisValue, Pipeline, RedisFuture, RedisResult, Value,
+};
+
+/// Opens the connections following the mode of the config.
+#[derive(Clone)]
+pub(crate) enum Connector {
+    Standalone(redis::Client),
+    /// The sentinels are asked for the address of the master on every connection,
+    /// so a failover is followed by the next reconnection.
+    Sentinel {
+        sentinel: Arc<tokio::sync::Mutex<Sentinel>>,
+        /// Name of the master watched by the sentinels.
+        master: String,
+        /// Credentials, db and tls of the master.
+        node: SentinelNodeConnectionInfo,
+    },
+    Cluster {
+        client: ClusterClient,
+        /// The urls of the seed nodes, for the pub/sub connections.
+        nodes: Vec<String>,
+    },
+}
+
+impl Connector {
+    pub(crate) fn new(redis_info: &RedisInfo) -> RedisResult<Self> {
+        let connect_timeout = Duration::from_millis(redis_info.connect_timeout);
+        let command_timeout = Duration::from_millis(redis_info.command_timeout);
+
+        Ok(match redis_info.mode {
+            RedisMode::Standalone => {
+                Connector::Standalone(redis::Client::open(redis_info.get_url())?)
+            }
+            RedisMode::Sentinel => {
+                let node = SentinelNodeConnectionInfo {
+                    tls_mode: redis_info.tls.then_some(if redis_info.tls_insecure {
+                        redis::TlsMode::Insecure
+                    } else {
+                        redis::TlsMode::Secure
+                    }),
+                    redis_connection_info: Some(redis::RedisConnectionInfo {
+                        db: redis_info.db,
+                        username: Some(redis_info.username.clone())
+                            .filter(|_| !redis_info.password.is_empty()),
+                        password: Some(redis_info.password.clone())
+                            .filter(|password| !password.is_empty()),
+                        ..Default::default()
+                    }),
+                };
+
+                Connector::Sentinel {
+                    sentinel: Arc::new(tokio::sync::Mutex::new(Sentinel::build(
+                        redis_info.node_urls(),
+                    )?)),
+                    master: redis_info.sentinel_master.clone(),
+                    node,
+                }
+            }
+            RedisMode::Cluster => Connector::Cluster {
+                client: ClusterClient::builder(redis_info.node_urls())
+                    .connection_timeout(connect_timeout)
+                    .response_timeout(command_timeout)
+                    .build()?,
+                nodes: redis_info.node_urls(),
+            },
+        })
+    }
+
+    /// Opens a connection, the config gives the timeouts of the single server connections.
+    pub(crate) async fn connect(&self, config: &AsyncConnectionConfig) -> RedisResult<Connection> {
+        match self {
+            Connector::Standalone(client) => client
+                .get_multiplexed_async_connection_with_config(config)
+                .await
+                .map(Connection::Single),
+            Connector::Sentinel {
+                sentinel,
+                master,
+                node,
+            } => sentinel
+                .lock()
+                .await
+                .async_master_for(master, Some(node))
+                .await?
+                .get_multiplexed_async_connection_with_config(config)
+                .await
+                .map(Connection::Single),
+            Connector::Cluster { client, .. } => {
+                client.get_async_connection().await.map(Connection::Cluster)
+            }
+        }
+    }
+
+    /// Opens a connection dedicated to pub/sub, it can not be shared with the commands.
+    /// In a cluster the messages published on a node are forwarded to all the others,
+    /// so the first seed node which answers is used.
+    pub(crate) async fn pubsub(&self) -> RedisResult<PubSub> {
+        match self {
+            Connector::Standalone(client) => client.get_async_pubsub().await,
+            Connector::Sentinel {
+                sentinel,
+                master,
+                node,
+            } => {
+                let client = sentinel
+                    .lock()
+                    .await
+                    .async_master_for(master, Some(node))
+                    .await?;
+                client.get_async_pubsub().await
+            }
+            Connector::Cluster { nodes, .. } => {
+                let mut last_error = None;
+                for node in nodes {
+                    match redis::Client::open(node.as_str()) {
+                        Ok(client) => match client.get_async_pubsub().await {
+                            Ok(pubsub) => return Ok(pubsub),
+                            Err(err) => last_error = Some(err),
+                        },
+                        Err(err) => last_error = Some(err),
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| {
+                    redis::RedisError::from((redis::ErrorKind::ClientError, "No cluster node"))
+                }))
+            }
+        }
+    }
+}
+
+/// Opens the pub/sub connections of a subscriber, see `RedisConnectionManager::spawn_subscriber`.
+pub(crate) struct PubSubConnector {
+    pub(crate) connector: Connector,
+    pub(crate) connect_timeout: Duration,
+}
+
+impl PubSubConnector {
+    /// Opens a pub/sub connection, it fails after the connection timeout of the config.
+    pub(crate) async fn open(&self) -> RedisResult<PubSub> {
+        tokio::time::timeout(self.connect_timeout, self.connector.pubsub())
+            .await
+            .unwrap_or_else(|_| {
+                Err(redis::RedisError::from((
+                    redis::ErrorKind::IoError,
+                    "Connection timed out",
+                )))
+            })
+    }
+}
+
+/// A connection to a single server, or to every node of a cluster.
+#[derive(Clone)]
+pub(crate) enum Connection {
+    Single(MultiplexedConnection),
+    Cluster(ClusterConnection),
+}
+
+/// A node of the deployment: `None` for the only server, the address of a master in a cluster.
+pub(crate) type Node = Option<(String, u16)>;
+
+impl Connection {
+    /// The nodes holding the keys: the server, or every master of the cluster.
+    /// The commands iterating over the keys, like SCAN, only see the keys of the node they run on.
+    pub(crate) async fn nodes(&mut self) -> RedisResult<Vec<Node>> {
+        match self {
+            Connection::Single(_) => Ok(vec![None]),
+            Connection::Cluster(con) => {
+                let nodes: String = redis::cmd("CLUSTER").arg("NODES").query_async(con).await?;
+                Ok(cluster_masters(&nodes).into_iter().map(Some).collect())
+            }
+        }
+    }
+
+    /// Sends a command to a node returned by `nodes`.
+    pub(crate) async fn query_on<T: FromRedisValue>(
+        &mut self,
+        node: &Node,
+        cmd: &Cmd,
+    ) -> RedisResult<T> {
+        match (self, node) {
+            (Connection::Cluster(con), Some((host, port))) => {
+                let routing = SingleNodeRoutingInfo::ByAddress {
+                    host: host.clone(),
+                    port: *port,
+                };
+                let value = con
+                    .route_command(cmd, RoutingInfo::SingleNode(routing))
+                    .await?;
+                T::from_redis_value(&value)
+            }
+            (con, _) => cmd.query_async(con).await,
+        }
+    }
+}
+
+impl ConnectionLike for Connection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            Connection::Single(con) => con.req_packed_command(cmd),
+            Connection::Cluster(con) => con.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            Connection::Single(con) => con.req_packed_commands(pipeline, offset, count),
+            Connection::Cluster(con) => con.req_packed_commands(pipeline, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Connection::Single(con) => con.get_db(),
+            Connection::Cluster(con) => con.get_db(),
+        }
+    }
+}
+
+/// The addresses of the masters in the reply of CLUSTER NODES, without the failing ones.
+/// A line is `<id> <ip:port@cport[,hostname]> <flags> <master> <ping> <pong> <epoch> <state> <slots>...`.
+fn cluster_masters(nodes: &str) -> Vec<(String, u16)> {
+    nodes
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let address = fields.nth(1)?;
+            let flags = fields.next()?.split(',').collect::<Vec<_>>();
+            if !flags.contains(&"master") || flags.iter().any(|flag| flag.starts_with("fail")) {
+                return None;
+            }
+
+            let address = address.split('@').next()?;
+            let (host, port) = address.rsplit_once(':')?;
+            // un nœud dont l'adresse est inconnue est noté :0
+            if host.is_empty() {
+                return None;
+            }
+            Some((host.to_string(), port.parse().ok()?))
+        })
+        .collect()
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+
+    #[test]
+    fn test_cluster_masters() {
+        let nodes = "\
+07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,node-4 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002 master - 0 1426238316232 2 connected 5461-10922
+292f8b365bb7edb5e285caf0b7e6ddc7265d2f4f 127.0.0.1:30003@31003 master - 0 1426238318243 3 connected 10923-16383
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001 myself,master - 0 0 1 connected 0-5460
+6ec23923021cf3ffec47632106199cb7f496ce01 127.0.0.1:30005@31005 master,fail - 1426238316232 1426238315232 5 disconnected
+824fe116063bc5fcf9f4ffd895bc17aee7731ac3 :0@0 master,noaddr - 0 0 0 disconnected";
+
+        assert_eq!(
+            cluster_masters(nodes),
+            vec![
+                ("127.0.0.1".to_string(), 30002),
+                ("127.0.0.1".to_string(), 30003),
+                ("127.0.0.1".to_string(), 30001),
+            ]
+        );
+    }
+}
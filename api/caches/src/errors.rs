@@ -3,6 +3,8 @@ pub enum RedisRepositoryError {
     NotFound,
     ParseIntError(std::num::ParseIntError),
     RedisError(redis::RedisError),
+    /// A value could not be encoded or decoded by a typed cache.
+    SerializationError(String),
 }
 
 impl std::fmt::Display for RedisRepositoryError {
@@ -11,6 +13,9 @@ impl std::fmt::Display for RedisRepositoryError {
             RedisRepositoryError::NotFound => write!(f, "Not Found"),
             RedisRepositoryError::RedisError(err) => write!(f, "Redis Error: {}", err),
             RedisRepositoryError::ParseIntError(err) => write!(f, "Parse Int Error: {}", err),
+            RedisRepositoryError::SerializationError(err) => {
+                write!(f, "Serialization Error: {}", err)
+            }
         }
     }
 }
@@ -23,6 +28,12 @@ impl From<redis::RedisError> for RedisRepositoryError {
     }
 }
 
+impl From<serde_json::Error> for RedisRepositoryError {
+    fn from(err: serde_json::Error) -> Self {
+        RedisRepositoryError::SerializationError(err.to_string())
+    }
+}
+
 impl From<std::num::ParseIntError> for RedisRepositoryError {
     fn from(err: std::num::ParseIntError) -> Self {
         RedisRepositoryError::ParseIntError(err)
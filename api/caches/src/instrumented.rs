@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::redis::{RedisClient, RedisRepository, RedisRepositoryResult};
+
+/// Calls, errors and latency of a command since the start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommandMetrics {
+    /// Number of calls, failed or not.
+    pub calls: u64,
+    /// Number of failed calls.
+    pub errors: u64,
+    /// Sum of the latencies of the calls.
+    pub total_latency: Duration,
+    /// Latency of the slowest call.
+    pub max_latency: Duration,
+}
+
+impl CommandMetrics {
+    /// The mean latency of the calls, zero without call.
+    pub fn mean_latency(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => self.total_latency / calls as u32,
+        }
+    }
+
+    fn record(&mut self, latency: Duration, failed: bool) {
+        self.calls += 1;
+        self.errors += failed as u64;
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+    }
+}
+
+/// `RedisRepository` decorator recording the latency and the errors of each command.
+/// The commands are named after the methods of `RedisRepository`, the clones share the metrics.
+#[derive(Clone)]
+pub struct InstrumentedRedis<R: RedisRepository = RedisClient> {
+    inner: R,
+    metrics: Arc<Mutex<HashMap<&'static str, CommandMetrics>>>,
+    /// The commands slower than this are logged.
+    slow_threshold: Option<Duration>,
+}
+
+impl<R: RedisRepository> InstrumentedRedis<R> {
+    /// Decorates a repository, without metrics yet.
+    pub fn new(inner: R) -> Self {
+        InstrumentedRedis {
+            inner,
+            metrics: Arc::new(Mutex::new(HashMap::new())),
+            slow_threshold: None,
+        }
+    }
+
+    /// Logs a warning for each command slower than `threshold`.
+    pub fn slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// The decorated repository.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// The metrics of the commands called at least once, by name.
+    pub fn metrics(&self) -> BTreeMap<&'static str, CommandMetrics> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|(command, metrics)| (*command, *metrics))
+            .collect()
+    }
+
+    /// Forgets the metrics.
+    pub fn reset(&self) {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+
+    async fn observe<T>(
+        &self,
+        command: &'static str,
+        call: impl Future<Output = RedisRepositoryResult<T>>,
+    ) -> RedisRepositoryResult<T> {
+        let start = Instant::now();
+        let result = call.await;
+        let latency = start.elapsed();
+
+        if self
+            .slow_threshold
+            .is_some_and(|threshold| latency > threshold)
+        {
+            log::warn!("Slow Redis command {}: {:?}", command, latency);
+        }
+        self.metrics
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(command)
+            .or_default()
+            .record(latency, result.is_err());
+
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: RedisRepository> RedisRepository for InstrumentedRedis<R> {
+    async fn ping(&self) -> RedisRepositoryResult<Option<String>> {
+        self.observe("ping", self.inner.ping()).await
+    }
+
+    async fn exists(&self, key: &str) -> RedisRepositoryResult<bool> {
+        self.observe("exists", self.inner.exists(key)).await
+    }
+
+    async fn get(&self, key: &str) -> RedisRepositoryResult<Option<String>> {
+        self.observe("get", self.inner.get(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.observe("set", self.inner.set(key, value)).await
+    }
+
+    async fn hset_multiple(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+    ) -> RedisRepositoryResult<()> {
+        self.observe("hset_multiple", self.inner.hset_multiple(key, fields))
+            .await
+    }
+
+    async fn hset_multiple_ttl(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+        ttl: i64,
+    ) -> RedisRepositoryResult<()> {
+        self.observe(
+            "hset_multiple_ttl",
+            self.inner.hset_multiple_ttl(key, fields, ttl),
+        )
+        .await
+    }
+
+    async fn hget_multiple(
+        &self,
+        key: &str,
+        fields: Vec<String>,
+    ) -> RedisRepositoryResult<Vec<Option<String>>> {
+        self.observe("hget_multiple", self.inner.hget_multiple(key, fields))
+            .await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> RedisRepositoryResult<Vec<Option<String>>> {
+        self.observe("get_many", self.inner.get_many(keys)).await
+    }
+
+    async fn set_many(
+        &self,
+        entries: Vec<(String, String)>,
+        ttl: Option<i64>,
+    ) -> RedisRepositoryResult<()> {
+        self.observe("set_many", self.inner.set_many(entries, ttl))
+            .await
+    }
+
+    async fn hget_all(&self, key: &str) -> RedisRepositoryResult<Vec<(String, String)>> {
+        self.observe("hget_all", self.inner.hget_all(key)).await
+    }
+
+    async fn hget(&self, key: &str, field: &str) -> RedisRepositoryResult<Option<String>> {
+        self.observe("hget", self.inner.hget(key, field)).await
+    }
+
+    async fn hset(&self, key: &str, field: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.observe("hset", self.inner.hset(key, field, value))
+            .await
+    }
+
+    async fn hincr(&self, key: &str, field: &str, ttl: i64) -> RedisRepositoryResult<i64> {
+        self.observe("hincr", self.inner.hincr(key, field, ttl))
+            .await
+    }
+
+    async fn expire(&self, key: &str, ttl: i64) -> RedisRepositoryResult<()> {
+        self.observe("expire", self.inner.expire(key, ttl)).await
+    }
+
+    async fn ttl(&self, key: &str) -> RedisRepositoryResult<i64> {
+        self.observe("ttl", self.inner.ttl(key)).await
+    }
+
+    async fn update(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
+        self.observe("update", self.inner.update(key, value)).await
+    }
+
+    async fn update_ttl(&self, key: &str, value: &str, ttl: i64) -> RedisRepositoryResult<()> {
+        self.observe("update_ttl", self.inner.update_ttl(key, value, ttl))
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> RedisRepositoryResult<()> {
+        self.observe("delete", self.inner.delete(key)).await
+    }
+
+    async fn delete_all(&self, keys: &[&str]) -> RedisRepositoryResult<()> {
+        self.observe("delete_all", self.inner.delete_all(keys))
+            .await
+    }
+
+    async fn publish(&self, channel: &str, message: &str) -> RedisRepositoryResult<()> {
+        self.observe("publish", self.inner.publish(channel, message))
+            .await
+    }
+
+    async fn scan_keys(&self, pattern: &str) -> RedisRepositoryResult<Vec<String>> {
+        self.observe("scan_keys", self.inner.scan_keys(pattern))
+            .await
+    }
+
+    async fn eval_ints(
+        &self,
+        script: &redis::Script,
+        keys: &[&str],
+        args: &[String],
+    ) -> RedisRepositoryResult<Vec<i64>> {
+        self.observe("eval_ints", self.inner.eval_ints(script, keys, args))
+            .await
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::chaos::{ChaosRedis, Faults};
+
+    #[actix_rt::test]
+    async fn test_metrics_per_command() {
+        let config = api_configs::config::Config::init();
+        let chaos = ChaosRedis::new(crate::redis::get_redis_client(&config));
+        let redis = InstrumentedRedis::new(chaos.clone());
+
+        redis.set("test_instrumented", "value").await.unwrap();
+        redis.get("test_instrumented").await.unwrap();
+        redis.get("test_instrumented").await.unwrap();
+
+        chaos.set_faults(Faults {
+            delay: Duration::from_millis(50),
+            error_rate: 1.0,
+            ..Default::default()
+        });
+        assert!(redis.get("test_instrumented").await.is_err());
+        chaos.set_faults(Faults::default());
+        redis.delete("test_instrumented").await.unwrap();
+
+        let metrics = redis.metrics();
+        assert_eq!(
+            metrics.keys().copied().collect::<Vec<_>>(),
+            vec!["delete", "get", "set"]
+        );
+        assert_eq!(metrics["get"].calls, 3);
+        assert_eq!(metrics["get"].errors, 1);
+        assert!(metrics["get"].max_latency >= Duration::from_millis(50));
+        assert!(metrics["get"].mean_latency() <= metrics["get"].max_latency);
+        assert_eq!(metrics["set"].errors, 0);
+
+        redis.reset();
+        assert!(redis.metrics().is_empty());
+    }
+}
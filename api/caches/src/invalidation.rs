@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connection::PubSubConnector,
+    redis::{
+        RedisClient, RedisRepository, RedisRepositoryResult, MAX_RECONNECT_BACKOFF,
+        RECONNECT_BACKOFF,
+    },
+};
+
+/// The pub/sub channel of the invalidations, shared by every instance of the api.
+pub const INVALIDATION_CHANNEL: &str = "cache_invalidations";
+
+/// What an in-process cache must forget.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum Invalidation {
+    /// One key of a namespace.
+    Key { namespace: String, key: String },
+    /// Every key of a namespace.
+    Namespace { namespace: String },
+}
+
+impl Invalidation {
+    /// Invalidates one key of a namespace.
+    pub fn key(namespace: &str, key: impl Display) -> Self {
+        Invalidation::Key {
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    /// Invalidates every key of a namespace.
+    pub fn all(namespace: &str) -> Self {
+        Invalidation::Namespace {
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// The namespace of the invalidated keys.
+    pub fn namespace(&self) -> &str {
+        match self {
+            Invalidation::Key { namespace, .. } | Invalidation::Namespace { namespace } => {
+                namespace
+            }
+        }
+    }
+}
+
+/// Called with each invalidation of the namespace it listens to.
+pub type InvalidationListener = Arc<dyn Fn(&Invalidation) + Send + Sync>;
+
+/// Trait for the buses propagating the invalidations of the in-process caches to every instance.
+#[async_trait::async_trait]
+pub trait InvalidationBus: Send + Sync + 'static {
+    /// Invalidates on every instance: the listeners of this instance are called before
+    /// returning, the other instances receive a message.
+    async fn publish(&self, invalidation: Invalidation) -> RedisRepositoryResult<()>;
+
+    /// Calls `listener` for each invalidation of `namespace`, published by any instance.
+    fn listen(&self, namespace: &str, listener: InvalidationListener);
+}
+
+/// The listeners by namespace.
+#[derive(Default)]
+struct Listeners {
+    by_namespace: Mutex<HashMap<String, Vec<InvalidationListener>>>,
+}
+
+impl Listeners {
+    fn add(&self, namespace: &str, listener: InvalidationListener) {
+        self.by_namespace
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(namespace.to_string())
+            .or_default()
+            .push(listener);
+    }
+
+    fn notify(&self, invalidation: &Invalidation) {
+        // un listener peut en ajouter un autre, ils sont appelés sans le verrou
+        let listeners = self
+            .by_namespace
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(invalidation.namespace())
+            .cloned()
+            .unwrap_or_default();
+
+        for listener in listeners {
+            listener(invalidation);
+        }
+    }
+
+    fn namespaces(&self) -> Vec<String> {
+        self.by_namespace
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
+/// A message of the channel, the instance which published it ignores it.
+#[derive(Serialize, Deserialize)]
+struct Message {
+    origin: String,
+    #[serde(flatten)]
+    invalidation: Invalidation,
+}
+
+/// Redis-based implementation of `InvalidationBus`, the invalidations are published as JSON
+/// on `INVALIDATION_CHANNEL`.
+///
+/// The subscription is started by `start` on startup. A message published while an instance
+/// is disconnected is lost for it, so after a resubscription every namespace listened to is
+/// invalidated.
+pub struct InvalidationBusRedis {
+    client: RedisClient,
+    /// Identifies the messages of this instance.
+    origin: String,
+    listeners: Arc<Listeners>,
+    subscribed: Arc<AtomicBool>,
+    started: AtomicBool,
+}
+
+impl InvalidationBusRedis {
+    /// Creates a new instance of `InvalidationBusRedis`, not subscribed yet.
+    ///
+    /// # Arguments
+    /// * `client` - The Redis client instance.
+    ///
+    /// # Returns
+    /// A new `InvalidationBusRedis` instance.
+    pub fn new(client: RedisClient) -> Self {
+        InvalidationBusRedis {
+            client,
+            origin: uuid::Uuid::new_v4().to_string(),
+            listeners: Arc::new(Listeners::default()),
+            subscribed: Arc::new(AtomicBool::new(false)),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Subscribes to the invalidations of the other instances, in the background until the
+    /// Redis client is dropped. The subscription is retried with a backoff when it fails or
+    /// breaks. Calling it again does nothing.
+    pub fn start(&self) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let origin = self.origin.clone();
+        let listeners = Arc::clone(&self.listeners);
+        let subscribed = Arc::clone(&self.subscribed);
+        self.client
+            .spawn_subscriber(move |connector| subscribe(connector, origin, listeners, subscribed));
+    }
+
+    /// Whether the invalidations of the other instances are received.
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribed.load(Ordering::Relaxed)
+    }
+}
+
+/// Receives the invalidations until the runtime of the Redis client stops.
+async fn subscribe(
+    connector: PubSubConnector,
+    origin: String,
+    listeners: Arc<Listeners>,
+    subscribed: Arc<AtomicBool>,
+) {
+    let mut backoff = RECONNECT_BACKOFF;
+    let mut resubscription = false;
+
+    loop {
+        let pubsub = match connector.open().await {
+            Ok(mut pubsub) => pubsub.subscribe(INVALIDATION_CHANNEL).await.map(|_| pubsub),
+            Err(err) => Err(err),
+        };
+        let pubsub = match pubsub {
+            Ok(pubsub) => pubsub,
+            Err(err) => {
+                log::error!(
+                    "Failed to subscribe to the cache invalidations, retry in {:?}: {}",
+                    backoff,
+                    err
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                continue;
+            }
+        };
+
+        log::info!("Subscribed to the cache invalidations");
+        backoff = RECONNECT_BACKOFF;
+        subscribed.store(true, Ordering::Relaxed);
+        if resubscription {
+            // les invalidations publiées pendant la coupure sont perdues
+            for namespace in listeners.namespaces() {
+                listeners.notify(&Invalidation::all(&namespace));
+            }
+        }
+        resubscription = true;
+
+        let mut messages = pubsub.into_on_message();
+        while let Some(message) = messages.next().await {
+            let message = message
+                .get_payload::<String>()
+                .map_err(|err| err.to_string())
+                .and_then(|payload| {
+                    serde_json::from_str::<Message>(&payload).map_err(|err| err.to_string())
+                });
+            match message {
+                Ok(message) if message.origin == origin => {}
+                Ok(message) => listeners.notify(&message.invalidation),
+                Err(err) => log::warn!("Invalid cache invalidation message: {}", err),
+            }
+        }
+
+        subscribed.store(false, Ordering::Relaxed);
+        log::warn!("Lost the subscription to the cache invalidations, resubscribing");
+    }
+}
+
+#[async_trait::async_trait]
+impl InvalidationBus for InvalidationBusRedis {
+    async fn publish(&self, invalidation: Invalidation) -> RedisRepositoryResult<()> {
+        self.listeners.notify(&invalidation);
+
+        let message = serde_json::to_string(&Message {
+            origin: self.origin.clone(),
+            invalidation,
+        })?;
+        self.client.publish(INVALIDATION_CHANNEL, &message).await
+    }
+
+    fn listen(&self, namespace: &str, listener: InvalidationListener) {
+        self.listeners.add(namespace, listener);
+    }
+}
+
+/// In-process implementation of `InvalidationBus`, for the tests and the deployments with a
+/// single instance of the api.
+#[derive(Default)]
+pub struct InvalidationBusMemory {
+    listeners: Listeners,
+}
+
+#[async_trait::async_trait]
+impl InvalidationBus for InvalidationBusMemory {
+    async fn publish(&self, invalidation: Invalidation) -> RedisRepositoryResult<()> {
+        self.listeners.notify(&invalidation);
+        Ok(())
+    }
+
+    fn listen(&self, namespace: &str, listener: InvalidationListener) {
+        self.listeners.add(namespace, listener);
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::time::Duration;
+
+    /// A listener keeping the invalidations it receives.
+    #[allow(dead_code)]
+    fn recorder() -> (Arc<Mutex<Vec<Invalidation>>>, InvalidationListener) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let listener = {
+            let received = Arc::clone(&received);
+            Arc::new(move |invalidation: &Invalidation| {
+                received.lock().unwrap().push(invalidation.clone())
+            })
+        };
+        (received, listener)
+    }
+
+    #[test]
+    fn test_message_format() {
+        let message = Message {
+            origin: "instance".to_string(),
+            invalidation: Invalidation::key("user_profiles", 1),
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"origin":"instance","scope":"key","namespace":"user_profiles","key":"1"}"#
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_bus_notifies_the_namespace() {
+        let bus = InvalidationBusMemory::default();
+        let (received, listener) = recorder();
+        bus.listen("test_memory_bus", listener);
+
+        bus.publish(Invalidation::key("test_memory_bus", "a"))
+            .await
+            .unwrap();
+        bus.publish(Invalidation::all("other")).await.unwrap();
+        bus.publish(Invalidation::all("test_memory_bus"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                Invalidation::key("test_memory_bus", "a"),
+                Invalidation::all("test_memory_bus")
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_bus_between_instances() {
+        let config = api_configs::config::Config::init();
+        let client = crate::redis::get_redis_client(&config);
+        // deux instances de l'api sur le même Redis
+        let first = InvalidationBusRedis::new(Arc::clone(&client));
+        let second = InvalidationBusRedis::new(Arc::clone(&client));
+
+        let (first_received, listener) = recorder();
+        first.listen("test_redis_bus", listener);
+        let (second_received, listener) = recorder();
+        second.listen("test_redis_bus", listener);
+
+        first.start();
+        second.start();
+        for _ in 0..100 {
+            if first.is_subscribed() && second.is_subscribed() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(first.is_subscribed() && second.is_subscribed());
+
+        first
+            .publish(Invalidation::key("test_redis_bus", 1))
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if !second_received.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(
+            *second_received.lock().unwrap(),
+            vec![Invalidation::key("test_redis_bus", 1)]
+        );
+        // son propre message est ignoré
+        assert_eq!(
+            *first_received.lock().unwrap(),
+            vec![Invalidation::key("test_redis_bus", 1)]
+        );
+    }
+}
@@ -1,6 +1,21 @@
 pub mod errors;
 
+mod connection;
 pub mod redis;
 
+pub mod chaos;
+pub mod instrumented;
+
+pub mod cache;
+pub mod invalidation;
+
+pub mod clock;
+pub mod memory;
+pub mod single_flight;
+
 pub mod access_refresh_tokens;
+pub mod bans;
+pub mod login_attempts;
+pub mod rate_limit_algorithms;
 pub mod token_buckets;
+pub mod user_profiles;
@@ -0,0 +1,263 @@
+use chrono::Utc;
+
+use crate::redis::{hash_tag, RedisClient, RedisRepository, RedisRepositoryResult};
+
+/// A lockout level is forgotten after one day without a new lockout.
+const LOCKOUT_LEVEL_TTL: i64 = 24 * 60 * 60;
+
+/// What is locked after too many failed login attempts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockoutScope {
+    /// The account, whatever the ip of the attempt.
+    Account,
+    /// The account, only for one ip.
+    IpAccount(String),
+}
+
+impl LockoutScope {
+    /// Field of the Redis hashes of the account used for this scope.
+    fn field(&self) -> String {
+        match self {
+            LockoutScope::Account => "account".to_string(),
+            LockoutScope::IpAccount(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+/// Number of recent failed attempts on an account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailedAttempts {
+    /// Attempts from every ip.
+    pub account: i64,
+    /// Attempts from the ip of the current request.
+    pub ip_account: i64,
+}
+
+/// Trait for tracking the failed login attempts and the lockouts of the accounts.
+/// An account is identified by the email used to log in, it does not have to exist.
+#[async_trait::async_trait]
+pub trait LoginAttemptsCache: Clone + Send + Sync + 'static {
+    /// Returns the unix timestamp until which the account is locked for this ip, if it is.
+    async fn locked_until(&self, email: &str, ip: &str) -> RedisRepositoryResult<Option<i64>>;
+
+    /// Records a failed attempt and returns the number of recent failed attempts.
+    ///
+    /// # Arguments
+    /// * `window` - Number of seconds after which the attempts are forgotten.
+    async fn record_failure(
+        &self,
+        email: &str,
+        ip: &str,
+        window: i64,
+    ) -> RedisRepositoryResult<FailedAttempts>;
+
+    /// Locks the scope and resets its attempts, the duration doubles at each lockout.
+    ///
+    /// # Returns
+    /// The duration of the lockout in seconds.
+    async fn start_lockout(
+        &self,
+        email: &str,
+        scope: &LockoutScope,
+        base_duration: i64,
+        max_duration: i64,
+    ) -> RedisRepositoryResult<i64>;
+
+    /// Forgets the failed attempts of the account after a successful login.
+    async fn clear_failures(&self, email: &str) -> RedisRepositoryResult<()>;
+
+    /// Removes every lockout and failed attempt of the account.
+    async fn unlock(&self, email: &str) -> RedisRepositoryResult<()>;
+}
+
+/// Compute the duration of the n-th lockout (starting at 1).
+pub fn lockout_duration(level: i64, base_duration: i64, max_duration: i64) -> i64 {
+    let exponent = (level - 1).clamp(0, 32) as u32;
+
+    base_duration
+        .saturating_mul(2_i64.saturating_pow(exponent))
+        .min(max_duration)
+}
+
+/// Redis-based implementation of `LoginAttemptsCache`.
+/// Each account has two hashes, one for the failed attempts and one for the lockouts,
+/// with a field per scope, so that an unlock removes everything at once.
+/// The two hashes share the hash tag of the email, so they are on the same node of a cluster.
+#[derive(Clone)]
+pub struct LoginAttemptsCacheRedis<R: RedisRepository = RedisClient> {
+    /// Redis client instance.
+    client: R,
+}
+
+impl<R: RedisRepository> LoginAttemptsCacheRedis<R> {
+    /// Creates a new instance of `LoginAttemptsCacheRedis`.
+    ///
+    /// # Arguments
+    /// * `client` - The Redis client instance.
+    ///
+    /// # Returns
+    /// A new `LoginAttemptsCacheRedis` instance.
+    pub fn new(client: R) -> Self {
+        LoginAttemptsCacheRedis { client }
+    }
+
+    fn failures_key(email: &str) -> String {
+        format!("login_failures:{}", hash_tag(email))
+    }
+
+    fn lockouts_key(email: &str) -> String {
+        format!("login_lockouts:{}", hash_tag(email))
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: RedisRepository> LoginAttemptsCache for LoginAttemptsCacheRedis<R> {
+    async fn locked_until(&self, email: &str, ip: &str) -> RedisRepositoryResult<Option<i64>> {
+        let now = Utc::now().timestamp();
+
+        let locked_until = self
+            .client
+            .hget_multiple(
+                &Self::lockouts_key(email),
+                vec![
+                    format!("until:{}", LockoutScope::Account.field()),
+                    format!("until:{}", LockoutScope::IpAccount(ip.to_string()).field()),
+                ],
+            )
+            .await?
+            .into_iter()
+            .flatten()
+            .filter_map(|until| until.parse::<i64>().ok())
+            .filter(|until| *until > now)
+            .max();
+
+        Ok(locked_until)
+    }
+
+    async fn record_failure(
+        &self,
+        email: &str,
+        ip: &str,
+        window: i64,
+    ) -> RedisRepositoryResult<FailedAttempts> {
+        let key = Self::failures_key(email);
+
+        Ok(FailedAttempts {
+            account: self
+                .client
+                .hincr(&key, &LockoutScope::Account.field(), window)
+                .await?,
+            ip_account: self
+                .client
+                .hincr(
+                    &key,
+                    &LockoutScope::IpAccount(ip.to_string()).field(),
+                    window,
+                )
+                .await?,
+        })
+    }
+
+    async fn start_lockout(
+        &self,
+        email: &str,
+        scope: &LockoutScope,
+        base_duration: i64,
+        max_duration: i64,
+    ) -> RedisRepositoryResult<i64> {
+        let key = Self::lockouts_key(email);
+        let field = scope.field();
+
+        let level = self
+            .client
+            .hincr(
+                &key,
+                &format!("level:{}", field),
+                LOCKOUT_LEVEL_TTL.max(max_duration),
+            )
+            .await?;
+        let duration = lockout_duration(level, base_duration, max_duration);
+
+        self.client
+            .hset(
+                &key,
+                &format!("until:{}", field),
+                &(Utc::now().timestamp() + duration).to_string(),
+            )
+            .await?;
+
+        // the next attempts after the lockout start from zero
+        self.client
+            .hset(&Self::failures_key(email), &field, "0")
+            .await?;
+
+        Ok(duration)
+    }
+
+    async fn clear_failures(&self, email: &str) -> RedisRepositoryResult<()> {
+        self.client.delete(&Self::failures_key(email)).await
+    }
+
+    async fn unlock(&self, email: &str) -> RedisRepositoryResult<()> {
+        self.client
+            .delete_all(&[&Self::failures_key(email), &Self::lockouts_key(email)])
+            .await
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CONFIG: Lazy<api_configs::config::Config> = Lazy::new(api_configs::config::Config::init);
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    static CLIENT: Lazy<RedisClient> =
+        Lazy::new(|| crate::redis::get_redis_client(&CONFIG.clone()));
+
+    #[test]
+    fn test_lockout_duration_grows_exponentially() {
+        assert_eq!(lockout_duration(1, 60, 3600), 60);
+        assert_eq!(lockout_duration(2, 60, 3600), 120);
+        assert_eq!(lockout_duration(3, 60, 3600), 240);
+        assert_eq!(lockout_duration(10, 60, 3600), 3600);
+        assert_eq!(lockout_duration(1000, 60, 3600), 3600);
+    }
+
+    #[actix_rt::test]
+    async fn test_lockout_and_unlock() {
+        let cache = LoginAttemptsCacheRedis::new(CLIENT.clone());
+        let email = "lockout@test.com";
+        let ip = "10.0.0.1";
+
+        cache.unlock(email).await.unwrap();
+
+        let attempts = cache.record_failure(email, ip, 60).await.unwrap();
+        assert_eq!(
+            attempts,
+            FailedAttempts {
+                account: 1,
+                ip_account: 1
+            }
+        );
+        assert_eq!(cache.locked_until(email, ip).await.unwrap(), None);
+
+        let scope = LockoutScope::IpAccount(ip.to_string());
+        assert_eq!(
+            cache.start_lockout(email, &scope, 60, 3600).await.unwrap(),
+            60
+        );
+        assert_eq!(
+            cache.start_lockout(email, &scope, 60, 3600).await.unwrap(),
+            120
+        );
+
+        assert!(cache.locked_until(email, ip).await.unwrap().is_some());
+        // only this ip is locked
+        assert_eq!(cache.locked_until(email, "10.0.0.2").await.unwrap(), None);
+
+        cache.unlock(email).await.unwrap();
+        assert_eq!(cache.locked_until(email, ip).await.unwrap(), None);
+    }
+}
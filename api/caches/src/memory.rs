@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// Number of writes between two removals of the expired entries.
+const SWEEP_INTERVAL: usize = 1024;
+
+struct Entry<V> {
+    value: V,
+    /// Milliseconds since the unix epoch, `None` for an entry without ttl.
+    expires_at: Option<i64>,
+}
+
+struct Entries<V> {
+    map: HashMap<String, Entry<V>>,
+    writes: usize,
+}
+
+/// Thread-safe key-value store with the ttl semantics of Redis, used by the in-memory caches.
+/// An expired entry is never returned, the expired entries are removed from time to time.
+pub struct MemoryStore<V> {
+    entries: Mutex<Entries<V>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<V: Clone> Default for MemoryStore<V> {
+    fn default() -> Self {
+        MemoryStore::new(Arc::new(SystemClock))
+    }
+}
+
+impl<V: Clone> MemoryStore<V> {
+    /// Creates an empty store reading the time from `clock`.
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        MemoryStore {
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                writes: 0,
+            }),
+            clock,
+        }
+    }
+
+    /// The current time of the store in milliseconds.
+    pub fn now_millis(&self) -> i64 {
+        self.clock.now_millis()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries<V>> {
+        // une valeur écrite avant un panic reste cohérente, le verrou est récupéré
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the value of a key, if it has not expired.
+    pub fn get(&self, key: &str) -> Option<V> {
+        let now = self.now_millis();
+        let entries = self.lock();
+        entries
+            .map
+            .get(key)
+            .filter(|entry| !expired(entry, now))
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Sets the value of a key, it expires after `ttl_ms` milliseconds if given.
+    pub fn set(&self, key: &str, value: V, ttl_ms: Option<i64>) {
+        self.update(key, |_, _| (Some((value, ttl_ms)), ()))
+    }
+
+    /// Reads then writes a key atomically.
+    /// `f` receives the value of the key if it has not expired and the current time, it returns
+    /// the new value with its ttl in milliseconds, or `None` to leave the key unchanged.
+    pub fn update<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(Option<&V>, i64) -> (Option<(V, Option<i64>)>, R),
+    ) -> R {
+        let now = self.now_millis();
+        let mut entries = self.lock();
+
+        let current = entries
+            .map
+            .get(key)
+            .filter(|entry| !expired(entry, now))
+            .map(|entry| &entry.value);
+        let (value, result) = f(current, now);
+
+        if let Some((value, ttl_ms)) = value {
+            entries.map.insert(
+                key.to_string(),
+                Entry {
+                    value,
+                    expires_at: ttl_ms.map(|ttl_ms| now + ttl_ms.max(1)),
+                },
+            );
+
+            entries.writes += 1;
+            if entries.writes.is_multiple_of(SWEEP_INTERVAL) {
+                entries.map.retain(|_, entry| !expired(entry, now));
+            }
+        }
+
+        result
+    }
+
+    /// Removes a key, returns its value if it had not expired.
+    pub fn remove(&self, key: &str) -> Option<V> {
+        let now = self.now_millis();
+        self.lock()
+            .map
+            .remove(key)
+            .filter(|entry| !expired(entry, now))
+            .map(|entry| entry.value)
+    }
+
+    /// Removes every key.
+    pub fn clear(&self) {
+        self.lock().map.clear();
+    }
+
+    /// Milliseconds before a key expires, like the PTTL of Redis: -1 without ttl, -2 if missing.
+    pub fn ttl(&self, key: &str) -> i64 {
+        let now = self.now_millis();
+        match self.lock().map.get(key) {
+            Some(entry) if expired(entry, now) => -2,
+            Some(entry) => entry.expires_at.map_or(-1, |expires_at| expires_at - now),
+            None => -2,
+        }
+    }
+
+    /// Removes the expired entries and the ones without ttl, returns the number of entries
+    /// without ttl removed.
+    pub fn remove_without_ttl(&self) -> u64 {
+        let now = self.now_millis();
+        let mut entries = self.lock();
+
+        let mut removed = 0;
+        entries.map.retain(|_, entry| match entry.expires_at {
+            Some(expires_at) => expires_at > now,
+            None => {
+                removed += 1;
+                false
+            }
+        });
+
+        removed
+    }
+}
+
+fn expired<V>(entry: &Entry<V>, now: i64) -> bool {
+    entry.expires_at.is_some_and(|expires_at| expires_at <= now)
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::clock::ManualClock;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::time::Duration;
+
+    #[test]
+    fn test_entries_expire_with_the_clock() {
+        let clock = ManualClock::default();
+        let store = MemoryStore::new(Arc::new(clock.clone()));
+
+        store.set("short", 1, Some(1_000));
+        store.set("forever", 2, None);
+        assert_eq!(store.ttl("short"), 1_000);
+        assert_eq!(store.ttl("forever"), -1);
+        assert_eq!(store.ttl("missing"), -2);
+
+        clock.advance(Duration::from_millis(999));
+        assert_eq!(store.get("short"), Some(1));
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(store.get("short"), None);
+        assert_eq!(store.ttl("short"), -2);
+        assert_eq!(store.get("forever"), Some(2));
+
+        assert_eq!(store.remove_without_ttl(), 1);
+        assert_eq!(store.get("forever"), None);
+    }
+
+    #[test]
+    fn test_update_can_leave_the_entry_unchanged() {
+        let store = MemoryStore::new(Arc::new(ManualClock::default()));
+        store.set("counter", 1, Some(1_000));
+
+        let seen = store.update("counter", |value, _| (None, value.copied()));
+        assert_eq!(seen, Some(1));
+        assert_eq!(store.ttl("counter"), 1_000);
+
+        store.update("counter", |value, _| (Some((value.unwrap() + 1, None)), ()));
+        assert_eq!(store.get("counter"), Some(2));
+        assert_eq!(store.ttl("counter"), -1);
+    }
+}
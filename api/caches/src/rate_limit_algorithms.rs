@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use api_configs::rate_limit::{RateLimitAlgorithmKind, RateLimitPolicy};
+use once_cell::sync::Lazy;
+
+/// Refill and consumption of a bucket in a single atomic call.
+static TOKEN_BUCKET_SCRIPT: Lazy<redis::Script> =
+    Lazy::new(|| redis::Script::new(include_str!("scripts/token_bucket.lua")));
+static SLIDING_WINDOW_SCRIPT: Lazy<redis::Script> =
+    Lazy::new(|| redis::Script::new(include_str!("scripts/sliding_window.lua")));
+static GCRA_SCRIPT: Lazy<redis::Script> =
+    Lazy::new(|| redis::Script::new(include_str!("scripts/gcra.lua")));
+
+/// The fields of the hash of a caller, kept in memory by the in-process caches.
+pub type BucketFields = HashMap<String, f64>;
+
+/// A rate limit algorithm running on Redis, or in process for the in-memory caches.
+/// The script counts and consumes the cost of a request atomically, it returns
+/// `{ allowed, remaining, retry_after_ms, reset_after_ms }`.
+pub trait RateLimitAlgorithm: Send + Sync {
+    /// The Lua script of the algorithm, its only key is the state of the caller.
+    fn script(&self) -> &redis::Script;
+
+    /// The arguments of the script for a policy.
+    fn args(&self, policy: &RateLimitPolicy) -> Vec<String> {
+        vec![
+            policy.capacity.to_string(),
+            policy.refill_amount.to_string(),
+            (policy.refill_period * 1000).to_string(),
+            policy.cost.to_string(),
+        ]
+    }
+
+    /// The script run in process on the fields of the caller at `now` in milliseconds.
+    /// Returns the reply of the script and the ttl in milliseconds of the updated fields,
+    /// or `None` when the fields are left unchanged.
+    fn consume_in_memory(
+        &self,
+        fields: &mut BucketFields,
+        policy: &RateLimitPolicy,
+        now: i64,
+    ) -> (Vec<i64>, Option<i64>);
+}
+
+/// The arguments of the scripts as numbers: capacity, refill amount, refill period in milliseconds
+/// and cost.
+fn numbers(policy: &RateLimitPolicy) -> (f64, f64, f64, f64) {
+    (
+        policy.capacity as f64,
+        policy.refill_amount as f64,
+        (policy.refill_period * 1000) as f64,
+        policy.cost as f64,
+    )
+}
+
+/// Tokens refilled continuously, allows bursts up to the capacity.
+pub struct TokenBucketAlgorithm;
+
+impl RateLimitAlgorithm for TokenBucketAlgorithm {
+    fn script(&self) -> &redis::Script {
+        &TOKEN_BUCKET_SCRIPT
+    }
+
+    /// Same as `scripts/token_bucket.lua`.
+    fn consume_in_memory(
+        &self,
+        fields: &mut BucketFields,
+        policy: &RateLimitPolicy,
+        now: i64,
+    ) -> (Vec<i64>, Option<i64>) {
+        let (capacity, refill_amount, refill_period, cost) = numbers(policy);
+        let now = now as f64;
+
+        let (mut tokens, mut last_refill_time) =
+            match (fields.get("tokens"), fields.get("last_refill_time")) {
+                (Some(tokens), Some(last_refill_time)) => (*tokens, *last_refill_time),
+                _ => (capacity, now),
+            };
+
+        let added = ((now - last_refill_time).max(0.0) * refill_amount / refill_period).floor();
+        if tokens + added >= capacity {
+            tokens = capacity;
+            last_refill_time = now;
+        } else if added > 0.0 {
+            tokens += added;
+            last_refill_time += (added * refill_period / refill_amount).floor();
+        }
+
+        let mut allowed = 0;
+        let mut retry_after = 0.0;
+        if tokens >= cost {
+            allowed = 1;
+            tokens -= cost;
+        } else {
+            retry_after = (last_refill_time + (cost - tokens) * refill_period / refill_amount
+                - now)
+                .ceil()
+                .max(0.0);
+        }
+
+        let reset_after = (last_refill_time + (capacity - tokens) * refill_period / refill_amount
+            - now)
+            .ceil()
+            .max(0.0);
+
+        fields.insert("capacity".to_string(), capacity);
+        fields.insert("tokens".to_string(), tokens);
+        fields.insert("last_refill_time".to_string(), last_refill_time);
+        let ttl = (capacity * refill_period / refill_amount).ceil().max(1.0);
+
+        (
+            vec![
+                allowed,
+                tokens as i64,
+                retry_after as i64,
+                reset_after as i64,
+            ],
+            Some(ttl as i64),
+        )
+    }
+}
+
+/// Counters of the current and previous windows, the previous one weighted by its overlap
+/// with the sliding window.
+pub struct SlidingWindowAlgorithm;
+
+impl RateLimitAlgorithm for SlidingWindowAlgorithm {
+    fn script(&self) -> &redis::Script {
+        &SLIDING_WINDOW_SCRIPT
+    }
+
+    /// Same as `scripts/sliding_window.lua`.
+    fn consume_in_memory(
+        &self,
+        fields: &mut BucketFields,
+        policy: &RateLimitPolicy,
+        now: i64,
+    ) -> (Vec<i64>, Option<i64>) {
+        let (capacity, refill_amount, refill_period, cost) = numbers(policy);
+        let window = capacity * refill_period / refill_amount;
+        let now = now as f64;
+
+        let index = (now / window).floor();
+        let elapsed = now - index * window;
+
+        let mut current = fields.get("current").copied().unwrap_or(0.0);
+        let mut previous = fields.get("previous").copied().unwrap_or(0.0);
+        match fields.get("window").copied() {
+            Some(stored_index) if stored_index == index - 1.0 => {
+                previous = current;
+                current = 0.0;
+            }
+            Some(stored_index) if stored_index >= index => {}
+            _ => {
+                current = 0.0;
+                previous = 0.0;
+            }
+        }
+
+        let mut estimate = previous * (window - elapsed) / window + current;
+
+        let mut allowed = 0;
+        let mut retry_after = 0.0;
+        if estimate + cost <= capacity {
+            allowed = 1;
+            current += cost;
+            estimate += cost;
+        } else if current + cost <= capacity {
+            // wait until the weight of the previous window is low enough
+            retry_after = window * (1.0 - (capacity - current - cost) / previous) - elapsed;
+        } else if cost <= capacity {
+            // wait for the next window, where the current window becomes the previous one
+            retry_after = window - elapsed + window * (1.0 - (capacity - cost) / current);
+        } else {
+            // a request costing more than the capacity is never allowed
+            retry_after = 2.0 * window;
+        }
+
+        let reset_after = if current > 0.0 {
+            2.0 * window - elapsed
+        } else if previous > 0.0 {
+            window - elapsed
+        } else {
+            0.0
+        };
+
+        fields.insert("window".to_string(), index);
+        fields.insert("current".to_string(), current);
+        fields.insert("previous".to_string(), previous);
+        let ttl = (2.0 * window).ceil().max(1.0);
+
+        (
+            vec![
+                allowed,
+                (capacity - estimate).floor().max(0.0) as i64,
+                retry_after.ceil().max(0.0) as i64,
+                reset_after.ceil() as i64,
+            ],
+            Some(ttl as i64),
+        )
+    }
+}
+
+/// Generic cell rate algorithm, a single timestamp per caller.
+pub struct GcraAlgorithm;
+
+impl RateLimitAlgorithm for GcraAlgorithm {
+    fn script(&self) -> &redis::Script {
+        &GCRA_SCRIPT
+    }
+
+    /// Same as `scripts/gcra.lua`.
+    fn consume_in_memory(
+        &self,
+        fields: &mut BucketFields,
+        policy: &RateLimitPolicy,
+        now: i64,
+    ) -> (Vec<i64>, Option<i64>) {
+        let (capacity, refill_amount, refill_period, cost) = numbers(policy);
+        let now = now as f64;
+
+        let interval = refill_period / refill_amount;
+        let burst = interval * capacity;
+
+        let mut tat = fields.get("tat").copied().unwrap_or(now).max(now);
+        let new_tat = tat + interval * cost;
+        let allow_at = new_tat - burst;
+
+        let mut allowed = 0;
+        let mut retry_after = 0.0;
+        let mut ttl = None;
+        if now >= allow_at {
+            allowed = 1;
+            tat = new_tat;
+            fields.insert("tat".to_string(), tat);
+            // once the tat is reached, the state is the same as a new caller
+            ttl = Some((tat - now).ceil().max(1.0) as i64);
+        } else {
+            retry_after = allow_at - now;
+        }
+
+        (
+            vec![
+                allowed,
+                ((burst - (tat - now)) / interval).floor().max(0.0) as i64,
+                retry_after.ceil() as i64,
+                (tat - now).ceil() as i64,
+            ],
+            ttl,
+        )
+    }
+}
+
+/// The implementation of an algorithm of the config.
+pub fn algorithm(kind: RateLimitAlgorithmKind) -> &'static dyn RateLimitAlgorithm {
+    match kind {
+        RateLimitAlgorithmKind::TokenBucket => &TokenBucketAlgorithm,
+        RateLimitAlgorithmKind::SlidingWindow => &SlidingWindowAlgorithm,
+        RateLimitAlgorithmKind::Gcra => &GcraAlgorithm,
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::{
+        redis::{RedisClient, RedisRepository},
+        token_buckets::{TokenBucketsCache, TokenBucketsCacheMemory, TokenBucketsCacheRedis},
+    };
+
+    #[allow(dead_code)]
+    static CONFIG: Lazy<api_configs::config::Config> = Lazy::new(api_configs::config::Config::init);
+    #[allow(dead_code)]
+    static CLIENT: Lazy<RedisClient> =
+        Lazy::new(|| crate::redis::get_redis_client(&CONFIG.clone()));
+
+    #[actix_rt::test]
+    async fn test_sliding_window_allows_the_quota_of_the_window() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-sliding-window";
+        CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+
+        // 5 requests per minute
+        let policy = policy(RateLimitAlgorithmKind::SlidingWindow, 5, 5, 60);
+
+        for remaining in (0..5).rev() {
+            let status = cache.consume_tokens(id, &policy).await.unwrap();
+            assert!(status.allowed);
+            assert_eq!(status.remaining, remaining);
+        }
+
+        let status = cache.consume_tokens(id, &policy).await.unwrap();
+        assert!(!status.allowed);
+        assert_eq!(status.remaining, 0);
+        // the requests are counted until the end of the next window
+        assert!(status.retry_after_ms > 0 && status.retry_after_ms <= 120_000);
+        assert!(status.reset_after_ms > 60_000 && status.reset_after_ms <= 120_000);
+
+        CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_sliding_window_weights_the_previous_window() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-sliding-window-previous";
+        let key = format!("ratelimit:{}", id);
+
+        // windows of 10 minutes, the previous one is full
+        let policy = policy(RateLimitAlgorithmKind::SlidingWindow, 10, 10, 600);
+        let now = chrono::Utc::now().timestamp_millis();
+        let index = now / 600_000;
+        CLIENT
+            .hset_multiple(
+                &key,
+                vec![
+                    ("window".to_string(), (index - 1).to_string()),
+                    ("current".to_string(), "10".to_string()),
+                    ("previous".to_string(), "0".to_string()),
+                ],
+            )
+            .await
+            .unwrap();
+
+        // ce qui reste de la fenêtre précédente compte encore
+        let elapsed = now - index * 600_000;
+        let weighted = (10 * (600_000 - elapsed)) as f64 / 600_000.0;
+        let expected = (10.0 - weighted).floor() as u64;
+
+        let mut allowed = 0;
+        for _ in 0..10 {
+            if cache.consume_tokens(id, &policy).await.unwrap().allowed {
+                allowed += 1;
+            }
+        }
+        assert!(allowed == expected || allowed == expected + 1);
+
+        CLIENT.delete(&key).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_gcra_spaces_the_requests_after_the_burst() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-gcra";
+        CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+
+        // a burst of 3, then one request every 20 seconds
+        let policy = policy(RateLimitAlgorithmKind::Gcra, 3, 1, 20);
+
+        for remaining in (0..3).rev() {
+            let status = cache.consume_tokens(id, &policy).await.unwrap();
+            assert!(status.allowed);
+            assert_eq!(status.remaining, remaining);
+        }
+
+        let status = cache.consume_tokens(id, &policy).await.unwrap();
+        assert!(!status.allowed);
+        assert!(status.retry_after_ms > 19_000 && status.retry_after_ms <= 20_000);
+        assert!(status.reset_after_ms > 59_000 && status.reset_after_ms <= 60_000);
+
+        // a refused request is not counted
+        let again = cache.consume_tokens(id, &policy).await.unwrap();
+        assert!(again.retry_after_ms <= status.retry_after_ms);
+
+        CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_algorithms_give_the_same_answers_as_redis() {
+        let redis = TokenBucketsCacheRedis::new(CLIENT.clone());
+        let memory = TokenBucketsCacheMemory::default();
+
+        let policies = [
+            policy(RateLimitAlgorithmKind::TokenBucket, 3, 1, 20),
+            policy(RateLimitAlgorithmKind::SlidingWindow, 3, 3, 600),
+            policy(RateLimitAlgorithmKind::Gcra, 3, 1, 20),
+        ];
+
+        for (i, policy) in policies.iter().enumerate() {
+            let id = format!("test-memory-algorithm-{}", i);
+            CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+
+            for _ in 0..5 {
+                let expected = redis.consume_tokens(&id, policy).await.unwrap();
+                let status = memory.consume_tokens(&id, policy).await.unwrap();
+
+                assert_eq!(status.allowed, expected.allowed);
+                assert_eq!(status.remaining, expected.remaining);
+                // les deux horloges avancent pendant le test
+                assert!(status.retry_after_ms.abs_diff(expected.retry_after_ms) <= 1_000);
+                assert!(status.reset_after_ms.abs_diff(expected.reset_after_ms) <= 1_000);
+            }
+
+            CLIENT.delete(&format!("ratelimit:{}", id)).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sliding_window_in_memory_weights_the_previous_window() {
+        // windows of 10 minutes, the previous one is full, a quarter of the current one elapsed
+        let policy = policy(RateLimitAlgorithmKind::SlidingWindow, 10, 10, 600);
+        let now = 100 * 600_000 + 150_000;
+        let mut fields = BucketFields::from([
+            ("window".to_string(), 99.0),
+            ("current".to_string(), 10.0),
+            ("previous".to_string(), 0.0),
+        ]);
+
+        let allowed = (0..10)
+            .filter(|_| {
+                let (result, _) =
+                    SlidingWindowAlgorithm.consume_in_memory(&mut fields, &policy, now);
+                result[0] == 1
+            })
+            .count();
+
+        // 7.5 requests of the previous window still count
+        assert_eq!(allowed, 2);
+    }
+
+    #[allow(dead_code)]
+    fn policy(
+        algorithm: RateLimitAlgorithmKind,
+        capacity: u64,
+        refill_amount: u64,
+        refill_period: u64,
+    ) -> RateLimitPolicy {
+        RateLimitPolicy {
+            name: "test".to_string(),
+            path_prefix: String::new(),
+            methods: vec![],
+            caller: Default::default(),
+            algorithm,
+            capacity,
+            refill_amount,
+            refill_period,
+            cost: 1,
+            opt_in: false,
+        }
+    }
+}
@@ -1,18 +1,311 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use api_configs::config::Config;
+use redis::{AsyncConnectionConfig, FromRedisValue};
 
-use crate::errors::RedisRepositoryError;
+use crate::{
+    connection::{Connection, Connector, Node, PubSubConnector},
+    errors::RedisRepositoryError,
+};
 
 extern crate redis;
 
+/// The first delay before reconnecting after a failed connection, doubled on each failure.
+pub(crate) const RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+/// The maximum delay before reconnecting.
+pub(crate) const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
 // type initialization
 pub type RedisRepositoryResult<T> = Result<T, RedisRepositoryError>;
-pub type RedisClient = Arc<redis::Client>;
+pub type RedisClient = Arc<RedisConnectionManager>;
 
 // public function to get a redis client
 pub fn get_redis_client(config: &Config) -> RedisClient {
-    Arc::new(redis::Client::open(config.redis_info.get_url()).unwrap())
+    Arc::new(RedisConnectionManager::new(config))
+}
+
+/// Wraps a part of a key in a hash tag: in cluster mode only the part between braces chooses
+/// the slot, so the keys with the same tag can be used by one script or one DEL.
+pub fn hash_tag(tag: &str) -> String {
+    format!("{{{}}}", tag)
+}
+
+/// State of the connection to Redis, for the readiness probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedisHealth {
+    /// Whether a connection is open and its last command did not fail.
+    pub connected: bool,
+    /// Number of connections opened since the start.
+    pub connections: u64,
+    /// Number of failed connections and commands since the start.
+    pub failures: u64,
+    /// The last error, if any.
+    pub last_error: Option<String>,
+}
+
+struct Reconnect {
+    backoff: Duration,
+    not_before: Instant,
+}
+
+/// Shared multiplexed connection to Redis, reopened when it breaks.
+/// Every command goes through the same connection, instead of opening one per command.
+/// The connection is driven by a runtime of the manager, so it outlives the runtimes of the
+/// actix workers and of the tests which use it.
+/// In sentinel mode, the master is asked to the sentinels on each reconnection, and in cluster
+/// mode the connection routes each command to the node owning its key.
+pub struct RedisConnectionManager {
+    connector: Connector,
+    connection_config: AsyncConnectionConfig,
+    connect_timeout: Duration,
+    connection: tokio::sync::Mutex<Option<Connection>>,
+    /// After a failed connection, the commands fail without waiting until the backoff is over.
+    reconnect: Mutex<Option<Reconnect>>,
+    runtime: Option<tokio::runtime::Runtime>,
+    connected: AtomicBool,
+    connections: AtomicU64,
+    failures: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl RedisConnectionManager {
+    /// Creates the manager, the connection is opened by the first command.
+    pub fn new(config: &Config) -> Self {
+        let redis_info = &config.redis_info;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("redis-connection")
+            .enable_all()
+            .build()
+            .expect("Failed to start the runtime of the redis connection");
+
+        RedisConnectionManager {
+            connector: Connector::new(redis_info).expect("Invalid redis configuration"),
+            connection_config: AsyncConnectionConfig::new()
+                .set_connection_timeout(Duration::from_millis(redis_info.connect_timeout))
+                .set_response_timeout(Duration::from_millis(redis_info.command_timeout)),
+            connect_timeout: Duration::from_millis(redis_info.connect_timeout),
+            connection: tokio::sync::Mutex::new(None),
+            reconnect: Mutex::new(None),
+            runtime: Some(runtime),
+            connected: AtomicBool::new(false),
+            connections: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// The state of the connection, without sending a command.
+    pub fn health(&self) -> RedisHealth {
+        RedisHealth {
+            connected: self.connected.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            last_error: self
+                .last_error
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone(),
+        }
+    }
+
+    /// Runs a subscriber on the runtime of the manager, it stops when the manager is dropped.
+    /// The subscriber opens its own pub/sub connections with the given connector: a
+    /// subscribed connection can not send the other commands.
+    pub(crate) fn spawn_subscriber<F, Fut>(&self, subscriber: F)
+    where
+        F: FnOnce(PubSubConnector) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let connector = PubSubConnector {
+            connector: self.connector.clone(),
+            connect_timeout: self.connect_timeout,
+        };
+        self.runtime
+            .as_ref()
+            .expect("the runtime is only taken on drop")
+            .spawn(subscriber(connector));
+    }
+
+    /// Returns the shared connection, opening it if needed.
+    async fn connection(&self) -> RedisRepositoryResult<Connection> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+
+        let backoff = {
+            let reconnect = self.reconnect.lock().unwrap_or_else(|err| err.into_inner());
+            match reconnect.as_ref() {
+                Some(reconnect) if Instant::now() < reconnect.not_before => {
+                    return Err(RedisRepositoryError::RedisError(redis::RedisError::from((
+                        redis::ErrorKind::IoError,
+                        "Redis unavailable, waiting before reconnecting",
+                    ))));
+                }
+                Some(reconnect) => (reconnect.backoff * 2).min(MAX_RECONNECT_BACKOFF),
+                None => RECONNECT_BACKOFF,
+            }
+        };
+
+        let connector = self.connector.clone();
+        let connection_config = self.connection_config.clone();
+        let connect_timeout = self.connect_timeout;
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("the runtime is only taken on drop");
+        let opened = runtime
+            .spawn(async move {
+                // les sentinelles et le cluster ouvrent plusieurs connexions, le timeout
+                // s'applique à l'ensemble
+                tokio::time::timeout(connect_timeout, connector.connect(&connection_config))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(redis::RedisError::from((
+                            redis::ErrorKind::IoError,
+                            "Connection timed out",
+                        )))
+                    })
+            })
+            .await
+            .unwrap_or_else(|err| {
+                Err(redis::RedisError::from((
+                    redis::ErrorKind::IoError,
+                    "Connection task failed",
+                    err.to_string(),
+                )))
+            });
+
+        match opened {
+            Ok(opened) => {
+                log::info!("Connected to Redis");
+                self.connections.fetch_add(1, Ordering::Relaxed);
+                self.connected.store(true, Ordering::Relaxed);
+                *self.reconnect.lock().unwrap_or_else(|err| err.into_inner()) = None;
+                *connection = Some(opened.clone());
+                Ok(opened)
+            }
+            Err(err) => {
+                log::error!(
+                    "Failed to connect to Redis, retry in {:?}: {}",
+                    backoff,
+                    err
+                );
+                self.failed(&err);
+                *self.reconnect.lock().unwrap_or_else(|err| err.into_inner()) = Some(Reconnect {
+                    backoff,
+                    not_before: Instant::now() + backoff,
+                });
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Drops the connection after an error it can not recover from, the next command reconnects.
+    async fn check(&self, err: &redis::RedisError) {
+        self.failed(err);
+        if err.is_unrecoverable_error() {
+            log::warn!("Redis connection lost: {}", err);
+            *self.connection.lock().await = None;
+        }
+    }
+
+    fn failed(&self, err: &redis::RedisError) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        self.connected.store(false, Ordering::Relaxed);
+        *self
+            .last_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(err.to_string());
+    }
+
+    fn succeeded(&self) {
+        self.connected.store(true, Ordering::Relaxed);
+    }
+
+    /// Sends a command on the shared connection.
+    async fn query<T: FromRedisValue>(&self, cmd: &redis::Cmd) -> RedisRepositoryResult<T> {
+        let mut con = self.connection().await?;
+        match cmd.query_async(&mut con).await {
+            Ok(value) => {
+                self.succeeded();
+                Ok(value)
+            }
+            Err(err) => {
+                self.check(&err).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// The nodes holding the keys, see `Connection::nodes`.
+    async fn nodes(&self) -> RedisRepositoryResult<Vec<Node>> {
+        let mut con = self.connection().await?;
+        match con.nodes().await {
+            Ok(nodes) => {
+                self.succeeded();
+                Ok(nodes)
+            }
+            Err(err) => {
+                self.check(&err).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Sends a command to one node, the server or a master of the cluster.
+    async fn query_on<T: FromRedisValue>(
+        &self,
+        node: &Node,
+        cmd: &redis::Cmd,
+    ) -> RedisRepositoryResult<T> {
+        let mut con = self.connection().await?;
+        match con.query_on(node, cmd).await {
+            Ok(value) => {
+                self.succeeded();
+                Ok(value)
+            }
+            Err(err) => {
+                self.check(&err).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Sends a pipeline on the shared connection.
+    async fn query_pipe<T: FromRedisValue>(
+        &self,
+        pipe: &redis::Pipeline,
+    ) -> RedisRepositoryResult<T> {
+        let mut con = self.connection().await?;
+        match pipe.query_async(&mut con).await {
+            Ok(value) => {
+                self.succeeded();
+                Ok(value)
+            }
+            Err(err) => {
+                self.check(&err).await;
+                Err(err.into())
+            }
+        }
+    }
+}
+
+impl Drop for RedisConnectionManager {
+    fn drop(&mut self) {
+        // un runtime ne peut pas être arrêté en attendant depuis une tâche async
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -26,55 +319,74 @@ pub trait RedisRepository: Clone + Send + Sync + 'static {
         key: &str,
         fields: Vec<(String, String)>,
     ) -> RedisRepositoryResult<()>;
+    /// Set the fields of a hash and (re)set the ttl of the hash, in seconds.
+    async fn hset_multiple_ttl(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+        ttl: i64,
+    ) -> RedisRepositoryResult<()>;
     async fn hget_multiple(
         &self,
         key: &str,
         fields: Vec<String>,
     ) -> RedisRepositoryResult<Vec<Option<String>>>;
+    /// Values of several keys in one command, `None` for the missing keys.
+    /// In cluster mode the keys of different slots are fetched from each node and merged.
+    async fn get_many(&self, keys: &[&str]) -> RedisRepositoryResult<Vec<Option<String>>>;
+    /// Set several keys in one round trip, with a ttl in seconds if given.
+    /// The keys are not written atomically.
+    async fn set_many(
+        &self,
+        entries: Vec<(String, String)>,
+        ttl: Option<i64>,
+    ) -> RedisRepositoryResult<()>;
+    /// Every field of a hash, empty if the hash is missing.
+    async fn hget_all(&self, key: &str) -> RedisRepositoryResult<Vec<(String, String)>>;
     async fn hget(&self, key: &str, field: &str) -> RedisRepositoryResult<Option<String>>;
     async fn hset(&self, key: &str, field: &str, value: &str) -> RedisRepositoryResult<()>;
+    /// Increment the field of a hash and (re)set the ttl of the hash, returns the new value.
+    async fn hincr(&self, key: &str, field: &str, ttl: i64) -> RedisRepositoryResult<i64>;
+    async fn expire(&self, key: &str, ttl: i64) -> RedisRepositoryResult<()>;
     async fn ttl(&self, key: &str) -> RedisRepositoryResult<i64>;
     async fn update(&self, key: &str, value: &str) -> RedisRepositoryResult<()>;
     async fn update_ttl(&self, key: &str, value: &str, ttl: i64) -> RedisRepositoryResult<()>;
     async fn delete(&self, key: &str) -> RedisRepositoryResult<()>;
+    /// Delete several keys in one command. In cluster mode the keys must share a slot,
+    /// see `hash_tag`.
+    async fn delete_all(&self, keys: &[&str]) -> RedisRepositoryResult<()>;
+    /// Publish a message on a pub/sub channel, it is lost for the clients not subscribed yet.
+    async fn publish(&self, channel: &str, message: &str) -> RedisRepositoryResult<()>;
+    /// Iterate over the keys matching a pattern with SCAN, without blocking the server like KEYS.
+    /// In cluster mode every master is scanned.
+    async fn scan_keys(&self, pattern: &str) -> RedisRepositoryResult<Vec<String>>;
+    /// Run a Lua script atomically on the server and returns the integers it returns.
+    /// The script is sent once, then called by its hash.
+    /// In cluster mode all the keys must share a slot, see `hash_tag`.
+    async fn eval_ints(
+        &self,
+        script: &redis::Script,
+        keys: &[&str],
+        args: &[String],
+    ) -> RedisRepositoryResult<Vec<i64>>;
 }
 
 #[async_trait::async_trait]
 impl RedisRepository for RedisClient {
     async fn ping(&self) -> RedisRepositoryResult<Option<String>> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("PING")
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(&redis::cmd("PING")).await
     }
 
     async fn exists(&self, key: &str) -> RedisRepositoryResult<bool> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("EXISTS")
-            .arg(key)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("EXISTS").arg(key)).await
     }
 
     async fn get(&self, key: &str) -> RedisRepositoryResult<Option<String>> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("GET")
-            .arg(key)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("GET").arg(key)).await
     }
 
     async fn set(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("SET")
-            .arg(key)
-            .arg(value)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("SET").arg(key).arg(value)).await
     }
 
     async fn hset_multiple(
@@ -82,13 +394,28 @@ impl RedisRepository for RedisClient {
         key: &str,
         fields: Vec<(String, String)>,
     ) -> RedisRepositoryResult<()> {
-        let mut conn = self.get_multiplexed_async_connection().await?;
-        redis::cmd("HMSET")
-            .arg(key)
-            .arg(fields)
-            .query_async(&mut conn)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("HMSET").arg(key).arg(fields)).await
+    }
+
+    async fn hset_multiple_ttl(
+        &self,
+        key: &str,
+        fields: Vec<(String, String)>,
+        ttl: i64,
+    ) -> RedisRepositoryResult<()> {
+        self.query_pipe(
+            redis::pipe()
+                .atomic()
+                .cmd("HMSET")
+                .arg(key)
+                .arg(fields)
+                .ignore()
+                .cmd("EXPIRE")
+                .arg(key)
+                .arg(ttl)
+                .ignore(),
+        )
+        .await
     }
 
     async fn hget_multiple(
@@ -96,74 +423,158 @@ impl RedisRepository for RedisClient {
         key: &str,
         fields: Vec<String>,
     ) -> RedisRepositoryResult<Vec<Option<String>>> {
-        let mut conn = self.get_multiplexed_async_connection().await?;
-        redis::cmd("HMGET")
-            .arg(key)
-            .arg(fields)
-            .query_async(&mut conn)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("HMGET").arg(key).arg(fields)).await
+    }
+
+    async fn get_many(&self, keys: &[&str]) -> RedisRepositoryResult<Vec<Option<String>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        self.query(redis::cmd("MGET").arg(keys)).await
+    }
+
+    async fn set_many(
+        &self,
+        entries: Vec<(String, String)>,
+        ttl: Option<i64>,
+    ) -> RedisRepositoryResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        // pas de MULTI : en cluster les clés peuvent être sur des nœuds différents
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            let cmd = pipe.cmd("SET").arg(key).arg(value);
+            if let Some(ttl) = ttl {
+                cmd.arg("EX").arg(ttl);
+            }
+            cmd.ignore();
+        }
+        self.query_pipe(&pipe).await
+    }
+
+    async fn hget_all(&self, key: &str) -> RedisRepositoryResult<Vec<(String, String)>> {
+        self.query(redis::cmd("HGETALL").arg(key)).await
     }
 
     async fn hget(&self, key: &str, field: &str) -> RedisRepositoryResult<Option<String>> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("HGET")
-            .arg(key)
-            .arg(field)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("HGET").arg(key).arg(field)).await
     }
 
     async fn hset(&self, key: &str, field: &str, value: &str) -> RedisRepositoryResult<()> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("HSET")
-            .arg(key)
-            .arg(field)
-            .arg(value)
-            .query_async(&mut con)
+        self.query(redis::cmd("HSET").arg(key).arg(field).arg(value))
             .await
-            .map_err(|e| e.into())
+    }
+
+    async fn hincr(&self, key: &str, field: &str, ttl: i64) -> RedisRepositoryResult<i64> {
+        let (value,): (i64,) = self
+            .query_pipe(
+                redis::pipe()
+                    .atomic()
+                    .cmd("HINCRBY")
+                    .arg(key)
+                    .arg(field)
+                    .arg(1)
+                    .cmd("EXPIRE")
+                    .arg(key)
+                    .arg(ttl)
+                    .ignore(),
+            )
+            .await?;
+
+        Ok(value)
+    }
+
+    async fn expire(&self, key: &str, ttl: i64) -> RedisRepositoryResult<()> {
+        self.query(redis::cmd("EXPIRE").arg(key).arg(ttl)).await
     }
 
     async fn ttl(&self, key: &str) -> RedisRepositoryResult<i64> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("TTL")
-            .arg(key)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("TTL").arg(key)).await
     }
 
     async fn update(&self, key: &str, value: &str) -> RedisRepositoryResult<()> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("SET")
-            .arg(key)
-            .arg(value)
-            .query_async(&mut con)
-            .await
-            .map_err(|e| e.into())
+        self.query(redis::cmd("SET").arg(key).arg(value)).await
     }
 
     async fn update_ttl(&self, key: &str, value: &str, ttl: i64) -> RedisRepositoryResult<()> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("SET")
-            .arg(key)
-            .arg(value)
-            .arg("EX")
-            .arg(ttl)
-            .query_async(&mut con)
+        self.query(redis::cmd("SET").arg(key).arg(value).arg("EX").arg(ttl))
             .await
-            .map_err(|e| e.into())
     }
 
     async fn delete(&self, key: &str) -> RedisRepositoryResult<()> {
-        let mut con = self.get_multiplexed_async_connection().await?;
-        redis::cmd("DEL")
-            .arg(key)
-            .query_async(&mut con)
+        self.query(redis::cmd("DEL").arg(key)).await
+    }
+
+    async fn delete_all(&self, keys: &[&str]) -> RedisRepositoryResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.query(redis::cmd("DEL").arg(keys)).await
+    }
+
+    async fn publish(&self, channel: &str, message: &str) -> RedisRepositoryResult<()> {
+        self.query(redis::cmd("PUBLISH").arg(channel).arg(message))
             .await
-            .map_err(|e| e.into())
+    }
+
+    async fn scan_keys(&self, pattern: &str) -> RedisRepositoryResult<Vec<String>> {
+        let mut keys = Vec::new();
+
+        // en cluster, chaque master a ses propres clés et son propre curseur
+        for node in self.nodes().await? {
+            let mut cursor = 0;
+            loop {
+                let (next, batch): (u64, Vec<String>) = self
+                    .query_on(
+                        &node,
+                        redis::cmd("SCAN")
+                            .arg(cursor)
+                            .arg("MATCH")
+                            .arg(pattern)
+                            .arg("COUNT")
+                            .arg(100),
+                    )
+                    .await?;
+                keys.extend(batch);
+
+                // le curseur revient à 0 à la fin de l'itération
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn eval_ints(
+        &self,
+        script: &redis::Script,
+        keys: &[&str],
+        args: &[String],
+    ) -> RedisRepositoryResult<Vec<i64>> {
+        let mut con = self.connection().await?;
+        let mut invocation = script.prepare_invoke();
+        for key in keys {
+            invocation.key(*key);
+        }
+        for arg in args {
+            invocation.arg(arg);
+        }
+
+        match invocation.invoke_async(&mut con).await {
+            Ok(value) => {
+                self.succeeded();
+                Ok(value)
+            }
+            Err(err) => {
+                self.check(&err).await;
+                Err(err.into())
+            }
+        }
     }
 }
 
@@ -229,4 +640,104 @@ mod tests {
         let result = CLIENT.get(key).await.unwrap();
         assert_eq!(result, None);
     }
+
+    #[actix_rt::test]
+    async fn test_redis_hset_multiple_ttl() {
+        let key = "test_hset_multiple_ttl";
+        CLIENT
+            .hset_multiple_ttl(key, vec![("field".to_string(), "value".to_string())], 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            CLIENT.hget(key, "field").await.unwrap(),
+            Some("value".to_string())
+        );
+        assert_eq!(CLIENT.ttl(key).await.unwrap(), 10);
+        CLIENT.delete(key).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_scan_keys() {
+        let keys = ["test_scan:1", "test_scan:2"];
+        for key in keys {
+            CLIENT.set(key, "value").await.unwrap();
+        }
+        let mut result = CLIENT.scan_keys("test_scan:*").await.unwrap();
+        result.sort();
+        assert_eq!(result, keys);
+        for key in keys {
+            CLIENT.delete(key).await.unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_delete_all_with_hash_tag() {
+        let tag = hash_tag("test_delete_all");
+        assert_eq!(tag, "{test_delete_all}");
+
+        let keys = [format!("a:{}", tag), format!("b:{}", tag)];
+        for key in &keys {
+            CLIENT.set(key, "value").await.unwrap();
+        }
+        CLIENT
+            .delete_all(&keys.iter().map(String::as_str).collect::<Vec<_>>())
+            .await
+            .unwrap();
+        for key in &keys {
+            assert!(!CLIENT.exists(key).await.unwrap());
+        }
+        CLIENT.delete_all(&[]).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_commands_share_one_connection() {
+        let client = get_redis_client(&CONFIG.clone());
+
+        let results =
+            futures_util::future::join_all((0..20).map(|_| client.get("test_shared"))).await;
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let health = client.health();
+        assert!(health.connected);
+        assert_eq!(health.connections, 1);
+        assert_eq!(health.failures, 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_connection_outlives_the_runtime() {
+        let client = get_redis_client(&CONFIG.clone());
+
+        // la connexion ouverte par un autre runtime, comme un autre worker actix
+        let other = Arc::clone(&client);
+        std::thread::spawn(move || {
+            actix_rt::System::new().block_on(async move { other.ping().await.unwrap() })
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(client.ping().await, Ok(Some("PONG".to_string())));
+        assert_eq!(client.health().connections, 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_waits_before_reconnecting() {
+        let mut config = CONFIG.clone();
+        // aucun serveur redis sur ce port
+        config.redis_info.port = "1".to_string();
+        let client = get_redis_client(&config);
+
+        assert!(client.ping().await.is_err());
+        assert_eq!(client.health().failures, 1);
+
+        // pendant le délai, les commandes échouent sans nouvelle tentative de connexion
+        let started = Instant::now();
+        assert!(client.ping().await.is_err());
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        let health = client.health();
+        assert!(!health.connected);
+        assert_eq!(health.connections, 0);
+        assert_eq!(health.failures, 1);
+        assert!(health.last_error.is_some());
+    }
 }
@@ -0,0 +1,30 @@
+-- Replace a value only if it is still the expected one.
+-- KEYS[1] : the key
+-- ARGV[1] : 1 if a value is expected, 0 if the key must be missing
+-- ARGV[2] : the expected value
+-- ARGV[3] : 1 to set the new value, 0 to delete the key
+-- ARGV[4] : the new value
+-- ARGV[5] : ttl of the new value in seconds, 0 without ttl
+-- Returns { 1 } if the key has been replaced, { 0 } otherwise
+local current = redis.call('GET', KEYS[1])
+
+if ARGV[1] == '1' then
+    if current ~= ARGV[2] then
+        return { 0 }
+    end
+elseif current then
+    return { 0 }
+end
+
+if ARGV[3] == '1' then
+    local ttl = tonumber(ARGV[5])
+    if ttl > 0 then
+        redis.call('SET', KEYS[1], ARGV[4], 'EX', ttl)
+    else
+        redis.call('SET', KEYS[1], ARGV[4])
+    end
+else
+    redis.call('DEL', KEYS[1])
+end
+
+return { 1 }
@@ -0,0 +1,41 @@
+-- Generic cell rate algorithm: the theoretical arrival time (tat) of the next request moves forward
+-- by one emission interval per token, a request is allowed while the tat is less than a burst ahead.
+-- KEYS[1] : the state (hash with the field tat in milliseconds)
+-- ARGV[1] : burst capacity in tokens
+-- ARGV[2] : tokens emitted every ARGV[3] milliseconds
+-- ARGV[4] : cost of the request
+-- Returns { allowed (1 or 0), remaining tokens, milliseconds before the request could be allowed,
+--           milliseconds before the full burst is available }
+local capacity = tonumber(ARGV[1])
+local refill_amount = tonumber(ARGV[2])
+local refill_period = tonumber(ARGV[3])
+local cost = tonumber(ARGV[4])
+
+local interval = refill_period / refill_amount
+local burst = interval * capacity
+
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local tat = math.max(tonumber(redis.call('HGET', KEYS[1], 'tat')) or now, now)
+local new_tat = tat + interval * cost
+local allow_at = new_tat - burst
+
+local allowed = 0
+local retry_after = 0
+if now >= allow_at then
+    allowed = 1
+    tat = new_tat
+    redis.call('HSET', KEYS[1], 'tat', tat)
+    -- once the tat is reached, the state is the same as a new caller
+    redis.call('PEXPIRE', KEYS[1], math.max(math.ceil(tat - now), 1))
+else
+    retry_after = allow_at - now
+end
+
+return {
+    allowed,
+    math.max(math.floor((burst - (tat - now)) / interval), 0),
+    math.ceil(retry_after),
+    math.ceil(tat - now),
+}
@@ -0,0 +1,69 @@
+-- Sliding window counter: the count of the previous fixed window is weighted by the part of it
+-- still covered by the sliding window, then added to the count of the current window.
+-- KEYS[1] : the counters (hash with the fields window, current and previous)
+-- ARGV[1] : maximum cost of the requests in a window
+-- ARGV[2], ARGV[3] : the window lasts the time needed to refill ARGV[1] at ARGV[2] per ARGV[3] milliseconds
+-- ARGV[4] : cost of the request
+-- Returns { allowed (1 or 0), remaining, milliseconds before the request could be allowed,
+--           milliseconds before the counters are empty }
+local capacity = tonumber(ARGV[1])
+local refill_amount = tonumber(ARGV[2])
+local refill_period = tonumber(ARGV[3])
+local cost = tonumber(ARGV[4])
+local window = capacity * refill_period / refill_amount
+
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local index = math.floor(now / window)
+local elapsed = now - index * window
+
+local counters = redis.call('HMGET', KEYS[1], 'window', 'current', 'previous')
+local stored_index = tonumber(counters[1])
+local current = tonumber(counters[2]) or 0
+local previous = tonumber(counters[3]) or 0
+
+if stored_index == nil or stored_index < index - 1 then
+    current = 0
+    previous = 0
+elseif stored_index == index - 1 then
+    previous = current
+    current = 0
+end
+
+local estimate = previous * (window - elapsed) / window + current
+
+local allowed = 0
+local retry_after = 0
+if estimate + cost <= capacity then
+    allowed = 1
+    current = current + cost
+    estimate = estimate + cost
+elseif current + cost <= capacity then
+    -- wait until the weight of the previous window is low enough
+    retry_after = window * (1 - (capacity - current - cost) / previous) - elapsed
+elseif cost <= capacity then
+    -- wait for the next window, where the current window becomes the previous one
+    retry_after = window - elapsed + window * (1 - (capacity - cost) / current)
+else
+    -- a request costing more than the capacity is never allowed
+    retry_after = 2 * window
+end
+
+local reset_after = 0
+if current > 0 then
+    reset_after = 2 * window - elapsed
+elseif previous > 0 then
+    reset_after = window - elapsed
+end
+
+redis.call('HSET', KEYS[1], 'window', index, 'current', current, 'previous', previous)
+-- the counters are no longer used after two windows
+redis.call('PEXPIRE', KEYS[1], math.max(math.ceil(2 * window), 1))
+
+return {
+    allowed,
+    math.max(math.floor(capacity - estimate), 0),
+    math.max(math.ceil(retry_after), 0),
+    math.ceil(reset_after),
+}
@@ -0,0 +1,52 @@
+-- Refill then consume a token bucket atomically.
+-- KEYS[1] : the bucket (hash with the fields capacity, tokens and last_refill_time in milliseconds)
+-- ARGV[1] : capacity of the bucket
+-- ARGV[2] : tokens added every refill period
+-- ARGV[3] : refill period in milliseconds
+-- ARGV[4] : tokens to consume
+-- Returns { allowed (1 or 0), remaining tokens, milliseconds before enough tokens are available,
+--           milliseconds before the bucket is full }
+local capacity = tonumber(ARGV[1])
+local refill_amount = tonumber(ARGV[2])
+local refill_period = tonumber(ARGV[3])
+local cost = tonumber(ARGV[4])
+
+-- l'heure du serveur redis, commune à toutes les instances de l'api
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'last_refill_time')
+local tokens = tonumber(bucket[1])
+local last_refill_time = tonumber(bucket[2])
+
+if tokens == nil or last_refill_time == nil then
+    tokens = capacity
+    last_refill_time = now
+end
+
+-- only whole tokens are added, the remaining time is kept for the next refill
+local added = math.floor(math.max(now - last_refill_time, 0) * refill_amount / refill_period)
+if tokens + added >= capacity then
+    tokens = capacity
+    last_refill_time = now
+elseif added > 0 then
+    tokens = tokens + added
+    last_refill_time = last_refill_time + math.floor(added * refill_period / refill_amount)
+end
+
+local allowed = 0
+local retry_after = 0
+if tokens >= cost then
+    allowed = 1
+    tokens = tokens - cost
+else
+    retry_after = math.max(math.ceil(last_refill_time + (cost - tokens) * refill_period / refill_amount - now), 0)
+end
+
+local reset_after = math.max(math.ceil(last_refill_time + (capacity - tokens) * refill_period / refill_amount - now), 0)
+
+redis.call('HSET', KEYS[1], 'capacity', capacity, 'tokens', tokens, 'last_refill_time', last_refill_time)
+-- un bucket inactif est plein après ce délai, il peut être supprimé
+redis.call('PEXPIRE', KEYS[1], math.max(math.ceil(capacity * refill_period / refill_amount), 1))
+
+return { allowed, tokens, retry_after, reset_after }
@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// One lock per key, so that only one task loads a missing value while the others wait for it
+/// instead of all hitting the database at once (cache stampede).
+/// The locks are removed once nobody waits for them.
+pub struct SingleFlight<K> {
+    in_flight: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl<K: Eq + Hash + Clone> Default for SingleFlight<K> {
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> SingleFlight<K> {
+    /// Waits for the lock of a key. The caller checks the cache again once it has the lock:
+    /// the previous holder has probably filled it.
+    pub async fn lock(&self, key: K) -> SingleFlightGuard<'_, K> {
+        let lock = Arc::clone(
+            self.in_flight
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .entry(key.clone())
+                .or_default(),
+        );
+
+        SingleFlightGuard {
+            guard: Some(Arc::clone(&lock).lock_owned().await),
+            lock,
+            key,
+            flight: self,
+        }
+    }
+
+    /// Number of keys being loaded or waited for.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len()
+    }
+}
+
+/// The lock of a key, released on drop.
+pub struct SingleFlightGuard<'a, K: Eq + Hash + Clone> {
+    guard: Option<OwnedMutexGuard<()>>,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    key: K,
+    flight: &'a SingleFlight<K>,
+}
+
+impl<K: Eq + Hash + Clone> Drop for SingleFlightGuard<'_, K> {
+    fn drop(&mut self) {
+        self.guard.take();
+
+        let mut in_flight = self
+            .flight
+            .in_flight
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        // la map et ce guard : plus personne n'attend ce verrou
+        if Arc::strong_count(&self.lock) == 2 {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[actix_rt::test]
+    async fn test_single_flight_loads_once() {
+        let flight = SingleFlight::<i32>::default();
+        let cache = Mutex::new(None);
+        let loads = AtomicU32::new(0);
+
+        let load = || async {
+            let _guard = flight.lock(1).await;
+            if let Some(value) = *cache.lock().unwrap() {
+                return value;
+            }
+
+            loads.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            *cache.lock().unwrap() = Some(42);
+            42
+        };
+
+        let values = futures_util::future::join_all((0..10).map(|_| load())).await;
+
+        assert!(values.iter().all(|value| *value == 42));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(flight.in_flight(), 0);
+    }
+}
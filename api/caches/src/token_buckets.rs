@@ -1,31 +1,70 @@
+use std::sync::Arc;
+
 use crate::{
+    cache::Cache,
+    clock::{Clock, SystemClock},
     errors::RateLimitError,
+    memory::MemoryStore,
+    rate_limit_algorithms::{algorithm, BucketFields},
     redis::{RedisClient, RedisRepository},
 };
-use actix_web::http::Method;
+use api_configs::rate_limit::RateLimitPolicy;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-/// The refill rate in tokens per second.
-const REFILL_RATE_PER_SECOND: i64 = 10;
 /// The default maximum capacity of the token bucket.
 const DEFAULT_CAPACITY: u64 = 100;
 
 pub type RateLimiterResult<T> = Result<T, RateLimitError>;
 
+/// State of a token bucket after an attempt to consume tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BucketStatus {
+    /// Whether the tokens have been consumed.
+    pub allowed: bool,
+    /// The number of tokens left in the bucket.
+    pub remaining: u64,
+    /// Milliseconds to wait before enough tokens are available, 0 if allowed.
+    pub retry_after_ms: u64,
+    /// Milliseconds before the whole capacity is available again.
+    pub reset_after_ms: u64,
+}
+
+impl BucketStatus {
+    fn from_script(result: Vec<i64>) -> RateLimiterResult<Self> {
+        match result[..] {
+            [allowed, remaining, retry_after_ms, reset_after_ms] => Ok(BucketStatus {
+                allowed: allowed == 1,
+                remaining: remaining.max(0) as u64,
+                retry_after_ms: retry_after_ms.max(0) as u64,
+                reset_after_ms: reset_after_ms.max(0) as u64,
+            }),
+            _ => Err(RateLimitError::NotFound),
+        }
+    }
+}
+
 /// A structure representing a token bucket for rate limiting.
-#[derive(Clone)]
+/// Stored as a hash with the fields read and written by `scripts/token_bucket.lua`.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TokenBucket {
     /// The maximum number of tokens the bucket can hold.
+    #[serde(default = "default_capacity")]
     capacity: u64,
     /// The current number of available tokens.
+    #[serde(default)]
     tokens: u64,
-    /// The last time the bucket was refilled.
+    /// The last time the bucket was refilled, in milliseconds.
+    #[serde(with = "chrono::serde::ts_milliseconds")]
     last_refill_time: DateTime<Utc>,
 }
 
+fn default_capacity() -> u64 {
+    DEFAULT_CAPACITY
+}
+
 impl Default for TokenBucket {
     fn default() -> Self {
-        // TODO: ajouter dans le .env
         TokenBucket {
             capacity: DEFAULT_CAPACITY,
             tokens: 100,
@@ -34,96 +73,75 @@ impl Default for TokenBucket {
     }
 }
 
-fn _from(bucket: &TokenBucket) -> Vec<(String, String)> {
-    vec![
-        ("capacity".to_string(), bucket.capacity.to_string()),
-        ("tokens".to_string(), bucket.tokens.to_string()),
-        (
-            "last_refill_time".to_string(),
-            bucket.last_refill_time.to_rfc3339(),
-        ),
-    ]
-}
-
-/// Converts a `TokenBucket` into a vector of key-value string pairs for Redis storage.
-impl From<TokenBucket> for Vec<(String, String)> {
-    fn from(bucket: TokenBucket) -> Self {
-        _from(&bucket)
-    }
-}
-
-/// Converts a reference to `TokenBucket` into a vector of key-value string pairs for Redis storage.
-impl From<&TokenBucket> for Vec<(String, String)> {
+/// Converts a reference to `TokenBucket` into the fields of the in-memory buckets.
+impl From<&TokenBucket> for BucketFields {
     fn from(bucket: &TokenBucket) -> Self {
-        _from(bucket)
+        BucketFields::from([
+            ("capacity".to_string(), bucket.capacity as f64),
+            ("tokens".to_string(), bucket.tokens as f64),
+            (
+                "last_refill_time".to_string(),
+                bucket.last_refill_time.timestamp_millis() as f64,
+            ),
+        ])
     }
 }
 
 impl TokenBucket {
-    /// Creates a `TokenBucket` from cached Redis values.
-    ///
-    /// # Arguments
-    /// * `cache` - A vector of optional strings representing Redis values.
-    ///
-    /// # Returns
-    /// A `TokenBucket` initialized from the cache.
-    fn from_cache(cache: Vec<Option<String>>) -> Self {
+    /// A full bucket of the policy, the same as a missing bucket.
+    fn full(policy: &RateLimitPolicy) -> Self {
         TokenBucket {
-            capacity: cache[0]
-                .as_ref()
-                .and_then(|el| el.parse().ok())
-                .unwrap_or(DEFAULT_CAPACITY),
-            tokens: cache[1]
-                .as_ref()
-                .and_then(|el| el.parse().ok())
-                .unwrap_or(0),
-            last_refill_time: cache[2]
-                .as_ref()
-                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-                .map(|dt| dt.to_utc())
-                .unwrap_or_else(Utc::now),
+            capacity: policy.capacity,
+            tokens: policy.capacity,
+            last_refill_time: Utc::now(),
         }
     }
 
-    /// Refills the token bucket based on the elapsed time since the last refill.
-    fn refill(&mut self) {
-        let now = Utc::now();
-        let elapsed = now.signed_duration_since(self.last_refill_time);
-
-        let tokens_to_add = (elapsed.num_seconds() * REFILL_RATE_PER_SECOND).max(0);
-        self.tokens = self
-            .tokens
-            .saturating_add(tokens_to_add as u64)
-            .min(self.capacity);
-        self.last_refill_time = now;
+    /// Seconds before an idle bucket is full again at the refill rate of the policy, it is
+    /// then the same as a missing bucket. The same ttl as the one of `scripts/token_bucket.lua`.
+    fn ttl(&self, policy: &RateLimitPolicy) -> i64 {
+        self.capacity
+            .saturating_mul(policy.refill_period)
+            .div_ceil(policy.refill_amount.max(1))
+            .max(1) as i64
     }
 }
 
 /// Trait for managing token buckets in a Redis-based cache.
 #[async_trait::async_trait]
 pub trait TokenBucketsCache: Clone + Send + Sync + 'static {
-    /// Saves a token bucket to Redis.
-    async fn save_bucket(&self, id: &str, bucket: &TokenBucket) -> RateLimiterResult<()>;
-    /// Creates a new token bucket with default values in Redis.
-    async fn create_bucket(&self, id: &str) -> RateLimiterResult<()>;
-    /// Checks if a token bucket exists in Redis.
-    async fn bucket_exists(&self, id: &str) -> RateLimiterResult<bool>;
-    /// Refills an existing token bucket and updates Redis.
-    async fn refill_bucket(&self, id: &str) -> RateLimiterResult<()>;
-    /// Consumes tokens from a token bucket and updates Redis.
-    async fn consume_tokens(&self, id: &str, method: &Method) -> RateLimiterResult<()>;
+    /// Saves a token bucket to Redis, it expires once refilled by the policy.
+    async fn save_bucket(
+        &self,
+        id: &str,
+        bucket: &TokenBucket,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<()>;
+    /// Creates a full token bucket of the policy in Redis, it expires once refilled.
+    async fn create_bucket(&self, id: &str, policy: &RateLimitPolicy) -> RateLimiterResult<()>;
+    /// Refills the token bucket, creating it if needed, then consumes the tokens of the request
+    /// if there are enough of them, atomically, following the algorithm, capacity, refill and cost
+    /// of the policy.
+    async fn consume_tokens(
+        &self,
+        id: &str,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<BucketStatus>;
+    /// Deletes the buckets without a ttl, written before the buckets expired.
+    /// Returns the number of deleted buckets.
+    async fn delete_buckets_without_ttl(&self) -> RateLimiterResult<u64>;
 }
 
 /// Redis-based implementation of `TokenBucketsCache`.
 #[derive(Clone)]
-pub struct TokenBucketsCacheRedis {
-    /// Redis client instance.
-    client: RedisClient,
-    /// Prefix used for Redis keys.
-    prefix: String,
+pub struct TokenBucketsCacheRedis<R: RedisRepository = RedisClient> {
+    /// Redis client instance, for the scripts and the cleanup.
+    client: R,
+    /// The buckets under `ratelimit:{id}`.
+    buckets: Cache<str, TokenBucket, R>,
 }
 
-impl TokenBucketsCacheRedis {
+impl<R: RedisRepository> TokenBucketsCacheRedis<R> {
     /// Creates a new instance of `TokenBucketsCacheRedis`.
     ///
     /// # Arguments
@@ -131,130 +149,193 @@ impl TokenBucketsCacheRedis {
     ///
     /// # Returns
     /// A new `TokenBucketsCacheRedis` instance.
-    pub fn new(client: RedisClient) -> Self {
+    pub fn new(client: R) -> Self {
         TokenBucketsCacheRedis {
+            buckets: Cache::new(client.clone(), "ratelimit"),
             client,
-            prefix: "ratelimit".to_string(),
         }
     }
 }
 
 #[async_trait::async_trait]
-impl TokenBucketsCache for TokenBucketsCacheRedis {
+impl<R: RedisRepository> TokenBucketsCache for TokenBucketsCacheRedis<R> {
     /// Saves a token bucket to Redis.
     ///
     /// # Arguments
     /// * `id` - The unique identifier for the token bucket (ip or uuid).
     /// * `bucket` - The token bucket instance to save.
-    async fn save_bucket(&self, id: &str, bucket: &TokenBucket) -> RateLimiterResult<()> {
+    /// * `policy` - The rate limit policy refilling the bucket, for its ttl.
+    async fn save_bucket(
+        &self,
+        id: &str,
+        bucket: &TokenBucket,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<()> {
         log::info!("Saving token bucket for {}", id);
-        self.client
-            .hset_multiple(&format!("{}:{}", self.prefix, id), bucket.into())
+        self.buckets
+            .set_fields(id, bucket, bucket.ttl(policy))
             .await
             .map_err(RateLimitError::from)
     }
 
-    /// Creates a new token bucket in Redis with default values.
+    /// Creates a new token bucket in Redis, full at the capacity of the policy.
     ///
     /// # Arguments
     /// * `id` - The unique identifier for the token bucket (ip or uuid).
-    async fn create_bucket(&self, id: &str) -> RateLimiterResult<()> {
+    /// * `policy` - The rate limit policy of the bucket.
+    async fn create_bucket(&self, id: &str, policy: &RateLimitPolicy) -> RateLimiterResult<()> {
         log::info!("Creating token bucket for {}", id);
-        self.client
-            .hset_multiple(
-                &format!("{}:{}", self.prefix, id),
-                TokenBucket::default().into(),
-            )
+        let bucket = TokenBucket::full(policy);
+        self.buckets
+            .set_fields(id, &bucket, bucket.ttl(policy))
             .await
             .map_err(RateLimitError::from)
     }
 
-    /// Checks if a token bucket exists in Redis.
+    /// Refills the token bucket and consumes the tokens of the request in the single Lua script
+    /// of the algorithm of the policy, so that concurrent requests can not overspend tokens.
     ///
     /// # Arguments
     /// * `id` - The unique identifier for the token bucket (ip or uuid).
-    async fn bucket_exists(&self, id: &str) -> RateLimiterResult<bool> {
-        self.client
-            .exists(&format!("{}:{}", self.prefix, id))
-            .await
-            .map_err(RateLimitError::from)
-    }
-
-    /// Refills an existing token bucket and updates Redis.
+    /// * `policy` - The rate limit policy applied to the request.
     ///
-    /// # Arguments
-    /// * `id` - The unique identifier for the token bucket (ip or uuid).
-    async fn refill_bucket(&self, id: &str) -> RateLimiterResult<()> {
-        let bucket_from_cache = self
+    /// # Returns
+    /// The state of the bucket after the attempt.
+    async fn consume_tokens(
+        &self,
+        id: &str,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<BucketStatus> {
+        let algorithm = algorithm(policy.algorithm);
+        let result = self
             .client
-            .hget_multiple(
-                &format!("{}:{}", self.prefix, id),
-                vec![
-                    "capacity".to_string(),
-                    "tokens".to_string(),
-                    "last_refill_time".to_string(),
-                ],
+            .eval_ints(
+                algorithm.script(),
+                &[&self.buckets.key(id)],
+                &algorithm.args(policy),
             )
             .await?;
 
-        if bucket_from_cache.iter().all(|v| v.is_none()) {
-            return Err(RateLimitError::NotFound);
-        }
+        let status = BucketStatus::from_script(result)?;
+        log::info!(
+            "Consuming tokens for id: {}, policy: {}, allowed: {}",
+            id,
+            policy.name,
+            status.allowed
+        );
 
-        let mut bucket = TokenBucket::from_cache(bucket_from_cache);
-        bucket.refill();
-        log::info!("Token bucket for {} refilled", id);
-        self.save_bucket(id, &bucket).await
+        Ok(status)
     }
 
-    /// Consumes tokens from a token bucket and updates Redis.
+    /// Scans the buckets and deletes the ones without a ttl, a missing bucket is full
+    /// so the callers keep their whole capacity.
     ///
-    /// # Arguments
-    /// * `id` - The unique identifier for the token bucket (ip or uuid).
-    /// * `method` - The HTTP method used to consume tokens.
-    async fn consume_tokens(&self, id: &str, method: &Method) -> RateLimiterResult<()> {
-        let tokens = self
-            .client
-            .hget(&format!("{}:{}", self.prefix, id), "tokens")
-            .await?;
-
-        if tokens.is_none() {
-            return Err(RateLimitError::NotFound);
+    /// # Returns
+    /// The number of deleted buckets.
+    async fn delete_buckets_without_ttl(&self) -> RateLimiterResult<u64> {
+        let keys = self.client.scan_keys(&self.buckets.key("*")).await?;
+
+        let mut deleted = 0;
+        for key in keys {
+            // -1 : pas de ttl, -2 : la clé a expiré depuis le scan
+            if self.client.ttl(&key).await? == -1 {
+                self.client.delete(&key).await?;
+                deleted += 1;
+            }
         }
 
-        let tokens = tokens.unwrap().parse::<u64>()?;
+        Ok(deleted)
+    }
+}
 
-        let used_tokens = match method {
-            &Method::GET => 1,
-            _ => 5,
-        };
+/// In-process implementation of `TokenBucketsCache`, for the tests and the deployments
+/// with a single instance of the api. It runs the same algorithms as the Redis scripts,
+/// the buckets are not shared between instances.
+#[derive(Clone)]
+pub struct TokenBucketsCacheMemory {
+    /// The buckets, shared by the clones of the cache.
+    store: Arc<MemoryStore<BucketFields>>,
+    /// Prefix used for the keys, the same as Redis.
+    prefix: String,
+}
 
-        let new_tokens = tokens.saturating_sub(used_tokens);
+impl Default for TokenBucketsCacheMemory {
+    fn default() -> Self {
+        TokenBucketsCacheMemory::new(Arc::new(SystemClock))
+    }
+}
 
-        if new_tokens == 0 {
-            return Err(RateLimitError::RateLimitExceeded);
+impl TokenBucketsCacheMemory {
+    /// Creates an empty `TokenBucketsCacheMemory`.
+    ///
+    /// # Arguments
+    /// * `clock` - The source of the time of the refills and expirations.
+    ///
+    /// # Returns
+    /// A new `TokenBucketsCacheMemory` instance.
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        TokenBucketsCacheMemory {
+            store: Arc::new(MemoryStore::new(clock)),
+            prefix: "ratelimit".to_string(),
         }
+    }
+}
 
-        log::info!("Consuming tokens for id: {}, method: {:?}", id, method);
+#[async_trait::async_trait]
+impl TokenBucketsCache for TokenBucketsCacheMemory {
+    async fn save_bucket(
+        &self,
+        id: &str,
+        bucket: &TokenBucket,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<()> {
+        self.store.set(
+            &format!("{}:{}", self.prefix, id),
+            bucket.into(),
+            Some(bucket.ttl(policy) * 1000),
+        );
+        Ok(())
+    }
 
-        self.client
-            .hset(
-                &format!("{}:{}", self.prefix, id),
-                "tokens",
-                &(tokens - used_tokens).to_string(),
-            )
-            .await?;
+    async fn create_bucket(&self, id: &str, policy: &RateLimitPolicy) -> RateLimiterResult<()> {
+        self.save_bucket(id, &TokenBucket::full(policy), policy)
+            .await
+    }
 
-        Ok(())
+    async fn consume_tokens(
+        &self,
+        id: &str,
+        policy: &RateLimitPolicy,
+    ) -> RateLimiterResult<BucketStatus> {
+        let algorithm = algorithm(policy.algorithm);
+        let result = self
+            .store
+            .update(&format!("{}:{}", self.prefix, id), |fields, now| {
+                let mut fields = fields.cloned().unwrap_or_default();
+                let (result, ttl) = algorithm.consume_in_memory(&mut fields, policy, now);
+                (ttl.map(|ttl| (fields, Some(ttl))), result)
+            });
+
+        BucketStatus::from_script(result)
+    }
+
+    async fn delete_buckets_without_ttl(&self) -> RateLimiterResult<u64> {
+        Ok(self.store.remove_without_ttl())
     }
 }
 
 mod tests {
     use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::clock::ManualClock;
     use once_cell::sync::Lazy;
 
     static CONFIG: Lazy<api_configs::config::Config> = Lazy::new(api_configs::config::Config::init);
     #[allow(dead_code)]
+    static READ: Lazy<RateLimitPolicy> = Lazy::new(|| policy("read", 1));
+    #[allow(dead_code)]
+    static WRITE: Lazy<RateLimitPolicy> = Lazy::new(|| policy("write", 5));
+    #[allow(dead_code)]
     static CLIENT: Lazy<RedisClient> =
         Lazy::new(|| crate::redis::get_redis_client(&CONFIG.clone()));
 
@@ -263,7 +344,7 @@ mod tests {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
         let id = "test-1";
-        cache.create_bucket(id).await.unwrap();
+        cache.create_bucket(id, &READ).await.unwrap();
 
         let bucket_from_cache = cache
             .client
@@ -295,32 +376,19 @@ mod tests {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
         let id = "test-2";
-        cache.create_bucket(id).await.unwrap();
-
-        let bucket_from_cache = cache
-            .client
-            .hget_multiple(
-                &format!("ratelimit:{}", id),
-                vec![
-                    "capacity".to_string(),
-                    "tokens".to_string(),
-                    "last_refill_time".to_string(),
-                ],
-            )
-            .await
-            .unwrap();
-
-        let mut bucket = TokenBucket::from_cache(bucket_from_cache);
-        bucket.tokens = bucket.tokens - 50;
-        let initial_tokens = bucket.tokens;
-
-        // Simuler l'écoulement du temps (par exemple 5 secondes)
-        std::thread::sleep(std::time::Duration::new(5, 0));
+        let bucket = TokenBucket {
+            tokens: 50,
+            // une seconde s'est écoulée depuis le dernier refill
+            last_refill_time: Utc::now() - chrono::Duration::seconds(1),
+            ..Default::default()
+        };
+        cache.save_bucket(id, &bucket, &READ).await.unwrap();
 
-        bucket.refill(); // Refill the bucket
+        let status = cache.consume_tokens(id, &READ).await.unwrap();
 
-        // Vérifier que le nombre de tokens a été mis à jour
-        assert!(bucket.tokens > initial_tokens);
+        // 10 tokens added, 1 consumed
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 59);
 
         cache
             .client
@@ -335,7 +403,7 @@ mod tests {
 
         let id = "test-3";
         let bucket = TokenBucket::default(); // Création du bucket par défaut
-        cache.save_bucket(id, &bucket).await.unwrap(); // Sauvegarde dans Redis
+        cache.save_bucket(id, &bucket, &READ).await.unwrap(); // Sauvegarde dans Redis
 
         // Vérification que les données ont bien été sauvegardées dans Redis simulé
         let saved_bucket = cache
@@ -384,40 +452,44 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn test_error_handling_refill() {
+    async fn test_consume_tokens_creates_missing_bucket() {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
         let id = "non-existent-id-2";
+        cache
+            .client
+            .delete(&format!("ratelimit:{}", id))
+            .await
+            .unwrap();
 
-        // Essayons de récupérer un bucket qui n'existe pas
-        let result = cache.refill_bucket(id).await;
+        let status = cache.consume_tokens(id, &READ).await.unwrap();
 
-        assert!(result.is_err());
+        assert_eq!(
+            status,
+            BucketStatus {
+                allowed: true,
+                remaining: 99,
+                retry_after_ms: 0,
+                reset_after_ms: 100
+            }
+        );
+
+        cache
+            .client
+            .delete(&format!("ratelimit:{}", id))
+            .await
+            .unwrap();
     }
 
     #[actix_rt::test]
-    async fn test_from_cache() {
+    async fn test_read_bucket_from_cache() {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
         let id = "test-4";
-        cache.create_bucket(id).await.unwrap();
+        cache.create_bucket(id, &READ).await.unwrap();
 
-        // Récupérer les données du bucket depuis Redis
-        let bucket_from_cache = cache
-            .client
-            .hget_multiple(
-                &format!("ratelimit:{}", id),
-                vec![
-                    "capacity".to_string(),
-                    "tokens".to_string(),
-                    "last_refill_time".to_string(),
-                ],
-            )
-            .await
-            .unwrap();
-
-        // Vérifier que le `TokenBucket` est correctement créé à partir du cache
-        let bucket = TokenBucket::from_cache(bucket_from_cache);
+        // Récupérer le bucket depuis Redis
+        let bucket = cache.buckets.get_fields(id).await.unwrap().unwrap();
         assert_eq!(bucket.capacity, 100);
         assert_eq!(bucket.tokens, 100);
 
@@ -433,28 +505,39 @@ mod tests {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
         let id = "test-5";
-        cache.create_bucket(id).await.unwrap();
+        cache.create_bucket(id, &READ).await.unwrap();
 
-        cache.consume_tokens(id, &Method::GET).await.unwrap();
+        let status = cache.consume_tokens(id, &READ).await.unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 99);
 
-        // Récupérer les données du bucket depuis Redis
-        let bucket_from_cache = cache
+        // Récupérer le bucket depuis Redis
+        let bucket = cache.buckets.get_fields(id).await.unwrap().unwrap();
+        assert_eq!(bucket.capacity, 100);
+        assert_eq!(bucket.tokens, 99);
+
+        cache
             .client
-            .hget_multiple(
-                &format!("ratelimit:{}", id),
-                vec![
-                    "capacity".to_string(),
-                    "tokens".to_string(),
-                    "last_refill_time".to_string(),
-                ],
-            )
+            .delete(&format!("ratelimit:{}", id))
             .await
             .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_big_consume_tokens() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-6";
+        cache.create_bucket(id, &READ).await.unwrap();
+
+        let status = cache.consume_tokens(id, &WRITE).await.unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 95);
 
-        // Vérifier que le `TokenBucket` est correctement créé à partir du cache
-        let bucket = TokenBucket::from_cache(bucket_from_cache);
+        // Récupérer le bucket depuis Redis
+        let bucket = cache.buckets.get_fields(id).await.unwrap().unwrap();
         assert_eq!(bucket.capacity, 100);
-        assert_eq!(bucket.tokens, 99);
+        assert_eq!(bucket.tokens, 95);
 
         cache
             .client
@@ -464,32 +547,81 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn test_big_consume_tokens() {
+    async fn test_consume_tokens_gives_retry_after_when_empty() {
         let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
 
-        let id = "test-6";
-        cache.create_bucket(id).await.unwrap();
+        let id = "test-7";
+        let bucket = TokenBucket {
+            tokens: 2,
+            ..Default::default()
+        };
+        cache.save_bucket(id, &bucket, &READ).await.unwrap();
 
-        cache.consume_tokens(id, &Method::POST).await.unwrap();
+        let status = cache.consume_tokens(id, &WRITE).await.unwrap();
 
-        // Récupérer les données du bucket depuis Redis
-        let bucket_from_cache = cache
+        // 3 tokens are missing, at 10 tokens per second
+        assert!(!status.allowed);
+        assert_eq!(status.remaining, 2);
+        assert!(status.retry_after_ms > 0 && status.retry_after_ms <= 300);
+
+        cache
             .client
-            .hget_multiple(
-                &format!("ratelimit:{}", id),
-                vec![
-                    "capacity".to_string(),
-                    "tokens".to_string(),
-                    "last_refill_time".to_string(),
-                ],
-            )
+            .delete(&format!("ratelimit:{}", id))
             .await
             .unwrap();
+    }
 
-        // Vérifier que le `TokenBucket` est correctement créé à partir du cache
-        let bucket = TokenBucket::from_cache(bucket_from_cache);
-        assert_eq!(bucket.capacity, 100);
-        assert_eq!(bucket.tokens, 95);
+    #[actix_rt::test]
+    async fn test_concurrent_consume_tokens_do_not_overspend() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-8";
+        cache.create_bucket(id, &READ).await.unwrap();
+
+        // 30 requests of 5 tokens en même temps pour un bucket de 100 tokens
+        let results =
+            futures_util::future::join_all((0..30).map(|_| cache.consume_tokens(id, &WRITE))).await;
+
+        let allowed = results
+            .into_iter()
+            .filter(|status| status.as_ref().unwrap().allowed)
+            .count();
+
+        // 20 requests, plus one if the bucket has been refilled during the test
+        assert!((20..=21).contains(&allowed));
+
+        cache
+            .client
+            .delete(&format!("ratelimit:{}", id))
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_consume_tokens_follows_the_policy() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-9";
+        cache
+            .client
+            .delete(&format!("ratelimit:{}", id))
+            .await
+            .unwrap();
+
+        // 2 requests per minute
+        let policy = RateLimitPolicy {
+            capacity: 2,
+            refill_amount: 2,
+            refill_period: 60,
+            ..policy("strict", 1)
+        };
+
+        assert!(cache.consume_tokens(id, &policy).await.unwrap().allowed);
+        assert!(cache.consume_tokens(id, &policy).await.unwrap().allowed);
+
+        let status = cache.consume_tokens(id, &policy).await.unwrap();
+        assert!(!status.allowed);
+        assert!(status.retry_after_ms > 29_000 && status.retry_after_ms <= 30_000);
 
         cache
             .client
@@ -497,4 +629,147 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[actix_rt::test]
+    async fn test_buckets_expire_once_refilled() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        let id = "test-10";
+        let key = format!("ratelimit:{}", id);
+        cache.create_bucket(id, &READ).await.unwrap();
+        // 100 tokens at 10 tokens per second
+        assert_eq!(cache.client.ttl(&key).await.unwrap(), 10);
+
+        // 2 tokens per minute
+        let policy = RateLimitPolicy {
+            capacity: 2,
+            refill_amount: 2,
+            refill_period: 60,
+            ..policy("strict", 1)
+        };
+        cache.consume_tokens(id, &policy).await.unwrap();
+        assert_eq!(cache.client.ttl(&key).await.unwrap(), 60);
+
+        // the same ttl as the script for the buckets written by the cache
+        cache.client.delete(&key).await.unwrap();
+        cache.create_bucket(id, &policy).await.unwrap();
+        assert_eq!(cache.client.ttl(&key).await.unwrap(), 60);
+        let bucket = TokenBucket {
+            capacity: 2,
+            tokens: 0,
+            last_refill_time: Utc::now(),
+        };
+        cache.save_bucket(id, &bucket, &policy).await.unwrap();
+        assert_eq!(cache.client.ttl(&key).await.unwrap(), 60);
+
+        cache.client.delete(&key).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_buckets_without_ttl() {
+        let cache = TokenBucketsCacheRedis::new(CLIENT.clone());
+
+        // un bucket écrit avant l'ajout des ttl
+        let legacy = "ratelimit:test-11";
+        cache.client.hset(legacy, "tokens", "100").await.unwrap();
+        let id = "test-12";
+        cache.create_bucket(id, &READ).await.unwrap();
+
+        let deleted = cache.delete_buckets_without_ttl().await.unwrap();
+
+        assert!(deleted >= 1);
+        assert!(!cache.client.exists(legacy).await.unwrap());
+        assert!(cache
+            .client
+            .exists(&format!("ratelimit:{}", id))
+            .await
+            .unwrap());
+
+        cache
+            .client
+            .delete(&format!("ratelimit:{}", id))
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_buckets_are_refilled_by_the_clock() {
+        let clock = ManualClock::default();
+        let cache = TokenBucketsCacheMemory::new(Arc::new(clock.clone()));
+
+        // 2 requests per minute
+        let policy = RateLimitPolicy {
+            capacity: 2,
+            refill_amount: 2,
+            refill_period: 60,
+            ..policy("strict", 1)
+        };
+
+        assert!(cache.consume_tokens("test", &policy).await.unwrap().allowed);
+        assert!(cache.consume_tokens("test", &policy).await.unwrap().allowed);
+
+        let status = cache.consume_tokens("test", &policy).await.unwrap();
+        assert_eq!(
+            status,
+            BucketStatus {
+                allowed: false,
+                remaining: 0,
+                retry_after_ms: 30_000,
+                reset_after_ms: 60_000
+            }
+        );
+        // the other callers have their own bucket
+        assert!(
+            cache
+                .consume_tokens("other", &policy)
+                .await
+                .unwrap()
+                .allowed
+        );
+
+        clock.advance(std::time::Duration::from_secs(30));
+        assert!(cache.consume_tokens("test", &policy).await.unwrap().allowed);
+        assert!(!cache.consume_tokens("test", &policy).await.unwrap().allowed);
+
+        // un bucket inactif expire une fois plein
+        clock.advance(std::time::Duration::from_secs(60));
+        assert_eq!(cache.store.ttl("ratelimit:test"), -2);
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_buckets_use_the_saved_bucket() {
+        let clock = ManualClock::default();
+        let cache = TokenBucketsCacheMemory::new(Arc::new(clock.clone()));
+
+        let bucket = TokenBucket {
+            tokens: 50,
+            // une seconde s'est écoulée depuis le dernier refill
+            last_refill_time: DateTime::from_timestamp_millis(clock.now_millis() - 1_000).unwrap(),
+            ..Default::default()
+        };
+        cache.save_bucket("test", &bucket, &READ).await.unwrap();
+
+        // 10 tokens added, 1 consumed
+        let status = cache.consume_tokens("test", &READ).await.unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 59);
+
+        assert_eq!(cache.delete_buckets_without_ttl().await.unwrap(), 0);
+    }
+
+    #[allow(dead_code)]
+    fn policy(name: &str, cost: u64) -> RateLimitPolicy {
+        RateLimitPolicy {
+            name: name.to_string(),
+            path_prefix: String::new(),
+            methods: vec![],
+            caller: Default::default(),
+            algorithm: Default::default(),
+            capacity: 100,
+            refill_amount: 10,
+            refill_period: 1,
+            cost,
+            opt_in: false,
+        }
+    }
 }
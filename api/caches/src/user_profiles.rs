@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use api_configs::config::Config;
+use api_types::user::SafeUser;
+
+use crate::{
+    cache::Cache,
+    clock::Clock,
+    invalidation::{Invalidation, InvalidationBus},
+    memory::MemoryStore,
+    redis::{RedisClient, RedisRepositoryResult},
+};
+
+/// Namespace of the profiles, for the keys in Redis and the invalidations.
+const NAMESPACE: &str = "user_profiles";
+
+/// Trait for the cached profiles of the users, read before the database.
+#[async_trait::async_trait]
+pub trait UserProfilesCache: Send + Sync + 'static {
+    /// Returns the cached profile of a user, if any.
+    async fn get_profile(&self, id: i32) -> RedisRepositoryResult<Option<SafeUser>>;
+
+    /// Caches the profile of a user until its ttl.
+    async fn save_profile(&self, user: &SafeUser) -> RedisRepositoryResult<()>;
+
+    /// Removes the profile of a user, after a change or a deletion.
+    async fn invalidate_profile(&self, id: i32) -> RedisRepositoryResult<()>;
+}
+
+/// Redis-based implementation of `UserProfilesCache`, the profiles are stored in JSON
+/// under `user_profiles:{id}`.
+#[derive(Clone)]
+pub struct UserProfilesCacheRedis {
+    profiles: Cache<i32, SafeUser>,
+}
+
+impl UserProfilesCacheRedis {
+    /// Creates a new instance of `UserProfilesCacheRedis`.
+    ///
+    /// # Arguments
+    /// * `client` - The Redis client instance.
+    /// * `config` - Configuration settings, for the ttl of the profiles.
+    ///
+    /// # Returns
+    /// A new `UserProfilesCacheRedis` instance.
+    pub fn new(client: RedisClient, config: &Config) -> Self {
+        UserProfilesCacheRedis {
+            profiles: Cache::new(client, NAMESPACE).ttl(config.user_profile_cache_ttl),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserProfilesCache for UserProfilesCacheRedis {
+    async fn get_profile(&self, id: i32) -> RedisRepositoryResult<Option<SafeUser>> {
+        self.profiles.get(&id).await
+    }
+
+    async fn save_profile(&self, user: &SafeUser) -> RedisRepositoryResult<()> {
+        self.profiles.set(&user.id, user).await
+    }
+
+    async fn invalidate_profile(&self, id: i32) -> RedisRepositoryResult<()> {
+        self.profiles.delete(&id).await
+    }
+}
+
+/// In-process implementation of `UserProfilesCache`, for the tests and the deployments
+/// with a single instance of the api. With several instances, the invalidations must go
+/// through an `InvalidationBus`, see `with_invalidation_bus`.
+#[derive(Clone)]
+pub struct UserProfilesCacheMemory {
+    /// The profiles by user id, shared by the clones of the cache.
+    store: Arc<MemoryStore<SafeUser>>,
+    /// Ttl of the profiles in seconds.
+    ttl: i64,
+    /// Propagates the invalidations to the other instances.
+    bus: Option<Arc<dyn InvalidationBus>>,
+}
+
+impl UserProfilesCacheMemory {
+    /// Creates an empty `UserProfilesCacheMemory`.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration settings, for the ttl of the profiles.
+    /// * `clock` - The source of the time of the expirations.
+    ///
+    /// # Returns
+    /// A new `UserProfilesCacheMemory` instance.
+    pub fn new(config: &Config, clock: Arc<dyn Clock>) -> Self {
+        UserProfilesCacheMemory {
+            store: Arc::new(MemoryStore::new(clock)),
+            ttl: config.user_profile_cache_ttl,
+            bus: None,
+        }
+    }
+
+    /// Invalidates the profiles through a bus: the profiles invalidated by any instance are
+    /// removed from this cache, and `invalidate_profile` publishes on the bus.
+    pub fn with_invalidation_bus(mut self, bus: Arc<dyn InvalidationBus>) -> Self {
+        let store = Arc::clone(&self.store);
+        bus.listen(
+            NAMESPACE,
+            Arc::new(move |invalidation| match invalidation {
+                Invalidation::Key { key, .. } => {
+                    store.remove(key);
+                }
+                Invalidation::Namespace { .. } => store.clear(),
+            }),
+        );
+
+        self.bus = Some(bus);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl UserProfilesCache for UserProfilesCacheMemory {
+    async fn get_profile(&self, id: i32) -> RedisRepositoryResult<Option<SafeUser>> {
+        Ok(self.store.get(&id.to_string()))
+    }
+
+    async fn save_profile(&self, user: &SafeUser) -> RedisRepositoryResult<()> {
+        self.store
+            .set(&user.id.to_string(), user.clone(), Some(self.ttl * 1000));
+        Ok(())
+    }
+
+    async fn invalidate_profile(&self, id: i32) -> RedisRepositoryResult<()> {
+        // retiré même si la publication échoue
+        self.store.remove(&id.to_string());
+        match &self.bus {
+            Some(bus) => bus.publish(Invalidation::key(NAMESPACE, id)).await,
+            None => Ok(()),
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use crate::clock::ManualClock;
+
+    #[allow(dead_code)]
+    fn user(id: i32) -> SafeUser {
+        SafeUser {
+            id,
+            pseudo: "tester".to_string(),
+            first_name: None,
+            last_name: Some("Doe".to_string()),
+            email: "tester@test.com".to_string(),
+            created_at: chrono::DateTime::from_timestamp(1_700_000_000, 0)
+                .unwrap()
+                .naive_utc(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_redis_profiles() {
+        let config = Config::init();
+        let client = crate::redis::get_redis_client(&config);
+        let cache = UserProfilesCacheRedis::new(Arc::clone(&client), &config);
+        let id = -45;
+
+        cache.save_profile(&user(id)).await.unwrap();
+        assert_eq!(cache.get_profile(id).await.unwrap(), Some(user(id)));
+        assert_eq!(
+            crate::redis::RedisRepository::ttl(&client, "user_profiles:-45")
+                .await
+                .unwrap(),
+            config.user_profile_cache_ttl
+        );
+
+        cache.invalidate_profile(id).await.unwrap();
+        assert_eq!(cache.get_profile(id).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_profiles_expire() {
+        let config = Config::init();
+        let clock = ManualClock::default();
+        let cache = UserProfilesCacheMemory::new(&config, Arc::new(clock.clone()));
+
+        cache.save_profile(&user(1)).await.unwrap();
+        assert_eq!(cache.get_profile(1).await.unwrap(), Some(user(1)));
+
+        clock.advance(std::time::Duration::from_secs(
+            config.user_profile_cache_ttl as u64,
+        ));
+        assert_eq!(cache.get_profile(1).await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_memory_profiles_invalidated_by_other_instance() {
+        let config = Config::init();
+        let bus = Arc::new(crate::invalidation::InvalidationBusMemory::default());
+        let instance = |bus: Arc<dyn InvalidationBus>| {
+            UserProfilesCacheMemory::new(&config, Arc::new(ManualClock::default()))
+                .with_invalidation_bus(bus)
+        };
+        let first = instance(bus.clone());
+        let second = instance(bus.clone());
+
+        first.save_profile(&user(1)).await.unwrap();
+        second.save_profile(&user(1)).await.unwrap();
+        second.save_profile(&user(2)).await.unwrap();
+
+        first.invalidate_profile(1).await.unwrap();
+        assert_eq!(second.get_profile(1).await.unwrap(), None);
+        assert_eq!(second.get_profile(2).await.unwrap(), Some(user(2)));
+
+        bus.publish(Invalidation::all(NAMESPACE)).await.unwrap();
+        assert_eq!(second.get_profile(2).await.unwrap(), None);
+    }
+}
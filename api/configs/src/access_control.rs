@@ -0,0 +1,103 @@
+use std::{env, net::IpAddr};
+
+use ipnet::IpNet;
+
+use crate::config::{optional_i64, parse_ip_nets};
+
+/// Static allow and deny lists, and automatic bans, durations are in seconds.
+/// Callers are identified like the buckets of the rate limiter: `user:{id}` or `client:{id}`.
+#[derive(Clone, Debug, Default)]
+pub struct AccessControlInfo {
+    /// ips never denied, banned or rate limited, like the internal monitors
+    pub allowed_ips: Vec<IpNet>,
+    /// ips always refused
+    pub denied_ips: Vec<IpNet>,
+    /// callers never denied, banned or rate limited
+    pub allowed_callers: Vec<String>,
+    /// callers always refused
+    pub denied_callers: Vec<String>,
+    /// users allowed to manage the bans from an interactive login
+    pub admin_users: Vec<i32>,
+    /// 401 and 429 responses before an automatic ban, 0 disables the automatic bans
+    pub auto_ban_threshold: i64,
+    /// a response is forgotten after this window
+    pub auto_ban_window: i64,
+    pub auto_ban_duration: i64,
+}
+
+impl AccessControlInfo {
+    /// Read the lists from comma separated environment variables:
+    /// `ACCESS_ALLOWED_IPS`, `ACCESS_DENIED_IPS` (CIDR blocks or addresses),
+    /// `ACCESS_ALLOWED_CALLERS`, `ACCESS_DENIED_CALLERS` and `ACCESS_ADMIN_USERS` (user ids).
+    pub fn from_env() -> Self {
+        let ip_nets = |name: &str| {
+            env::var(name)
+                .map(|value| parse_ip_nets(name, &value))
+                .unwrap_or_default()
+        };
+
+        AccessControlInfo {
+            allowed_ips: ip_nets("ACCESS_ALLOWED_IPS"),
+            denied_ips: ip_nets("ACCESS_DENIED_IPS"),
+            allowed_callers: list("ACCESS_ALLOWED_CALLERS"),
+            denied_callers: list("ACCESS_DENIED_CALLERS"),
+            admin_users: list("ACCESS_ADMIN_USERS")
+                .iter()
+                .map(|id| {
+                    id.parse().unwrap_or_else(|_| {
+                        panic!("ACCESS_ADMIN_USERS contains an invalid id: {}", id)
+                    })
+                })
+                .collect(),
+            auto_ban_threshold: optional_i64("AUTO_BAN_THRESHOLD", 0),
+            auto_ban_window: optional_i64("AUTO_BAN_WINDOW", 60),
+            auto_ban_duration: optional_i64("AUTO_BAN_DURATION", 15 * 60),
+        }
+    }
+
+    pub fn is_allowed(&self, ip: Option<IpAddr>, caller: Option<&str>) -> bool {
+        matches(&self.allowed_ips, &self.allowed_callers, ip, caller)
+    }
+
+    pub fn is_denied(&self, ip: Option<IpAddr>, caller: Option<&str>) -> bool {
+        matches(&self.denied_ips, &self.denied_callers, ip, caller)
+    }
+}
+
+fn matches(ips: &[IpNet], callers: &[String], ip: Option<IpAddr>, caller: Option<&str>) -> bool {
+    ip.is_some_and(|ip| ips.iter().any(|net| net.contains(&ip)))
+        || caller.is_some_and(|caller| callers.iter().any(|el| el == caller))
+}
+
+fn list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|el| !el.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+
+    #[test]
+    fn test_lists_match_ips_and_callers() {
+        let info = AccessControlInfo {
+            allowed_ips: vec!["10.0.0.0/8".parse().unwrap()],
+            denied_callers: vec!["user:66".to_string()],
+            ..Default::default()
+        };
+        let ip = |value: &str| Some(value.parse().unwrap());
+
+        assert!(info.is_allowed(ip("10.1.2.3"), None));
+        assert!(!info.is_allowed(ip("8.8.8.8"), Some("user:1")));
+        assert!(info.is_denied(ip("8.8.8.8"), Some("user:66")));
+        assert!(!info.is_denied(None, None));
+    }
+}
@@ -1,6 +1,8 @@
 use once_cell::sync::Lazy;
 
+pub mod access_control;
 pub mod config;
 pub mod parse;
+pub mod rate_limit;
 
 pub static CONFIG: Lazy<config::Config> = Lazy::new(config::Config::init);
@@ -69,13 +69,7 @@ impl<T: std::cmp::PartialEq> Choices<T> {
     where
         P: Into<T>,
     {
-        let conversion = value.try_into();
-
-        if conversion.is_err() && !self.has_default_choice {
-            return Err(Error::InvalidEnvVar);
-        }
-
-        let value_converted: T = conversion.unwrap();
+        let value_converted: T = value.into();
 
         if !self.choices.contains(&value_converted) {
             if !self.has_default_choice {
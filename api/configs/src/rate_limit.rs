@@ -0,0 +1,310 @@
+use std::env;
+
+use serde::Deserialize;
+
+/// Callers a rate limit policy applies to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitCaller {
+    #[default]
+    Any,
+    /// requests without credentials
+    Anonymous,
+    /// requests with a bearer token or an api key
+    Authenticated,
+}
+
+impl RateLimitCaller {
+    fn matches(&self, authenticated: bool) -> bool {
+        match self {
+            RateLimitCaller::Any => true,
+            RateLimitCaller::Anonymous => !authenticated,
+            RateLimitCaller::Authenticated => authenticated,
+        }
+    }
+}
+
+/// Algorithm counting the requests of a policy.
+/// Every algorithm allows `capacity` tokens at once and `refill_amount` tokens every `refill_period`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithmKind {
+    /// tokens refilled continuously, allows bursts up to the capacity
+    #[default]
+    TokenBucket,
+    /// weighted counters of two fixed windows, the window lasts `capacity * refill_period / refill_amount`,
+    /// precise quotas such as 5 requests per minute
+    SlidingWindow,
+    /// generic cell rate algorithm, spaces the requests evenly with a burst of `capacity`
+    Gcra,
+}
+
+/// A rate limit policy, each policy has its own bucket per caller.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RateLimitPolicy {
+    /// name of the policy, also used in the key of the buckets
+    pub name: String,
+    /// the policy applies to the paths starting with this prefix
+    #[serde(default)]
+    pub path_prefix: String,
+    /// the policy applies to these methods, every method when empty
+    #[serde(default)]
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub caller: RateLimitCaller,
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithmKind,
+    /// maximum number of tokens of the bucket
+    pub capacity: u64,
+    /// tokens added to the bucket every `refill_period` seconds
+    pub refill_amount: u64,
+    pub refill_period: u64,
+    /// tokens consumed by a request
+    pub cost: u64,
+    /// an opt-in policy is only applied by the routes asking for it by its name
+    #[serde(default)]
+    pub opt_in: bool,
+}
+
+impl RateLimitPolicy {
+    /// Check the values used by the algorithms: a zero would divide by zero in the Lua scripts,
+    /// and the durations are computed in milliseconds.
+    pub fn validate(&self) -> Result<(), String> {
+        for (field, value) in [
+            ("capacity", self.capacity),
+            ("refill_amount", self.refill_amount),
+            ("refill_period", self.refill_period),
+        ] {
+            if value == 0 {
+                return Err(format!(
+                    "{} of the policy {} must be positive",
+                    field, self.name
+                ));
+            }
+        }
+
+        // le bucket le plus long à remplir : capacity * refill_period secondes
+        self.capacity
+            .checked_mul(self.refill_period)
+            .and_then(|seconds| seconds.checked_mul(1000))
+            .ok_or_else(|| {
+                format!(
+                    "capacity * refill_period of the policy {} is too large",
+                    self.name
+                )
+            })?;
+
+        Ok(())
+    }
+
+    /// Check if the policy applies to a request.
+    pub fn matches(&self, path: &str, method: &str, authenticated: bool) -> bool {
+        !self.opt_in
+            && path.starts_with(&self.path_prefix)
+            && (self.methods.is_empty()
+                || self
+                    .methods
+                    .iter()
+                    .any(|el| el.eq_ignore_ascii_case(method)))
+            && self.caller.matches(authenticated)
+    }
+}
+
+/// Behavior of the rate limiter when Redis is unavailable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitDegradation {
+    /// the requests are not limited
+    FailOpen,
+    /// the requests are refused with a 503
+    FailClosed,
+    /// the requests are limited by an in-process limiter, per instance of the api
+    #[default]
+    Local,
+}
+
+/// The rate limit policies, in order of priority.
+#[derive(Clone, Debug)]
+pub struct RateLimitInfo {
+    pub policies: Vec<RateLimitPolicy>,
+    pub degradation: RateLimitDegradation,
+}
+
+impl RateLimitInfo {
+    /// Read the policies from the `RATE_LIMIT_POLICIES` environment variable (a JSON array),
+    /// the default policies are used when it is not set.
+    /// The behavior during a Redis outage is read from `RATE_LIMIT_DEGRADATION`
+    /// (`fail_open`, `fail_closed` or `local`).
+    pub fn from_env() -> Self {
+        let policies = env::var("RATE_LIMIT_POLICIES")
+            .map(|value| {
+                serde_json::from_str(&value)
+                    .unwrap_or_else(|err| panic!("RATE_LIMIT_POLICIES is invalid: {}", err))
+            })
+            .unwrap_or_else(|_| Self::default_policies());
+
+        let degradation = env::var("RATE_LIMIT_DEGRADATION")
+            .map(|value| {
+                serde_json::from_value(serde_json::Value::String(value.trim().to_lowercase()))
+                    .unwrap_or_else(|err| panic!("RATE_LIMIT_DEGRADATION is invalid: {}", err))
+            })
+            .unwrap_or_default();
+
+        let info = RateLimitInfo {
+            policies,
+            degradation,
+        };
+        info.validate()
+            .unwrap_or_else(|err| panic!("RATE_LIMIT_POLICIES is invalid: {}", err));
+
+        info
+    }
+
+    /// Check every policy, and that two policies do not share a name (and their buckets).
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, policy) in self.policies.iter().enumerate() {
+            policy.validate()?;
+            if self.policies[..i]
+                .iter()
+                .any(|other| other.name == policy.name)
+            {
+                return Err(format!("the policy {} is defined twice", policy.name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the first policy applying to a request.
+    pub fn find(&self, path: &str, method: &str, authenticated: bool) -> Option<&RateLimitPolicy> {
+        self.policies
+            .iter()
+            .find(|policy| policy.matches(path, method, authenticated))
+    }
+
+    /// Get a policy by its name, for the routes opting into a policy.
+    pub fn get(&self, name: &str) -> Option<&RateLimitPolicy> {
+        self.policies.iter().find(|policy| policy.name == name)
+    }
+
+    fn default_policies() -> Vec<RateLimitPolicy> {
+        vec![
+            RateLimitPolicy {
+                name: "login".to_string(),
+                path_prefix: String::new(),
+                methods: vec![],
+                caller: RateLimitCaller::Any,
+                algorithm: RateLimitAlgorithmKind::SlidingWindow,
+                capacity: 5,
+                refill_amount: 5,
+                refill_period: 60,
+                cost: 1,
+                opt_in: true,
+            },
+            RateLimitPolicy {
+                name: "read".to_string(),
+                path_prefix: String::new(),
+                methods: vec!["GET".to_string(), "HEAD".to_string()],
+                caller: RateLimitCaller::Any,
+                algorithm: RateLimitAlgorithmKind::TokenBucket,
+                capacity: 100,
+                refill_amount: 10,
+                refill_period: 1,
+                cost: 1,
+                opt_in: false,
+            },
+            RateLimitPolicy {
+                name: "write".to_string(),
+                path_prefix: String::new(),
+                methods: vec![],
+                caller: RateLimitCaller::Any,
+                algorithm: RateLimitAlgorithmKind::TokenBucket,
+                capacity: 100,
+                refill_amount: 10,
+                refill_period: 1,
+                cost: 5,
+                opt_in: false,
+            },
+        ]
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+
+    #[test]
+    fn test_find_policy_by_path_method_and_caller() {
+        let info = RateLimitInfo {
+            policies: serde_json::from_str(
+                r#"[
+                    {"name": "login", "algorithm": "sliding_window", "capacity": 5, "refill_amount": 5, "refill_period": 60, "cost": 1, "opt_in": true},
+                    {"name": "admin", "path_prefix": "/api/v1/users", "caller": "authenticated", "capacity": 10, "refill_amount": 1, "refill_period": 1, "cost": 1},
+                    {"name": "read", "methods": ["GET"], "capacity": 100, "refill_amount": 10, "refill_period": 1, "cost": 1},
+                    {"name": "write", "capacity": 100, "refill_amount": 10, "refill_period": 1, "cost": 5}
+                ]"#,
+            )
+            .unwrap(),
+            degradation: RateLimitDegradation::default(),
+        };
+
+        let name = |path, method, authenticated| {
+            info.find(path, method, authenticated)
+                .map(|policy| policy.name.as_str())
+        };
+
+        assert_eq!(name("/api/v1/users/1", "GET", true), Some("admin"));
+        assert_eq!(name("/api/v1/users/1", "GET", false), Some("read"));
+        assert_eq!(name("/api/v1/users/1", "post", false), Some("write"));
+        // opt-in policies are never found by the request
+        assert_eq!(name("/api/v1/auth/login", "POST", false), Some("write"));
+        assert_eq!(info.get("login").map(|policy| policy.capacity), Some(5));
+        assert_eq!(
+            info.get("login").map(|policy| policy.algorithm),
+            Some(RateLimitAlgorithmKind::SlidingWindow)
+        );
+        assert_eq!(
+            info.get("read").map(|policy| policy.algorithm),
+            Some(RateLimitAlgorithmKind::TokenBucket)
+        );
+    }
+
+    #[test]
+    fn test_invalid_policies_are_rejected() {
+        let info = |policies: &str| RateLimitInfo {
+            policies: serde_json::from_str(policies).unwrap(),
+            degradation: RateLimitDegradation::default(),
+        };
+
+        assert_eq!(RateLimitInfo::from_env().validate(), Ok(()));
+        assert!(info(r#"[{"name": "read", "capacity": 100, "refill_amount": 10, "refill_period": 1, "cost": 1}]"#)
+            .validate()
+            .is_ok());
+
+        for field in ["capacity", "refill_amount", "refill_period"] {
+            let mut policy = serde_json::json!({"name": "read", "capacity": 100, "refill_amount": 10, "refill_period": 1, "cost": 1});
+            policy[field] = serde_json::json!(0);
+            assert_eq!(
+                info(&format!("[{}]", policy)).validate(),
+                Err(format!("{} of the policy read must be positive", field))
+            );
+        }
+
+        let overflowing = format!(
+            r#"[{{"name": "read", "capacity": 2, "refill_amount": 1, "refill_period": {}, "cost": 1}}]"#,
+            u64::MAX / 1000
+        );
+        assert!(info(&overflowing).validate().is_err());
+
+        let duplicated = info(
+            r#"[
+                {"name": "read", "capacity": 100, "refill_amount": 10, "refill_period": 1, "cost": 1},
+                {"name": "read", "methods": ["GET"], "capacity": 10, "refill_amount": 1, "refill_period": 1, "cost": 1}
+            ]"#,
+        );
+        assert_eq!(
+            duplicated.validate(),
+            Err("the policy read is defined twice".to_string())
+        );
+    }
+}
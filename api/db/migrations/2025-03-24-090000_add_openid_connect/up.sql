@@ -0,0 +1,25 @@
+ALTER TABLE oauth_clients
+  ADD COLUMN redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+  ADD COLUMN is_public BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE oauth_consents (
+  id SERIAL NOT NULL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  client_id INTEGER NOT NULL REFERENCES oauth_clients(id) ON DELETE CASCADE,
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (user_id, client_id)
+);
+
+CREATE TABLE oauth_authorization_codes (
+  id SERIAL NOT NULL PRIMARY KEY,
+  code_hash TEXT NOT NULL UNIQUE,
+  client_id INTEGER NOT NULL REFERENCES oauth_clients(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  redirect_uri TEXT NOT NULL,
+  scopes TEXT[] NOT NULL DEFAULT '{}',
+  code_challenge TEXT NOT NULL,
+  nonce TEXT,
+  expires_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
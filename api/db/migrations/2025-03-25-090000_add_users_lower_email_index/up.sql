@@ -0,0 +1,3 @@
+-- les emails sont comparés sans la casse, à la connexion comme à l'inscription
+-- (échoue si deux comptes ont le même email à la casse près, à fusionner avant)
+CREATE UNIQUE INDEX users_lower_email_key ON users (lower(email));
@@ -17,21 +17,80 @@ impl<C: Connection> CustomizeConnection<C, diesel::r2d2::Error> for TestCustomiz
 }
 
 use api_configs::config::Config;
+use api_errors::ServiceError;
+use tokio::sync::Semaphore;
 
-pub type Pool = Arc<r2d2::Pool<ConnectionManager<PgConnection>>>;
+use crate::repository::RepositoryResult;
+
+pub type Pool = Arc<DbPool>;
+
+/// Pool of connections running the blocking queries of Diesel out of the actix workers,
+/// on the blocking threads of tokio.
+/// At most one query per connection runs at the same time, the next ones wait for a
+/// connection without holding a thread.
+pub struct DbPool {
+    connections: r2d2::Pool<ConnectionManager<PgConnection>>,
+    /// One permit per connection of the pool.
+    permits: Semaphore,
+}
+
+impl DbPool {
+    pub fn new(connections: r2d2::Pool<ConnectionManager<PgConnection>>) -> Self {
+        DbPool {
+            permits: Semaphore::new(connections.max_size() as usize),
+            connections,
+        }
+    }
+
+    /// Run a query with a connection of the pool, on a blocking thread.
+    pub async fn run<T, F>(&self, query: F) -> RepositoryResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut PgConnection) -> RepositoryResult<T> + Send + 'static,
+    {
+        // le sémaphore n'est jamais fermé
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| connection_error())?;
+
+        let connections = self.connections.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut conn = connections.get().map_err(|_| connection_error())?;
+            query(&mut conn)
+        });
+
+        match task.await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            // la tâche est annulée quand le runtime s'arrête
+            Err(_) => Err(connection_error()),
+        }
+    }
+}
+
+/// No connection could be used in time, the client can retry later.
+fn connection_error() -> ServiceError {
+    ServiceError {
+        message: Some("Database temporarily unavailable".to_string()),
+        error_type: api_errors::ServiceErrorType::ServiceUnavailable,
+    }
+}
 
 /// This function establishes a connection to the database.
-/// It creates a new connection pool with a maximum size of 10.
+/// It creates a new connection pool of `DATABASE_POOL_SIZE` connections, 10 by default.
 /// The pool is wrapped in an Arc to allow it to be shared across threads.
 /// The connection pool is created using the database URL from the configuration.
 /// If the pool fails to be created, the function will panic.
 pub fn establish_connection(config: &Config) -> Pool {
     let manager = ConnectionManager::<PgConnection>::new(config.database_url.clone());
-    let pool: Pool = Arc::new(
+    let pool: Pool = Arc::new(DbPool::new(
         r2d2::Pool::builder()
+            .max_size(config.database_pool_size)
             .build(manager)
             .expect("Failed to create pool"),
-    );
+    ));
     pool
 }
 
@@ -40,13 +99,86 @@ pub fn establish_connection(config: &Config) -> Pool {
 /// begins a test transaction on each connection acquisition.
 pub fn establish_testing_connection(config: &Config) -> Pool {
     let manager = ConnectionManager::<PgConnection>::new(config.database_test_url.clone());
-    let pool: Pool = Arc::new(
+    let pool: Pool = Arc::new(DbPool::new(
         r2d2::Pool::builder()
             .test_on_check_out(true)
             .max_size(1)
             .connection_customizer(Box::new(TestCustomizer))
             .build(manager)
             .expect("Failed to create testing pool"),
-    );
+    ));
     pool
 }
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use diesel::RunQueryDsl;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::time::{Duration, Instant};
+
+    #[actix_rt::test]
+    async fn test_query_does_not_block_the_worker() {
+        let pool = establish_testing_connection(&Config::init());
+
+        // le runtime d'actix n'a qu'un thread, les ticks n'avancent que s'il est libre
+        let ticker = actix_rt::spawn(async {
+            let mut ticks = 0;
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(200) {
+                actix_rt::time::sleep(Duration::from_millis(10)).await;
+                ticks += 1;
+            }
+            ticks
+        });
+        pool.run(|conn| {
+            diesel::sql_query("SELECT pg_sleep(0.3)")
+                .execute(conn)
+                .map_err(|_| connection_error())
+        })
+        .await
+        .unwrap();
+
+        assert!(ticker.await.unwrap() >= 10);
+    }
+
+    #[actix_rt::test]
+    async fn test_unavailable_database_is_a_temporary_error() {
+        // aucun serveur n'écoute sur ce port, la connexion n'est jamais obtenue
+        let manager = ConnectionManager::<PgConnection>::new("postgres://localhost:1/none");
+        let pool = DbPool::new(
+            r2d2::Pool::builder()
+                .max_size(1)
+                .min_idle(Some(0))
+                .connection_timeout(Duration::from_millis(100))
+                .build_unchecked(manager),
+        );
+
+        let error = pool.run(|_| Ok(())).await.err().unwrap();
+        assert_eq!(
+            error.error_type,
+            api_errors::ServiceErrorType::ServiceUnavailable
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_queries_wait_for_a_connection() {
+        // une seule connexion : la deuxième requête attend la fin de la première
+        let pool = establish_testing_connection(&Config::init());
+        let sleep = |pool: Pool| async move {
+            pool.run(|conn| {
+                diesel::sql_query("SELECT pg_sleep(0.1)")
+                    .execute(conn)
+                    .map_err(|_| connection_error())
+            })
+            .await
+        };
+
+        let start = Instant::now();
+        let first = actix_rt::spawn(sleep(pool.clone()));
+        sleep(pool).await.unwrap();
+        first.await.unwrap().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -1,3 +1,6 @@
+// the code generated by `#[derive(Repository)]` refers to this crate as `api_db`
+extern crate self as api_db;
+
 pub mod connection;
 
 pub mod models;
@@ -1 +1,5 @@
+pub mod api_key;
+pub mod oauth_authorization_code;
+pub mod oauth_client;
+pub mod oauth_consent;
 pub mod user;
@@ -0,0 +1,79 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use api_proc_macros::Repository;
+use api_types::api_key::{NewApiKey, SafeApiKey};
+
+use crate::schema::api_keys;
+
+#[derive(
+    Queryable,
+    Selectable,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Identifiable,
+    Associations,
+    Clone,
+    Repository,
+)]
+#[diesel(table_name = api_keys)]
+#[diesel(belongs_to(crate::models::user::User))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = ApiKeysRepository, new = NewApiKey, insertable = InsertableApiKey)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ApiKey {
+    /// Check if the key has an expiration date in the past.
+    pub fn is_expired(&self, now: chrono::NaiveDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl From<ApiKey> for SafeApiKey {
+    fn from(api_key: ApiKey) -> Self {
+        SafeApiKey {
+            id: api_key.id,
+            name: api_key.name,
+            prefix: api_key.prefix,
+            scopes: api_key.scopes,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+            created_at: api_key.created_at,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct InsertableApiKey<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub prefix: &'a str,
+    pub key_hash: &'a str,
+    pub scopes: &'a [String],
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+impl<'a> From<&'a NewApiKey> for InsertableApiKey<'a> {
+    fn from(api_key: &'a NewApiKey) -> Self {
+        InsertableApiKey {
+            user_id: api_key.user_id,
+            name: &api_key.name,
+            prefix: &api_key.prefix,
+            key_hash: &api_key.key_hash,
+            scopes: &api_key.scopes,
+            expires_at: api_key.expires_at,
+        }
+    }
+}
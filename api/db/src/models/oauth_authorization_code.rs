@@ -0,0 +1,54 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use api_proc_macros::Repository;
+use api_types::oidc::NewOAuthAuthorizationCode;
+
+use crate::schema::oauth_authorization_codes;
+
+#[derive(
+    Queryable, Selectable, Serialize, Deserialize, AsChangeset, Identifiable, Clone, Repository,
+)]
+#[diesel(table_name = oauth_authorization_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = OAuthAuthorizationCodesRepository, new = NewOAuthAuthorizationCode, insertable = InsertableOAuthAuthorizationCode)]
+pub struct OAuthAuthorizationCode {
+    pub id: i32,
+    pub code_hash: String,
+    pub client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_authorization_codes)]
+pub struct InsertableOAuthAuthorizationCode<'a> {
+    pub code_hash: &'a str,
+    pub client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: &'a str,
+    pub scopes: &'a [String],
+    pub code_challenge: &'a str,
+    pub nonce: Option<&'a str>,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+impl<'a> From<&'a NewOAuthAuthorizationCode> for InsertableOAuthAuthorizationCode<'a> {
+    fn from(code: &'a NewOAuthAuthorizationCode) -> Self {
+        InsertableOAuthAuthorizationCode {
+            code_hash: &code.code_hash,
+            client_id: code.client_id,
+            user_id: code.user_id,
+            redirect_uri: &code.redirect_uri,
+            scopes: &code.scopes,
+            code_challenge: &code.code_challenge,
+            nonce: code.nonce.as_deref(),
+            expires_at: code.expires_at,
+        }
+    }
+}
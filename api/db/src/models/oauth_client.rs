@@ -0,0 +1,48 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use api_proc_macros::Repository;
+use api_types::oauth_client::NewOAuthClient;
+
+use crate::schema::oauth_clients;
+
+#[derive(
+    Queryable, Selectable, Serialize, Deserialize, AsChangeset, Identifiable, Clone, Repository,
+)]
+#[diesel(table_name = oauth_clients)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = OAuthClientsRepository, new = NewOAuthClient, insertable = InsertableOAuthClient)]
+pub struct OAuthClient {
+    pub id: i32,
+    pub client_id: String,
+    pub secret_hash: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub redirect_uris: Vec<String>,
+    pub is_public: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_clients)]
+pub struct InsertableOAuthClient<'a> {
+    pub client_id: &'a str,
+    pub secret_hash: &'a str,
+    pub name: &'a str,
+    pub scopes: &'a [String],
+    pub redirect_uris: &'a [String],
+    pub is_public: bool,
+}
+
+impl<'a> From<&'a NewOAuthClient> for InsertableOAuthClient<'a> {
+    fn from(client: &'a NewOAuthClient) -> Self {
+        InsertableOAuthClient {
+            client_id: &client.client_id,
+            secret_hash: &client.secret_hash,
+            name: &client.name,
+            scopes: &client.scopes,
+            redirect_uris: &client.redirect_uris,
+            is_public: client.is_public,
+        }
+    }
+}
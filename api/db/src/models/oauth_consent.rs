@@ -0,0 +1,46 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use api_proc_macros::Repository;
+use api_types::oidc::NewOAuthConsent;
+
+use crate::schema::oauth_consents;
+
+#[derive(
+    Queryable, Selectable, Serialize, Deserialize, AsChangeset, Identifiable, Clone, Repository,
+)]
+#[diesel(table_name = oauth_consents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = OAuthConsentsRepository, new = NewOAuthConsent, insertable = InsertableOAuthConsent)]
+pub struct OAuthConsent {
+    pub id: i32,
+    pub user_id: i32,
+    pub client_id: i32,
+    pub scopes: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl OAuthConsent {
+    /// Check if the user already consented to every scope of the list.
+    pub fn covers(&self, scopes: &[String]) -> bool {
+        scopes.iter().all(|scope| self.scopes.contains(scope))
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = oauth_consents)]
+pub struct InsertableOAuthConsent<'a> {
+    pub user_id: i32,
+    pub client_id: i32,
+    pub scopes: &'a [String],
+}
+
+impl<'a> From<&'a NewOAuthConsent> for InsertableOAuthConsent<'a> {
+    fn from(consent: &'a NewOAuthConsent) -> Self {
+        InsertableOAuthConsent {
+            user_id: consent.user_id,
+            client_id: consent.client_id,
+            scopes: &consent.scopes,
+        }
+    }
+}
@@ -1,16 +1,25 @@
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use api_proc_macros::Updatable;
-use api_types::user::{NewUserWithId, SafeUser, UpdatableUser};
+use api_proc_macros::{Repository, Updatable};
+use api_types::user::{NewUser, NewUserWithId, SafeUser, UpdatableUser};
 
 use crate::schema::users;
 
 #[derive(
-    Queryable, Selectable, Serialize, Deserialize, AsChangeset, Updatable, Identifiable, Clone,
+    Queryable,
+    Selectable,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Updatable,
+    Identifiable,
+    Clone,
+    Repository,
 )]
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = UsersRepository, new = NewUser, insertable = InsertableUser)]
 pub struct User {
     pub id: i32,
     #[updatable]
@@ -62,3 +71,16 @@ pub struct InsertableUser<'a> {
     pub password: Option<&'a str>,
     pub google_id: Option<&'a str>,
 }
+
+impl<'a> From<&'a NewUser> for InsertableUser<'a> {
+    fn from(user: &'a NewUser) -> Self {
+        InsertableUser {
+            pseudo: &user.pseudo,
+            first_name: user.first_name.as_deref(),
+            last_name: user.last_name.as_deref(),
+            email: &user.email,
+            password: user.password.as_deref(),
+            google_id: user.google_id.as_deref(),
+        }
+    }
+}
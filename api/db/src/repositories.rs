@@ -1 +1,5 @@
+pub mod api_keys_repository;
+pub mod oauth_authorization_codes_repository;
+pub mod oauth_clients_repository;
+pub mod oauth_consents_repository;
 pub mod users_repository;
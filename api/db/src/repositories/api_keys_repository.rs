@@ -0,0 +1,64 @@
+use diesel::prelude::*;
+
+use crate::models::api_key::ApiKey;
+use crate::schema::api_keys;
+use api_errors::ServiceError;
+
+use crate::repository::{ApiKeyRepository, RepositoryResult};
+
+pub use crate::models::api_key::ApiKeysRepository;
+
+#[async_trait::async_trait]
+impl ApiKeyRepository for ApiKeysRepository {
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepositoryResult<ApiKey> {
+        let key_hash = key_hash.to_string();
+        self.conn
+            .run(move |conn| {
+                api_keys::table
+                    .filter(api_keys::key_hash.eq(key_hash))
+                    .select(ApiKey::as_select())
+                    .first(conn)
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+
+    async fn get_api_keys_by_user(&self, user_id: i32) -> RepositoryResult<Vec<ApiKey>> {
+        self.conn
+            .run(move |conn| {
+                api_keys::table
+                    .filter(api_keys::user_id.eq(user_id))
+                    .order(api_keys::created_at.desc())
+                    .select(ApiKey::as_select())
+                    .load(conn)
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+
+    async fn delete_api_key_of_user(&self, id: i32, user_id: i32) -> RepositoryResult<usize> {
+        self.conn
+            .run(move |conn| {
+                diesel::delete(
+                    api_keys::table
+                        .filter(api_keys::id.eq(id))
+                        .filter(api_keys::user_id.eq(user_id)),
+                )
+                .execute(conn)
+                .map_err(ServiceError::from)
+            })
+            .await
+    }
+
+    async fn touch_api_key(&self, id: i32, used_at: chrono::NaiveDateTime) -> RepositoryResult<()> {
+        self.conn
+            .run(move |conn| {
+                diesel::update(api_keys::table.filter(api_keys::id.eq(id)))
+                    .set(api_keys::last_used_at.eq(used_at))
+                    .execute(conn)
+                    .map(|_| ())
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+}
@@ -0,0 +1,44 @@
+use diesel::prelude::*;
+
+use crate::models::oauth_authorization_code::OAuthAuthorizationCode;
+use crate::schema::oauth_authorization_codes;
+use api_errors::ServiceError;
+
+use crate::repository::{OAuthAuthorizationCodeRepository, RepositoryResult};
+
+pub use crate::models::oauth_authorization_code::OAuthAuthorizationCodesRepository;
+
+#[async_trait::async_trait]
+impl OAuthAuthorizationCodeRepository for OAuthAuthorizationCodesRepository {
+    async fn consume_code(
+        &self,
+        code_hash: &str,
+    ) -> RepositoryResult<Option<OAuthAuthorizationCode>> {
+        let code_hash = code_hash.to_string();
+        self.conn
+            .run(move |conn| {
+                diesel::delete(
+                    oauth_authorization_codes::table
+                        .filter(oauth_authorization_codes::code_hash.eq(code_hash)),
+                )
+                .returning(OAuthAuthorizationCode::as_returning())
+                .get_result(conn)
+                .optional()
+                .map_err(ServiceError::from)
+            })
+            .await
+    }
+
+    async fn delete_expired_codes(&self, now: chrono::NaiveDateTime) -> RepositoryResult<usize> {
+        self.conn
+            .run(move |conn| {
+                diesel::delete(
+                    oauth_authorization_codes::table
+                        .filter(oauth_authorization_codes::expires_at.le(now)),
+                )
+                .execute(conn)
+                .map_err(ServiceError::from)
+            })
+            .await
+    }
+}
@@ -0,0 +1,28 @@
+use diesel::prelude::*;
+
+use crate::models::oauth_client::OAuthClient;
+use crate::schema::oauth_clients;
+use api_errors::ServiceError;
+
+use crate::repository::{OAuthClientRepository, RepositoryResult};
+
+pub use crate::models::oauth_client::OAuthClientsRepository;
+
+#[async_trait::async_trait]
+impl OAuthClientRepository for OAuthClientsRepository {
+    async fn get_oauth_client_by_client_id(
+        &self,
+        client_id: &str,
+    ) -> RepositoryResult<OAuthClient> {
+        let client_id = client_id.to_string();
+        self.conn
+            .run(move |conn| {
+                oauth_clients::table
+                    .filter(oauth_clients::client_id.eq(client_id))
+                    .select(OAuthClient::as_select())
+                    .first(conn)
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+}
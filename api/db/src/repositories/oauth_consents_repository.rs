@@ -0,0 +1,53 @@
+use diesel::prelude::*;
+
+use crate::models::oauth_consent::{InsertableOAuthConsent, OAuthConsent};
+use crate::schema::oauth_consents;
+use api_errors::ServiceError;
+use api_types::oidc::NewOAuthConsent;
+
+use crate::repository::{OAuthConsentRepository, RepositoryResult};
+
+pub use crate::models::oauth_consent::OAuthConsentsRepository;
+
+#[async_trait::async_trait]
+impl OAuthConsentRepository for OAuthConsentsRepository {
+    async fn get_consent(
+        &self,
+        user_id: i32,
+        client_id: i32,
+    ) -> RepositoryResult<Option<OAuthConsent>> {
+        self.conn
+            .run(move |conn| {
+                oauth_consents::table
+                    .filter(oauth_consents::user_id.eq(user_id))
+                    .filter(oauth_consents::client_id.eq(client_id))
+                    .select(OAuthConsent::as_select())
+                    .first(conn)
+                    .optional()
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+
+    async fn upsert_consent(&self, item: &NewOAuthConsent) -> RepositoryResult<OAuthConsent> {
+        let item = item.clone();
+        self.conn
+            .run(move |conn| {
+                let insertable_item = InsertableOAuthConsent {
+                    user_id: item.user_id,
+                    client_id: item.client_id,
+                    scopes: &item.scopes,
+                };
+
+                diesel::insert_into(oauth_consents::table)
+                    .values(&insertable_item)
+                    .on_conflict((oauth_consents::user_id, oauth_consents::client_id))
+                    .do_update()
+                    .set(oauth_consents::scopes.eq(&item.scopes))
+                    .returning(OAuthConsent::as_returning())
+                    .get_result(conn)
+                    .map_err(ServiceError::from)
+            })
+            .await
+    }
+}
@@ -1,150 +1,63 @@
 use diesel::prelude::*;
 
-use crate::connection::Pool;
-use crate::models::user::{InsertableUser, User};
+use crate::models::user::User;
 use crate::schema::users;
 use api_errors::ServiceError;
-use api_types::user::NewUser;
 
-use crate::repository::{Repository, RepositoryResult, UserRepository};
+use crate::repository::{RepositoryResult, UserRepository};
 
-#[derive(Clone)]
-pub struct UsersRepository {
-    conn: Pool,
-}
-
-impl UsersRepository {
-    pub fn new(conn: Pool) -> Self {
-        Self { conn }
-    }
-}
-
-#[async_trait::async_trait]
-impl Repository<User, NewUser> for UsersRepository {
-    async fn get(&self, id: i32) -> RepositoryResult<User> {
-        users::table
-            .filter(users::id.eq(id))
-            .select(User::as_select())
-            .first(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error getting user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
-            })
-    }
-
-    async fn get_all(&self) -> RepositoryResult<Vec<User>> {
-        users::table
-            .select(User::as_select())
-            .load(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error getting all users".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
-            })
-    }
+define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
 
-    async fn create(&self, item: &NewUser) -> RepositoryResult<User> {
-        let insertable_user = InsertableUser {
-            pseudo: &item.pseudo,
-            first_name: item.first_name.as_deref(),
-            last_name: item.last_name.as_deref(),
-            email: &item.email,
-            password: item.password.as_deref(),
-            google_id: item.google_id.as_deref(),
-        };
-
-        diesel::insert_into(users::table)
-            .values(insertable_user)
-            .returning(User::as_returning())
-            .get_result(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|err| ServiceError {
-                message: Some(err.to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
-            })
-    }
-
-    async fn update(&self, id: i32, item: &User) -> RepositoryResult<User> {
-        diesel::update(users::table)
-            .filter(users::id.eq(id))
-            .set(item)
-            .returning(User::as_returning())
-            .get_result(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error updating user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
-            })
-    }
-
-    async fn delete(&self, id: i32) -> RepositoryResult<usize> {
-        diesel::delete(users::table.filter(users::id.eq(id)))
-            .execute(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error deleting user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
-            })
-    }
-}
+pub use crate::models::user::UsersRepository;
 
 #[async_trait::async_trait]
 impl UserRepository for UsersRepository {
     async fn get_user_by_email(&self, email: &str) -> RepositoryResult<User> {
-        users::table
-            .filter(users::email.eq(email))
-            .select(User::as_select())
-            .first(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error getting user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
+        // les emails enregistrés avant leur normalisation peuvent contenir des majuscules
+        let email = email.to_lowercase();
+        self.conn
+            .run(move |conn| {
+                users::table
+                    .filter(lower(users::email).eq(email))
+                    .select(User::as_select())
+                    .first(conn)
+                    .map_err(ServiceError::from)
             })
+            .await
     }
 
     async fn delete_user_by_email(&self, email: &str) -> RepositoryResult<usize> {
-        diesel::delete(users::table.filter(users::email.eq(email)))
-            .execute(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error deleting user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
+        let email = email.to_lowercase();
+        self.conn
+            .run(move |conn| {
+                diesel::delete(users::table.filter(lower(users::email).eq(email)))
+                    .execute(conn)
+                    .map_err(ServiceError::from)
             })
+            .await
     }
 
     async fn get_user_by_google_id(&self, google_id: &str) -> RepositoryResult<User> {
-        users::table
-            .filter(users::google_id.eq(google_id))
-            .select(User::as_select())
-            .first(&mut self.conn.get().map_err(|_| ServiceError {
-                message: Some("Error for getting connection to the database".to_string()),
-                error_type: api_errors::ServiceErrorType::DatabaseError,
-            })?)
-            .map_err(|_| ServiceError {
-                message: Some("Error getting user".to_string()),
-                error_type: api_errors::ServiceErrorType::InternalServerError,
+        let google_id = google_id.to_string();
+        self.conn
+            .run(move |conn| {
+                users::table
+                    .filter(users::google_id.eq(google_id))
+                    .select(User::as_select())
+                    .first(conn)
+                    .map_err(ServiceError::from)
             })
+            .await
     }
 }
 
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    #[allow(unused_imports)]
+    use crate::repository::Repository;
+    #[allow(unused_imports)]
+    use api_types::user::NewUser;
     use once_cell::sync::Lazy;
 
     #[allow(dead_code)]
@@ -174,8 +87,111 @@ mod tests {
         cloned_user.first_name = Some("Jane".to_string());
 
         let updated_user = user_repository.update(cloned_user.id, &cloned_user).await;
-        assert_eq!(updated_user.is_ok(), true);
+        assert!(updated_user.is_ok());
 
         assert_eq!(updated_user.unwrap().first_name, Some("Jane".to_string()));
     }
+
+    #[actix_rt::test]
+    async fn test_get_page_of_users() {
+        let user_repository =
+            UsersRepository::new(crate::connection::establish_testing_connection(&CONFIG));
+        for pseudo in ["page_1", "page_2", "page_3"] {
+            user_repository
+                .create(&NewUser {
+                    pseudo: pseudo.to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: format!("{}@test.com", pseudo),
+                    password: None,
+                    google_id: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut ids = user_repository
+            .get_all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|user| user.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        let page = user_repository
+            .get_page(1, 2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|user| user.id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(page, ids[1..3]);
+    }
+
+    #[actix_rt::test]
+    async fn test_errors_of_the_database() {
+        let user_repository =
+            UsersRepository::new(crate::connection::establish_testing_connection(&CONFIG));
+
+        let missing = user_repository.get(-1).await.err().unwrap();
+        assert_eq!(missing.error_type, api_errors::ServiceErrorType::NotFound);
+
+        let new_user = NewUser {
+            pseudo: "pseudo_en_double".to_string(),
+            first_name: None,
+            last_name: None,
+            email: "pseudoendouble@test.com".to_string(),
+            password: None,
+            google_id: None,
+        };
+        user_repository.create(&new_user).await.unwrap();
+        // la transaction de test est annulée par l'erreur, c'est la dernière requête
+        let duplicate = user_repository.create(&new_user).await.err().unwrap();
+        assert_eq!(
+            duplicate,
+            ServiceError {
+                message: Some("pseudo already used".to_string()),
+                error_type: api_errors::ServiceErrorType::Conflict,
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_emails_are_compared_without_case() {
+        let user_repository =
+            UsersRepository::new(crate::connection::establish_testing_connection(&CONFIG));
+
+        let new_user = |pseudo: &str, email: &str| NewUser {
+            pseudo: pseudo.to_string(),
+            first_name: None,
+            last_name: None,
+            email: email.to_string(),
+            password: None,
+            google_id: None,
+        };
+        user_repository
+            .create(&new_user("casse", "Casse@test.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            user_repository
+                .delete_user_by_email("CASSE@test.com")
+                .await
+                .unwrap(),
+            1
+        );
+
+        user_repository
+            .create(&new_user("casse", "Casse@test.com"))
+            .await
+            .unwrap();
+        // l'index unique sur lower(email) refuse le même email avec une autre casse
+        let duplicate = user_repository
+            .create(&new_user("autre_casse", "casse@test.com"))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(duplicate.error_type, api_errors::ServiceErrorType::Conflict);
+    }
 }
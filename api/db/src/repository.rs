@@ -1,13 +1,24 @@
-use crate::models::user::User;
-use api_types::user::NewUser;
+use crate::models::{
+    api_key::ApiKey, oauth_authorization_code::OAuthAuthorizationCode, oauth_client::OAuthClient,
+    oauth_consent::OAuthConsent, user::User,
+};
+use api_types::{
+    api_key::NewApiKey,
+    oauth_client::NewOAuthClient,
+    oidc::{NewOAuthAuthorizationCode, NewOAuthConsent},
+    user::NewUser,
+};
 
 pub type RepositoryResult<T> = Result<T, api_errors::ServiceError>;
 
+/// Implemented with `#[derive(Repository)]` of `api_proc_macros` on the models.
 #[async_trait::async_trait]
 pub trait Repository<T, N>: Clone + Send + Sync + 'static {
     // methods global to all repositories
     async fn get(&self, id: i32) -> RepositoryResult<T>;
     async fn get_all(&self) -> RepositoryResult<Vec<T>>;
+    /// At most `limit` items ordered by id, after skipping `offset` of them.
+    async fn get_page(&self, offset: i64, limit: i64) -> RepositoryResult<Vec<T>>;
     async fn create(&self, item: &N) -> RepositoryResult<T>;
     async fn update(&self, id: i32, item: &T) -> RepositoryResult<T>;
     async fn delete(&self, id: i32) -> RepositoryResult<usize>;
@@ -16,7 +27,53 @@ pub trait Repository<T, N>: Clone + Send + Sync + 'static {
 #[async_trait::async_trait]
 pub trait UserRepository: Clone + Send + Sync + 'static + Repository<User, NewUser> {
     // methods specific to the users repository
+    /// The email is compared without case.
     async fn get_user_by_email(&self, email: &str) -> RepositoryResult<User>;
+    /// The email is compared without case.
     async fn delete_user_by_email(&self, email: &str) -> RepositoryResult<usize>;
     async fn get_user_by_google_id(&self, google_id: &str) -> RepositoryResult<User>;
 }
+
+#[async_trait::async_trait]
+pub trait ApiKeyRepository: Clone + Send + Sync + 'static + Repository<ApiKey, NewApiKey> {
+    // methods specific to the api keys repository
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepositoryResult<ApiKey>;
+    async fn get_api_keys_by_user(&self, user_id: i32) -> RepositoryResult<Vec<ApiKey>>;
+    async fn delete_api_key_of_user(&self, id: i32, user_id: i32) -> RepositoryResult<usize>;
+    async fn touch_api_key(&self, id: i32, used_at: chrono::NaiveDateTime) -> RepositoryResult<()>;
+}
+
+#[async_trait::async_trait]
+pub trait OAuthClientRepository:
+    Clone + Send + Sync + 'static + Repository<OAuthClient, NewOAuthClient>
+{
+    // methods specific to the oauth clients repository
+    async fn get_oauth_client_by_client_id(&self, client_id: &str)
+        -> RepositoryResult<OAuthClient>;
+}
+
+#[async_trait::async_trait]
+pub trait OAuthConsentRepository:
+    Clone + Send + Sync + 'static + Repository<OAuthConsent, NewOAuthConsent>
+{
+    // methods specific to the oauth consents repository
+    async fn get_consent(
+        &self,
+        user_id: i32,
+        client_id: i32,
+    ) -> RepositoryResult<Option<OAuthConsent>>;
+    async fn upsert_consent(&self, item: &NewOAuthConsent) -> RepositoryResult<OAuthConsent>;
+}
+
+#[async_trait::async_trait]
+pub trait OAuthAuthorizationCodeRepository:
+    Clone + Send + Sync + 'static + Repository<OAuthAuthorizationCode, NewOAuthAuthorizationCode>
+{
+    // methods specific to the oauth authorization codes repository
+    /// Delete the code and return it, a code can only be consumed once.
+    async fn consume_code(
+        &self,
+        code_hash: &str,
+    ) -> RepositoryResult<Option<OAuthAuthorizationCode>>;
+    async fn delete_expired_codes(&self, now: chrono::NaiveDateTime) -> RepositoryResult<usize>;
+}
@@ -1,5 +1,57 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Text,
+        prefix -> Text,
+        key_hash -> Text,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_authorization_codes (id) {
+        id -> Int4,
+        code_hash -> Text,
+        client_id -> Int4,
+        user_id -> Int4,
+        redirect_uri -> Text,
+        scopes -> Array<Text>,
+        code_challenge -> Text,
+        nonce -> Nullable<Text>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_clients (id) {
+        id -> Int4,
+        client_id -> Text,
+        secret_hash -> Text,
+        name -> Text,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        redirect_uris -> Array<Text>,
+        is_public -> Bool,
+    }
+}
+
+diesel::table! {
+    oauth_consents (id) {
+        id -> Int4,
+        user_id -> Int4,
+        client_id -> Int4,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Int4,
@@ -12,3 +64,17 @@ diesel::table! {
         created_at -> Timestamp,
     }
 }
+
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(oauth_authorization_codes -> oauth_clients (client_id));
+diesel::joinable!(oauth_authorization_codes -> users (user_id));
+diesel::joinable!(oauth_consents -> oauth_clients (client_id));
+diesel::joinable!(oauth_consents -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    oauth_authorization_codes,
+    oauth_clients,
+    oauth_consents,
+    users,
+);
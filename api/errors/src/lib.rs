@@ -2,7 +2,10 @@ extern crate redis;
 
 use std::fmt;
 
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{
+    http::{header::RETRY_AFTER, StatusCode},
+    HttpResponse, ResponseError,
+};
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum ServiceErrorType {
@@ -11,10 +14,12 @@ pub enum ServiceErrorType {
     BadDeserialization,
     DatabaseError,
     UnAuthorized,
+    Forbidden,
     UnprocessableEntityError,
     Conflict,
     NotFound,
     RateLimitExceeded,
+    ServiceUnavailable,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -46,15 +51,24 @@ impl ResponseError for ServiceError {
             ServiceErrorType::BadDeserialization => StatusCode::BAD_REQUEST,
             ServiceErrorType::DatabaseError => StatusCode::INTERNAL_SERVER_ERROR,
             ServiceErrorType::UnAuthorized => StatusCode::UNAUTHORIZED,
+            ServiceErrorType::Forbidden => StatusCode::FORBIDDEN,
             ServiceErrorType::UnprocessableEntityError => StatusCode::UNPROCESSABLE_ENTITY,
             ServiceErrorType::Conflict => StatusCode::CONFLICT,
             ServiceErrorType::NotFound => StatusCode::NOT_FOUND,
             ServiceErrorType::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ServiceErrorType::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(self.status_code()).json(self.message())
+        let mut response = HttpResponse::build(self.status_code());
+
+        // une indisponibilité est temporaire, le client peut réessayer
+        if self.error_type == ServiceErrorType::ServiceUnavailable {
+            response.insert_header((RETRY_AFTER, 1));
+        }
+
+        response.json(self.message())
     }
 }
 
@@ -69,9 +83,85 @@ impl From<api_caches::errors::RedisRepositoryError> for ServiceError {
 
 impl From<api_caches::errors::RateLimitError> for ServiceError {
     fn from(error: api_caches::errors::RateLimitError) -> Self {
+        match error {
+            api_caches::errors::RateLimitError::RateLimitExceeded => ServiceError {
+                message: Some(error.to_string()),
+                error_type: ServiceErrorType::RateLimitExceeded,
+            },
+            // the limiter can not count the requests, the client is not at fault
+            _ => ServiceError {
+                message: Some("Service temporarily unavailable".to_string()),
+                error_type: ServiceErrorType::ServiceUnavailable,
+            },
+        }
+    }
+}
+
+/// Public message of a violated unique constraint, the names of the constraints are not returned
+/// to the client, only the field they protect.
+fn unique_violation_message(constraint: Option<&str>) -> &'static str {
+    match constraint {
+        Some("users_email_key" | "users_lower_email_key") => "email already used",
+        Some("users_pseudo_key") => "pseudo already used",
+        Some("oauth_clients_client_id_key") => "client_id already used",
+        Some("oauth_consents_user_id_client_id_key") => "consent already given",
+        _ => "Resource already exists",
+    }
+}
+
+/// The errors of the database keep their meaning for the client: a missing row is a 404,
+/// a violated unique or foreign key constraint a 409 naming the field it protects when it is
+/// public, a transaction to retry a 503. The constraint names and the details of the other
+/// errors are only logged.
+impl From<diesel::result::Error> for ServiceError {
+    fn from(error: diesel::result::Error) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        // le nom de la contrainte révèle le schéma, il n'est que dans le journal
+        let constraint = match &error {
+            Error::DatabaseError(_, info) => info.constraint_name(),
+            _ => None,
+        };
+        let (error_type, message) = match &error {
+            Error::NotFound => (ServiceErrorType::NotFound, "Resource not found"),
+            Error::DatabaseError(kind, _) => match kind {
+                DatabaseErrorKind::UniqueViolation => (
+                    ServiceErrorType::Conflict,
+                    unique_violation_message(constraint),
+                ),
+                DatabaseErrorKind::ForeignKeyViolation => (
+                    ServiceErrorType::Conflict,
+                    "Resource referenced by or referencing another resource",
+                ),
+                DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => {
+                    (ServiceErrorType::UnprocessableEntityError, "Invalid value")
+                }
+                // la transaction peut réussir si le client réessaie
+                DatabaseErrorKind::SerializationFailure
+                | DatabaseErrorKind::ReadOnlyTransaction
+                | DatabaseErrorKind::ClosedConnection => (
+                    ServiceErrorType::ServiceUnavailable,
+                    "Database temporarily unavailable",
+                ),
+                _ => (ServiceErrorType::DatabaseError, "Database error"),
+            },
+            _ => (ServiceErrorType::InternalServerError, "Database error"),
+        };
+        let message = message.to_string();
+
+        let constraint = constraint.unwrap_or("none");
+        match error_type {
+            ServiceErrorType::DatabaseError
+            | ServiceErrorType::InternalServerError
+            | ServiceErrorType::ServiceUnavailable => {
+                log::error!("Database error (constraint {}): {}", constraint, error)
+            }
+            _ => log::debug!("Database error (constraint {}): {}", constraint, error),
+        }
+
         ServiceError {
-            message: Some(error.to_string()),
-            error_type: ServiceErrorType::RateLimitExceeded,
+            message: Some(message),
+            error_type,
         }
     }
 }
@@ -84,3 +174,95 @@ impl From<reqwest::Error> for ServiceError {
         }
     }
 }
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind, Error};
+
+    /// The information of a database error raised by a constraint.
+    #[allow(dead_code)]
+    struct Violation(&'static str);
+
+    impl DatabaseErrorInformation for Violation {
+        fn message(&self) -> &str {
+            "violation"
+        }
+        fn details(&self) -> Option<&str> {
+            None
+        }
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+        fn table_name(&self) -> Option<&str> {
+            None
+        }
+        fn column_name(&self) -> Option<&str> {
+            None
+        }
+        fn constraint_name(&self) -> Option<&str> {
+            Some(self.0)
+        }
+        fn statement_position(&self) -> Option<i32> {
+            None
+        }
+    }
+
+    #[allow(dead_code)]
+    fn database_error(kind: DatabaseErrorKind, constraint: &'static str) -> ServiceError {
+        ServiceError::from(Error::DatabaseError(kind, Box::new(Violation(constraint))))
+    }
+
+    #[test]
+    fn test_not_found() {
+        let error = ServiceError::from(Error::NotFound);
+        assert_eq!(error.error_type, ServiceErrorType::NotFound);
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_constraint_violations() {
+        let error = database_error(DatabaseErrorKind::UniqueViolation, "users_pseudo_key");
+        assert_eq!(
+            error,
+            ServiceError {
+                message: Some("pseudo already used".to_string()),
+                error_type: ServiceErrorType::Conflict,
+            }
+        );
+
+        // le client distingue un email pris d'un pseudo pris, sans le nom de la contrainte
+        let error = database_error(DatabaseErrorKind::UniqueViolation, "users_lower_email_key");
+        assert_eq!(error.message(), "email already used");
+
+        let error = database_error(DatabaseErrorKind::UniqueViolation, "api_keys_key_hash_key");
+        assert_eq!(error.error_type, ServiceErrorType::Conflict);
+        assert_eq!(error.message(), "Resource already exists");
+
+        let error = database_error(
+            DatabaseErrorKind::ForeignKeyViolation,
+            "api_keys_user_id_fkey",
+        );
+        assert_eq!(error.error_type, ServiceErrorType::Conflict);
+        assert!(!error.message().contains("api_keys_user_id_fkey"));
+
+        let error = database_error(DatabaseErrorKind::NotNullViolation, "users_email");
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.message(), "Invalid value");
+    }
+
+    #[test]
+    fn test_retryable_and_unknown_errors() {
+        let error = database_error(DatabaseErrorKind::SerializationFailure, "");
+        assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // les détails des autres erreurs ne sont pas renvoyés
+        let error = database_error(DatabaseErrorKind::Unknown, "secret");
+        assert_eq!(error.error_type, ServiceErrorType::DatabaseError);
+        assert_eq!(error.message(), "Database error");
+
+        let error = ServiceError::from(Error::RollbackTransaction);
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,36 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, Error, FromRequest, HttpMessage, HttpRequest};
+
+use api_errors::{ServiceError, ServiceErrorType};
+use api_services::auth::types::Principal;
+
+/// Extractor giving access to the `Principal` inserted by the authentication middleware.
+/// The route must be wrapped by `HttpAuthentication::bearer(validator)`.
+pub struct Authenticated(pub Principal);
+
+impl std::ops::Deref for Authenticated {
+    type Target = Principal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for Authenticated {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let principal = req.extensions().get::<Principal>().cloned();
+
+        ready(match principal {
+            Some(principal) => Ok(Authenticated(principal)),
+            None => Err(ServiceError {
+                message: Some("Authentification required".to_string()),
+                error_type: ServiceErrorType::UnAuthorized,
+            }
+            .into()),
+        })
+    }
+}
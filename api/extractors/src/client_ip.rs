@@ -0,0 +1,31 @@
+use std::{
+    fmt,
+    future::{ready, Ready},
+    net::IpAddr,
+};
+
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+
+use api_middlewares::client_ip::client_ip;
+
+/// Extractor giving the ip of the client, resolved through the trusted proxies of the config.
+/// `None` when the ip is unknown (no socket, like in the tests).
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ip) => write!(f, "{}", ip),
+            None => write!(f, "unknown"),
+        }
+    }
+}
+
+impl FromRequest for ClientIp {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(ClientIp(client_ip(req))))
+    }
+}
@@ -1 +1,2 @@
-
+pub mod authenticated;
+pub mod client_ip;
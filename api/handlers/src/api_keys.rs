@@ -0,0 +1,71 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+
+use validator::Validate;
+
+use api_errors::{ServiceError, ServiceErrorType};
+use api_extractors::authenticated::Authenticated;
+use api_services::{api_keys::ApiKeysService, auth::middleware::validator};
+use api_types::api_key::InputApiKey;
+
+pub fn service(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/v1/api-keys")
+            .wrap(HttpAuthentication::bearer(validator))
+            .service(
+                web::resource("")
+                    .route(web::get().to(index))
+                    .route(web::post().to(store)),
+            )
+            .service(web::resource("/{id}").route(web::delete().to(destroy))),
+    );
+}
+
+/// This function is used to list the api keys of the authenticated user
+pub async fn index(
+    api_keys_service: web::Data<ApiKeysService>,
+    principal: Authenticated,
+) -> Result<HttpResponse, Error> {
+    principal.require_interactive()?;
+    let user_id = principal.require_user()?;
+
+    Ok(api_keys_service
+        .list_api_keys(user_id)
+        .await
+        .map(|api_keys| HttpResponse::Ok().json(api_keys))?)
+}
+
+/// This function is used to create an api key, the secret is only shown in this response
+pub async fn store(
+    api_keys_service: web::Data<ApiKeysService>,
+    principal: Authenticated,
+    input: web::Json<InputApiKey>,
+) -> Result<HttpResponse, Error> {
+    principal.require_interactive()?;
+    let user_id = principal.require_user()?;
+
+    input.validate().map_err(|err| ServiceError {
+        message: Some(format!("Invalid api key: {}", err)),
+        error_type: ServiceErrorType::BadDeserialization,
+    })?;
+
+    Ok(api_keys_service
+        .create_api_key(user_id, input.into_inner())
+        .await
+        .map(|created_api_key| HttpResponse::Created().json(created_api_key))?)
+}
+
+/// This function is used to revoke an api key of the authenticated user
+pub async fn destroy(
+    api_keys_service: web::Data<ApiKeysService>,
+    principal: Authenticated,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    principal.require_interactive()?;
+    let user_id = principal.require_user()?;
+
+    Ok(api_keys_service
+        .revoke_api_key(user_id, id.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().json("Api key revoked"))?)
+}
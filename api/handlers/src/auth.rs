@@ -5,48 +5,101 @@ use actix_web_httpauth::middleware::HttpAuthentication;
 
 use validator::Validate;
 
-use api_caches::access_refresh_tokens::AccessRefreshTokensCache;
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCache, login_attempts::LoginAttemptsCache,
+    token_buckets::TokenBucketsCache,
+};
 use api_configs::config::Config;
 use api_db::repository::UserRepository;
 use api_errors::{ServiceError, ServiceErrorType};
+use api_extractors::{authenticated::Authenticated, client_ip::ClientIp};
+use api_middlewares::{concurrency_limiter::ConcurrencyLimiter, rate_limiter::RateLimiter};
 use api_services::auth::middleware::validator;
 use api_services::auth::services::AuthService;
-use api_types::user::{InputUser, RefreshableUser};
+use api_services::mailer::Mailer;
+use api_types::user::{InputUser, PasswordReset, PasswordResetRequest, RefreshableUser};
 
+use crate::bans::require_admin;
 use crate::helpers::tokens::send_secure_tokens;
 
-pub fn service<U: UserRepository, C: AccessRefreshTokensCache>(cfg: &mut web::ServiceConfig) {
+pub fn service<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+    T: TokenBucketsCache,
+>(
+    cfg: &mut web::ServiceConfig,
+) {
     cfg.service(
         web::scope("/v1/auth")
-            .service(web::resource("/login").route(web::post().to(login::<U, C>)))
-            .service(web::resource("/register").route(web::post().to(register::<U, C>)))
-            .service(web::resource("/refresh").route(web::post().to(refresh_tokens::<U, C>)))
+            // argon2 est coûteux, un appelant ne peut pas occuper tout le pool de hachage
+            .service(
+                web::resource("/login")
+                    .wrap(ConcurrencyLimiter::per_identity("login", 2))
+                    .wrap(RateLimiter::policy("login").with_cache::<T>())
+                    .route(web::post().to(login::<U, C, L, M>)),
+            )
+            .service(
+                web::resource("/register")
+                    .wrap(ConcurrencyLimiter::per_identity("register", 2))
+                    .route(web::post().to(register::<U, C, L, M>)),
+            )
+            // chaque demande envoie un email, elle est limitée comme la connexion
+            .service(
+                web::resource("/password-reset")
+                    .wrap(RateLimiter::policy("login").with_cache::<T>())
+                    .route(web::post().to(request_password_reset::<U, C, L, M>)),
+            )
+            .service(
+                web::resource("/password-reset/confirm")
+                    .wrap(ConcurrencyLimiter::per_identity("password_reset", 2))
+                    .route(web::post().to(reset_password::<U, C, L, M>)),
+            )
+            .service(web::resource("/refresh").route(web::post().to(refresh_tokens::<U, C, L, M>)))
             .service(
                 web::scope("/token")
                     .wrap(HttpAuthentication::bearer(validator))
                     .service(web::resource("/verify").route(web::get().to(verify_token))),
             ),
+    )
+    .service(
+        web::scope("/v1/admin/lockouts")
+            .wrap(HttpAuthentication::bearer(validator))
+            .service(web::resource("/{email}").route(web::delete().to(unlock::<U, C, L, M>))),
     );
 }
 
-pub async fn login<U: UserRepository, C: AccessRefreshTokensCache>(
+pub async fn login<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
     config: web::Data<Config>,
-    auth_service: web::Data<AuthService<U, C>>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
     user_json: web::Json<InputUser>,
+    client_ip: ClientIp,
 ) -> Result<HttpResponse, Error> {
     user_json.validate().map_err(|err| ServiceError {
         message: Some(format!("Invalid user: {}", err)),
         error_type: ServiceErrorType::BadDeserialization,
     })?;
 
-    let tokens = auth_service.login(user_json.into_inner(), &config).await?;
+    let tokens = auth_service
+        .login(user_json.into_inner(), &client_ip.to_string(), &config)
+        .await?;
 
     Ok(send_secure_tokens(tokens, &config))
 }
 
-pub async fn register<U: UserRepository, C: AccessRefreshTokensCache>(
-    config: web::Data<Config>,
-    auth_service: web::Data<AuthService<U, C>>,
+pub async fn register<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
+    auth_service: web::Data<AuthService<U, C, L, M>>,
     user_json: web::Json<InputUser>,
 ) -> Result<HttpResponse, Error> {
     user_json.validate().map_err(|err| ServiceError {
@@ -54,11 +107,76 @@ pub async fn register<U: UserRepository, C: AccessRefreshTokensCache>(
         error_type: ServiceErrorType::BadDeserialization,
     })?;
 
-    let tokens = auth_service
-        .register(user_json.into_inner(), &config)
+    auth_service.register(user_json.into_inner()).await?;
+
+    // same response whether the email is already used or not, the details are sent by email
+    Ok(HttpResponse::Accepted().json("Check your emails to finish your registration."))
+}
+
+pub async fn request_password_reset<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
+    config: web::Data<Config>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
+    reset_json: web::Json<PasswordResetRequest>,
+) -> Result<HttpResponse, Error> {
+    reset_json.validate().map_err(|err| ServiceError {
+        message: Some(format!("Invalid request: {}", err)),
+        error_type: ServiceErrorType::BadDeserialization,
+    })?;
+
+    auth_service
+        .request_password_reset(&reset_json.email, &config)
         .await?;
 
-    Ok(send_secure_tokens(tokens, &config))
+    // same response whether the account exists or not, the link is sent by email
+    Ok(HttpResponse::Accepted().json("Check your emails to reset your password."))
+}
+
+/// This function sets the new password of a reset link and lifts the lockout of the account
+pub async fn reset_password<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
+    config: web::Data<Config>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
+    reset_json: web::Json<PasswordReset>,
+) -> Result<HttpResponse, Error> {
+    reset_json.validate().map_err(|err| ServiceError {
+        message: Some(format!("Invalid request: {}", err)),
+        error_type: ServiceErrorType::BadDeserialization,
+    })?;
+
+    auth_service
+        .reset_password(reset_json.into_inner(), &config)
+        .await?;
+
+    Ok(HttpResponse::Ok().json("Password changed"))
+}
+
+/// This function is used to lift the lockout of an account, once its owner proved his identity
+pub async fn unlock<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
+    config: web::Data<Config>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
+    principal: Authenticated,
+    email: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    require_admin(&principal, &config)?;
+
+    auth_service.unlock_account(&email).await?;
+    log::warn!("{:?} unlocked the account {}", principal.subject, email);
+
+    Ok(HttpResponse::Ok().json("Account unlocked"))
 }
 
 pub async fn verify_token(
@@ -70,10 +188,15 @@ pub async fn verify_token(
     Ok(HttpResponse::Ok().finish())
 }
 
-pub async fn refresh_tokens<U: UserRepository, C: AccessRefreshTokensCache>(
+pub async fn refresh_tokens<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
     config: web::Data<Config>,
     user: web::Json<RefreshableUser>,
-    auth_service: web::Data<AuthService<U, C>>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
 ) -> Result<HttpResponse, Error> {
     // créer un nouveau access token et un refresh token puis modifier le refresh token dans redis
     user.validate().map_err(|err| ServiceError {
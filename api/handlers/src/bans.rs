@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use actix_web::{web, Error, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+
+use validator::Validate;
+
+use api_caches::bans::BansCache;
+use api_configs::config::Config;
+use api_errors::{ServiceError, ServiceErrorType};
+use api_extractors::authenticated::Authenticated;
+use api_services::auth::{
+    middleware::validator,
+    types::{Principal, Subject},
+};
+use api_types::{ban::InputBan, scopes};
+
+pub fn service<B: BansCache>(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/v1/admin/bans")
+            .wrap(HttpAuthentication::bearer(validator))
+            .service(
+                web::resource("")
+                    .route(web::get().to(index::<B>))
+                    .route(web::post().to(store::<B>)),
+            )
+            .service(web::resource("/{target}").route(web::delete().to(destroy::<B>))),
+    );
+}
+
+/// This function is used to list the current bans
+pub async fn index<B: BansCache>(
+    bans_cache: web::Data<Arc<B>>,
+    config: web::Data<Config>,
+    principal: Authenticated,
+) -> Result<HttpResponse, Error> {
+    require_admin(&principal, &config)?;
+
+    Ok(bans_cache
+        .list_bans()
+        .await
+        .map(|bans| HttpResponse::Ok().json(bans))
+        .map_err(ServiceError::from)?)
+}
+
+/// This function is used to ban an ip, a user or a client, replacing its current ban
+pub async fn store<B: BansCache>(
+    bans_cache: web::Data<Arc<B>>,
+    config: web::Data<Config>,
+    principal: Authenticated,
+    input: web::Json<InputBan>,
+) -> Result<HttpResponse, Error> {
+    require_admin(&principal, &config)?;
+
+    input.validate().map_err(|err| ServiceError {
+        message: Some(format!("Invalid ban: {}", err)),
+        error_type: ServiceErrorType::BadDeserialization,
+    })?;
+
+    let ban = bans_cache
+        .ban(&input.target, &input.reason, input.duration)
+        .await
+        .map_err(ServiceError::from)?;
+    log::warn!(
+        "{:?} banned {} until {}: {}",
+        principal.subject,
+        ban.target,
+        ban.expires_at,
+        ban.reason
+    );
+
+    Ok(HttpResponse::Created().json(ban))
+}
+
+/// This function is used to lift the ban of a target
+pub async fn destroy<B: BansCache>(
+    bans_cache: web::Data<Arc<B>>,
+    config: web::Data<Config>,
+    principal: Authenticated,
+    target: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    require_admin(&principal, &config)?;
+
+    bans_cache
+        .unban(&target)
+        .await
+        .map_err(ServiceError::from)?;
+    log::warn!("{:?} lifted the ban of {}", principal.subject, target);
+
+    Ok(HttpResponse::Ok().json("Ban lifted"))
+}
+
+/// The bans and the lockouts are managed by the users listed in the config, or by the clients
+/// granted the scope.
+pub(crate) fn require_admin(principal: &Principal, config: &Config) -> Result<(), ServiceError> {
+    match principal.subject {
+        Subject::User(id) if !config.access_control_info.admin_users.contains(&id) => {
+            Err(ServiceError {
+                message: Some("This action is reserved to administrators".to_string()),
+                error_type: ServiceErrorType::Forbidden,
+            })
+        }
+        _ => principal.require_scope(scopes::BANS_WRITE),
+    }
+}
@@ -1,9 +1,14 @@
 use actix_web::{web, Error, HttpResponse};
 
+use api_caches::{
+    instrumented::InstrumentedRedis,
+    redis::{RedisClient, RedisRepository},
+};
 use api_configs::config::Config;
 
 pub fn service(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/status").route(web::get().to(status)));
+    cfg.service(web::resource("/ready").route(web::get().to(ready)));
 }
 
 pub async fn status(config: web::Data<Config>) -> Result<HttpResponse, Error> {
@@ -11,3 +16,45 @@ pub async fn status(config: web::Data<Config>) -> Result<HttpResponse, Error> {
         .append_header(("VERSION", config.version.to_string()))
         .json("the server is alive."))
 }
+
+/// Readiness probe: 200 when Redis answers, 503 otherwise, with the state of the connection
+/// and the metrics of the commands when the app has an `InstrumentedRedis`.
+pub async fn ready(
+    redis_client: web::Data<RedisClient>,
+    instrumented_redis: Option<web::Data<InstrumentedRedis>>,
+) -> Result<HttpResponse, Error> {
+    let ping = redis_client.ping().await;
+    let health = redis_client.health();
+
+    let commands = instrumented_redis.map(|redis| {
+        redis
+            .metrics()
+            .into_iter()
+            .map(|(command, metrics)| {
+                let metrics = serde_json::json!({
+                    "calls": metrics.calls,
+                    "errors": metrics.errors,
+                    "mean_latency_ms": metrics.mean_latency().as_secs_f64() * 1000.0,
+                    "max_latency_ms": metrics.max_latency.as_secs_f64() * 1000.0,
+                });
+                (command.to_string(), metrics)
+            })
+            .collect::<serde_json::Map<_, _>>()
+    });
+
+    let body = serde_json::json!({
+        "redis": {
+            "ready": ping.is_ok(),
+            "connected": health.connected,
+            "connections": health.connections,
+            "failures": health.failures,
+            "last_error": health.last_error,
+            "commands": commands,
+        }
+    });
+
+    if ping.is_err() {
+        return Ok(HttpResponse::ServiceUnavailable().json(body));
+    }
+    Ok(HttpResponse::Ok().json(body))
+}
@@ -1,6 +1,9 @@
+pub mod api_keys;
 pub mod auth;
+pub mod bans;
 pub mod health;
 pub mod oauth;
+pub mod oidc;
 pub mod secure;
 pub mod users;
 
@@ -1,6 +1,9 @@
 use actix_web::{http::header, web, Error, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
 
-use api_caches::access_refresh_tokens::AccessRefreshTokensCache;
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCache, login_attempts::LoginAttemptsCache,
+};
 use api_db::repository::UserRepository;
 use serde::Deserialize;
 
@@ -11,18 +14,33 @@ use oauth2::{
 
 use api_configs::config::Config;
 use api_services::auth::services::AuthService;
+use api_services::mailer::Mailer;
+use api_services::oauth_clients::{
+    OAuthClientsService, OAuthTokenError, AUTHORIZATION_CODE_GRANT, CLIENT_CREDENTIALS_GRANT,
+};
+use api_services::oidc::services::OidcService;
+use api_types::oauth_client::TokenRequest;
 
 use crate::helpers::tokens::send_secure_tokens;
 
-pub fn service<U: UserRepository, C: AccessRefreshTokensCache>(cfg: &mut web::ServiceConfig) {
+pub fn service<U: UserRepository, C: AccessRefreshTokensCache, L: LoginAttemptsCache, M: Mailer>(
+    cfg: &mut web::ServiceConfig,
+) {
     cfg.service(
-        web::scope("/v1/oauth").service(
-            web::scope("/google")
-                .service(web::resource("/login").route(web::get().to(login)))
-                .service(
-                    web::resource("/oauth2callback").route(web::get().to(oauth2callback::<U, C>)),
-                ),
-        ),
+        web::scope("/v1/oauth")
+            .service(web::resource("/token").route(web::post().to(token)))
+            .service(
+                web::scope("/google")
+                    .service(web::resource("/login").route(web::get().to(login)))
+                    .service(
+                        web::resource("/oauth2callback").route(web::get().to(oauth2callback::<
+                            U,
+                            C,
+                            L,
+                            M,
+                        >)),
+                    ),
+            ),
     );
 }
 
@@ -48,10 +66,15 @@ pub struct AuthRequest {
     code: String,
 }
 
-pub async fn oauth2callback<U: UserRepository, C: AccessRefreshTokensCache>(
+pub async fn oauth2callback<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+>(
     params: web::Query<AuthRequest>,
     oauth_client: web::Data<BasicClient>,
-    auth_service: web::Data<AuthService<U, C>>,
+    auth_service: web::Data<AuthService<U, C, L, M>>,
     config: web::Data<Config>,
 ) -> Result<HttpResponse, Error> {
     let code = AuthorizationCode::new(params.code.clone());
@@ -73,3 +96,48 @@ pub async fn oauth2callback<U: UserRepository, C: AccessRefreshTokensCache>(
         Err(_) => Ok(HttpResponse::BadRequest().json("Échec de l'authentification par oauth2")),
     }
 }
+
+/// Token endpoint of the authorization server (RFC 6749 section 3.2).
+/// The `client_credentials` grant is used for service-to-service calls,
+/// the `authorization_code` grant by the relying parties of the OpenID Connect provider.
+/// The client can authenticate with HTTP Basic or with the form parameters.
+pub async fn token(
+    config: web::Data<Config>,
+    oauth_clients_service: web::Data<OAuthClientsService>,
+    oidc_service: Option<web::Data<OidcService>>,
+    basic_auth: Option<BasicAuth>,
+    form: web::Form<TokenRequest>,
+) -> Result<HttpResponse, Error> {
+    let form = form.into_inner();
+
+    let (client_id, client_secret) = match basic_auth {
+        Some(basic_auth) => (
+            basic_auth.user_id().to_string(),
+            basic_auth.password().map(str::to_string),
+        ),
+        None => (
+            form.client_id.clone().unwrap_or_default(),
+            form.client_secret.clone(),
+        ),
+    };
+
+    let access_token = match (form.grant_type.as_str(), oidc_service) {
+        (CLIENT_CREDENTIALS_GRANT, _) => {
+            let client_secret = client_secret.ok_or(OAuthTokenError::InvalidClient)?;
+
+            oauth_clients_service
+                .client_credentials(&client_id, &client_secret, form.scope.as_deref(), &config)
+                .await?
+        }
+        (AUTHORIZATION_CODE_GRANT, Some(oidc_service)) => {
+            oidc_service
+                .exchange_code(&form, &client_id, client_secret.as_deref(), &config)
+                .await?
+        }
+        _ => return Err(OAuthTokenError::UnsupportedGrantType.into()),
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(access_token))
+}
@@ -0,0 +1,94 @@
+use actix_web::{http::header, web, Error, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+
+use api_configs::config::Config;
+use api_extractors::authenticated::Authenticated;
+use api_services::{
+    auth::middleware::validator,
+    oidc::services::{AuthorizationOutcome, OidcService},
+};
+use api_types::{oidc::AuthorizationDecision, scopes};
+
+pub fn service(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/.well-known/openid-configuration").route(web::get().to(discovery)))
+        .service(
+            web::scope("/v1/oidc")
+                .service(web::resource("/jwks").route(web::get().to(jwks)))
+                .service(
+                    web::resource("/authorize")
+                        .route(web::get().to(authorize))
+                        .route(
+                            web::post()
+                                .to(decide)
+                                .wrap(HttpAuthentication::bearer(validator)),
+                        ),
+                )
+                .service(
+                    web::resource("/userinfo")
+                        .wrap(HttpAuthentication::bearer(validator))
+                        .route(web::get().to(userinfo)),
+                ),
+        );
+}
+
+/// Authorization endpoint, the user agent is sent to the front-end which logs the user in
+/// and asks for his consent before answering with `POST /authorize`
+pub async fn authorize(req: HttpRequest, config: web::Data<Config>) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((
+            header::LOCATION,
+            format!("{}?{}", config.oidc_info.consent_url, req.query_string()),
+        ))
+        .finish()
+}
+
+/// This function is used by the front-end to get a code for the logged in user
+pub async fn decide(
+    oidc_service: web::Data<OidcService>,
+    principal: Authenticated,
+    decision: web::Json<AuthorizationDecision>,
+) -> Result<HttpResponse, Error> {
+    principal.require_interactive()?;
+    let user_id = principal.require_user()?;
+
+    Ok(
+        match oidc_service
+            .authorize(user_id, decision.into_inner())
+            .await?
+        {
+            AuthorizationOutcome::Redirect(redirect) => HttpResponse::Ok().json(redirect),
+            AuthorizationOutcome::ConsentRequired(consent) => HttpResponse::Ok().json(consent),
+        },
+    )
+}
+
+/// UserInfo endpoint, the claims depend on the scopes of the access token
+pub async fn userinfo(
+    oidc_service: web::Data<OidcService>,
+    principal: Authenticated,
+) -> Result<HttpResponse, Error> {
+    principal.require_scope(scopes::OPENID)?;
+    let user_id = principal.require_user()?;
+
+    // an interactive login has every right
+    let granted_scopes = principal
+        .scopes
+        .clone()
+        .unwrap_or_else(|| scopes::OIDC.map(str::to_string).to_vec());
+
+    Ok(oidc_service
+        .userinfo(user_id, &granted_scopes)
+        .await
+        .map(|claims| HttpResponse::Ok().json(claims))?)
+}
+
+pub async fn jwks(oidc_service: web::Data<OidcService>) -> HttpResponse {
+    HttpResponse::Ok().json(oidc_service.jwks())
+}
+
+pub async fn discovery(
+    oidc_service: web::Data<OidcService>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(oidc_service.discovery(&config))
+}
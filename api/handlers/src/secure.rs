@@ -1,10 +1,8 @@
 use actix_web::{web, Error, HttpResponse};
-use actix_web_httpauth::extractors::bearer::BearerAuth;
 use actix_web_httpauth::middleware::HttpAuthentication;
 
-use api_configs::config::Config;
+use api_extractors::authenticated::Authenticated;
 use api_services::auth::middleware::validator;
-use api_services::auth::services::decode_token;
 
 pub fn service(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -16,9 +14,7 @@ pub fn service(cfg: &mut web::ServiceConfig) {
     );
 }
 
-pub async fn test(config: web::Data<Config>, token: BearerAuth) -> Result<HttpResponse, Error> {
-    // we can unwrap here because the token is already validated
-    let _ = decode_token(config, token.token()).unwrap().claims;
-
+pub async fn test(_principal: Authenticated) -> Result<HttpResponse, Error> {
+    // the principal is already validated by the authentication middleware
     Ok(HttpResponse::Ok().into())
 }
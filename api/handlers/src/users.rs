@@ -1,48 +1,52 @@
 use actix_web::{web, Error, HttpResponse};
-use actix_web_httpauth::{extractors::bearer::BearerAuth, middleware::HttpAuthentication};
+use actix_web_httpauth::middleware::HttpAuthentication;
 
-use api_configs::config::Config;
+use api_caches::user_profiles::UserProfilesCache;
 use api_db::{models::user::User, repository::UserRepository};
+use api_extractors::authenticated::Authenticated;
 use api_model_traits::update::Updatable;
-use api_services::{
-    auth::{middleware::validator, services::decode_token},
-    users::UsersService,
+use api_services::{auth::middleware::validator, users::UsersService};
+use api_types::{
+    scopes,
+    user::{NewUserWithId, UpdatableUser, UserPayload},
 };
-use api_types::user::{NewUserWithId, UpdatableUser, UserPayload};
 
-pub fn service<R: UserRepository>(cfg: &mut web::ServiceConfig) {
+pub fn service<R: UserRepository, P: UserProfilesCache>(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/v1/users")
             .wrap(HttpAuthentication::bearer(validator))
-            .service(web::resource("/profile").route(web::get().to(profile)))
+            .service(web::resource("/profile").route(web::get().to(profile::<P>)))
             .service(
                 web::resource("/{id}")
-                    .route(web::get().to(show))
-                    .route(web::patch().to(update::<R>))
-                    .route(web::put().to(replace::<R>))
-                    .route(web::delete().to(destroy)),
+                    .route(web::get().to(show::<P>))
+                    .route(web::patch().to(update::<R, P>))
+                    .route(web::put().to(replace::<R, P>))
+                    .route(web::delete().to(destroy::<P>)),
             ),
     );
 }
 
-pub async fn profile(
-    user_service: web::Data<UsersService>,
-    config: web::Data<Config>,
-    token: BearerAuth,
+pub async fn profile<P: UserProfilesCache>(
+    user_service: web::Data<UsersService<P>>,
+    principal: Authenticated,
 ) -> Result<HttpResponse, Error> {
-    let claims = decode_token(config, token.token()).unwrap().claims;
+    principal.require_scope(scopes::USERS_READ)?;
+    let user_id = principal.require_user()?;
 
     Ok(user_service
-        .get_safe_user(claims.sub)
+        .get_safe_user(user_id)
         .await
         .map(|user| HttpResponse::Ok().json(user))?)
 }
 
 /// This function is used to show a user from the database
-pub async fn show(
-    user_service: web::Data<UsersService>,
+pub async fn show<P: UserProfilesCache>(
+    user_service: web::Data<UsersService<P>>,
+    principal: Authenticated,
     id: web::Path<i32>,
 ) -> Result<HttpResponse, Error> {
+    principal.require_scope(scopes::USERS_READ)?;
+
     Ok(user_service
         .get_safe_user(id.into_inner())
         .await
@@ -50,11 +54,15 @@ pub async fn show(
 }
 
 /// This function is used to update partial content of a user from the database
-pub async fn update<R: UserRepository>(
+pub async fn update<R: UserRepository, P: UserProfilesCache>(
     repository: web::Data<R>,
+    user_service: web::Data<UsersService<P>>,
+    principal: Authenticated,
     id: web::Path<i32>,
     updatable_user: web::Json<UpdatableUser>,
 ) -> Result<HttpResponse, Error> {
+    principal.require_scope(scopes::USERS_WRITE)?;
+
     // il faut pouvoir transformer UpdatableUser en User car il dispose de asChangeSet
     let user = repository
         .get(id.into_inner())
@@ -62,31 +70,40 @@ pub async fn update<R: UserRepository>(
         .perform_update(updatable_user.into_inner())?;
 
     let updated_user = repository.update(user.id, &user).await?;
+    user_service.invalidate_profile(user.id).await;
 
     Ok(HttpResponse::Ok().json(updated_user))
 }
 
 /// This function is used to update a user from the database
-pub async fn replace<R: UserRepository>(
+pub async fn replace<R: UserRepository, P: UserProfilesCache>(
     repository: web::Data<R>,
+    user_service: web::Data<UsersService<P>>,
+    principal: Authenticated,
     id: web::Path<i32>,
     user_payload: web::Json<UserPayload>,
 ) -> Result<HttpResponse, Error> {
+    principal.require_scope(scopes::USERS_WRITE)?;
+
     let user = User::from(NewUserWithId {
         id: *id,
         user: user_payload.into_inner(),
     });
 
-    let updated_user = repository.update(id.into_inner(), &user).await?;
+    let updated_user = repository.update(user.id, &user).await?;
+    user_service.invalidate_profile(user.id).await;
 
     Ok(HttpResponse::Ok().json(updated_user))
 }
 
 /// This function is used to delete a user from the database
-pub async fn destroy(
-    user_service: web::Data<UsersService>,
+pub async fn destroy<P: UserProfilesCache>(
+    user_service: web::Data<UsersService<P>>,
+    principal: Authenticated,
     id: web::Path<i32>,
 ) -> Result<HttpResponse, Error> {
+    principal.require_scope(scopes::USERS_WRITE)?;
+
     Ok(user_service
         .destroy_user(id.into_inner())
         .await
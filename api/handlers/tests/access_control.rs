@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, web, App};
+
+use api_caches::{
+    bans::{BansCache, BansCacheRedis},
+    redis::RedisRepository,
+    token_buckets::TokenBucketsCacheRedis,
+};
+use api_handlers::{bans, health};
+use api_middlewares::{access_control::AccessControl, rate_limiter::RateLimiter};
+use api_services::auth::services::create_valid_token;
+use api_types::ban::Ban;
+
+mod common;
+
+fn bans_cache() -> Arc<BansCacheRedis> {
+    Arc::new(BansCacheRedis::new(Arc::clone(&common::REDIS_CLIENT)))
+}
+
+fn status_request(ip: &str) -> actix_web::test::TestRequest {
+    actix_web::test::TestRequest::get()
+        .uri("/status")
+        .peer_addr(format!("{}:1234", ip).parse().unwrap())
+}
+
+#[actix_web::test]
+async fn test_banned_ip_is_refused_until_the_ban_is_lifted() {
+    let bans_cache = bans_cache();
+    bans_cache.unban("ip:192.0.2.20").await.unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(Arc::clone(&bans_cache)))
+        .wrap(AccessControl::default())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    bans_cache
+        .ban("ip:192.0.2.20", "scraping", 60)
+        .await
+        .unwrap();
+
+    let err = actix_web::test::try_call_service(&app, status_request("192.0.2.20").to_request())
+        .await
+        .unwrap_err();
+    let resp = err.error_response();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    assert!(resp.headers().contains_key("retry-after"));
+
+    // les autres ips ne sont pas concernées
+    let resp = actix_web::test::call_service(&app, status_request("192.0.2.21").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    bans_cache.unban("ip:192.0.2.20").await.unwrap();
+    let resp = actix_web::test::call_service(&app, status_request("192.0.2.20").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_allowlisted_ip_bypasses_the_rate_limiter() {
+    let mut config = common::CONFIG.clone();
+    config.access_control_info.allowed_ips = vec!["192.0.2.30/32".parse().unwrap()];
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(bans_cache()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .wrap(AccessControl::default())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    for _ in 0..10 {
+        let resp =
+            actix_web::test::call_service(&app, status_request("192.0.2.30").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(!resp.headers().contains_key("ratelimit-remaining"));
+    }
+
+    assert!(!common::REDIS_CLIENT
+        .exists("ratelimit:login:ip:192.0.2.30")
+        .await
+        .unwrap());
+}
+
+#[actix_web::test]
+async fn test_denied_ip_is_refused() {
+    let mut config = common::CONFIG.clone();
+    config.access_control_info.denied_ips = vec!["198.51.100.0/24".parse().unwrap()];
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(bans_cache()))
+        .wrap(AccessControl::default())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let err = actix_web::test::try_call_service(&app, status_request("198.51.100.7").to_request())
+        .await
+        .unwrap_err();
+    assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_repeated_rate_limits_ban_the_ip() {
+    let ip = "192.0.2.40";
+    let bans_cache = bans_cache();
+    bans_cache.unban(&format!("ip:{}", ip)).await.unwrap();
+    common::REDIS_CLIENT
+        .delete(&format!("ratelimit:login:ip:{}", ip))
+        .await
+        .unwrap();
+
+    let mut config = common::CONFIG.clone();
+    config.access_control_info.auto_ban_threshold = 2;
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(Arc::clone(&bans_cache)))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .wrap(AccessControl::default())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let status = |resp: Result<actix_web::dev::ServiceResponse<_>, actix_web::Error>| match resp {
+        Ok(resp) => resp.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+
+    let mut statuses = vec![];
+    for _ in 0..8 {
+        let resp = actix_web::test::try_call_service(&app, status_request(ip).to_request()).await;
+        statuses.push(status(resp));
+    }
+
+    // 5 requests of the login policy, two 429 then banned
+    assert!(statuses[..5].iter().all(|status| *status == StatusCode::OK));
+    assert_eq!(statuses[5], StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(statuses[6], StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(statuses[7], StatusCode::FORBIDDEN);
+
+    let ban = bans_cache
+        .find_ban(&[format!("ip:{}", ip)])
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(ban.reason.contains("429"));
+
+    bans_cache.unban(&format!("ip:{}", ip)).await.unwrap();
+    common::REDIS_CLIENT
+        .delete(&format!("ratelimit:login:ip:{}", ip))
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_bans_are_managed_by_the_admins() {
+    let bans_cache = bans_cache();
+    bans_cache.unban("user:666").await.unwrap();
+
+    let mut config = common::CONFIG.clone();
+    config.access_control_info.admin_users = vec![1001];
+    let admin = create_valid_token(&config, 1001).unwrap();
+    let user = create_valid_token(&config, 1002).unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(Arc::clone(&bans_cache)))
+        .configure(bans::service::<BansCacheRedis>);
+    let app = actix_web::test::init_service(app).await;
+
+    let store = |token: &str, body: serde_json::Value| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/admin/bans")
+            .append_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+    let ban = serde_json::json!({"target": "user:666", "reason": "spam", "duration": 60});
+
+    let resp = actix_web::test::try_call_service(&app, store(&user, ban.clone())).await;
+    assert_eq!(
+        resp.map(|resp| resp.status())
+            .unwrap_or_else(|err| err.as_response_error().status_code()),
+        StatusCode::FORBIDDEN
+    );
+
+    let invalid = serde_json::json!({"target": "nobody", "reason": "spam", "duration": 60});
+    let resp = actix_web::test::try_call_service(&app, store(&admin, invalid)).await;
+    assert_eq!(
+        resp.map(|resp| resp.status())
+            .unwrap_or_else(|err| err.as_response_error().status_code()),
+        StatusCode::BAD_REQUEST
+    );
+
+    let resp = actix_web::test::call_service(&app, store(&admin, ban)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created: Ban = actix_web::test::read_body_json(resp).await;
+    assert_eq!(created.target, "user:666");
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/admin/bans")
+        .append_header(("Authorization", format!("Bearer {}", admin)))
+        .to_request();
+    let bans: Vec<Ban> = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert!(bans.contains(&created));
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri("/v1/admin/bans/user:666")
+        .append_header(("Authorization", format!("Bearer {}", admin)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        bans_cache
+            .find_ban(&["user:666".to_string()])
+            .await
+            .unwrap(),
+        None
+    );
+}
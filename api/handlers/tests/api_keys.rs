@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, web, App};
+
+use api_caches::user_profiles::UserProfilesCacheRedis;
+use api_db::repositories::users_repository::UsersRepository;
+use api_handlers::{api_keys, secure, users};
+use api_services::{api_keys::ApiKeysService, auth::services::create_valid_token};
+use api_types::api_key::{CreatedApiKey, SafeApiKey};
+
+mod common;
+
+#[actix_web::test]
+async fn test_create_api_key_and_use_it() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let api_keys_service = ApiKeysService::new(Arc::clone(&pool));
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(api_keys_service))
+        .configure(api_keys::service)
+        .configure(secure::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/api-keys")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(serde_json::json!({
+            "name": "script",
+            "scopes": ["users:read"],
+            "expires_in_days": 30
+        }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let created_api_key: CreatedApiKey = actix_web::test::read_body_json(resp).await;
+    assert!(created_api_key
+        .key
+        .starts_with(&created_api_key.api_key.prefix));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/secure/test-jwt")
+        .append_header(("Authorization", format!("Bearer {}", created_api_key.key)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/api-keys")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    let listed_api_keys: Vec<SafeApiKey> = actix_web::test::read_body_json(resp).await;
+    assert_eq!(listed_api_keys.len(), 1);
+    assert!(listed_api_keys[0].last_used_at.is_some());
+}
+
+#[actix_web::test]
+async fn test_api_key_cannot_manage_api_keys() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let api_keys_service = ApiKeysService::new(Arc::clone(&pool));
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let created_api_key = api_keys_service
+        .create_api_key(
+            user.id,
+            serde_json::from_value(serde_json::json!({
+                "name": "script",
+                "scopes": ["users:read", "users:write"]
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(api_keys_service))
+        .configure(api_keys::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/api-keys")
+        .append_header(("Authorization", format!("Bearer {}", created_api_key.key)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_api_key_without_scope_is_forbidden() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let api_keys_service = ApiKeysService::new(Arc::clone(&pool));
+    let user_service = common::user_service(Arc::clone(&pool));
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let created_api_key = api_keys_service
+        .create_api_key(
+            user.id,
+            serde_json::from_value(serde_json::json!({
+                "name": "script",
+                "scopes": []
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(api_keys_service))
+        .app_data(web::Data::new(user_service))
+        .configure(users::service::<UsersRepository, UserProfilesCacheRedis>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/users/profile")
+        .append_header(("Authorization", format!("Bearer {}", created_api_key.key)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_revoked_api_key_is_rejected() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let api_keys_service = ApiKeysService::new(Arc::clone(&pool));
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let created_api_key = api_keys_service
+        .create_api_key(
+            user.id,
+            serde_json::from_value(serde_json::json!({
+                "name": "script",
+                "scopes": []
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    api_keys_service
+        .revoke_api_key(user.id, created_api_key.api_key.id)
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(api_keys_service))
+        .configure(secure::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/secure/test-jwt")
+        .append_header(("Authorization", format!("Bearer {}", created_api_key.key)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
@@ -2,36 +2,50 @@ use std::sync::Arc;
 
 use actix_web::{http::StatusCode, web, App};
 
-use api_caches::{access_refresh_tokens::AccessRefreshTokensCacheRedis, redis::RedisRepository};
-use api_db::repositories::users_repository::UsersRepository;
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCacheRedis,
+    chaos::{ChaosRedis, Faults},
+    login_attempts::LoginAttemptsCacheRedis,
+    redis::RedisRepository,
+    token_buckets::TokenBucketsCacheRedis,
+};
+use api_db::{repositories::users_repository::UsersRepository, repository::Repository};
 use api_handlers::auth;
-use api_services::auth::{services::AuthService, types::Tokens};
+use api_services::auth::{
+    services::{create_valid_token, AuthService},
+    types::Tokens,
+};
+use api_types::user::NewUser;
 
 mod common;
 
+use common::RecordingMailer;
+
 #[actix_web::test]
 async fn test_login_with_bad_credentials() {
-    let redis_client = api_caches::redis::get_redis_client(&common::CONFIG);
-
     let users_repository = Arc::new(UsersRepository::new(
         api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
-    let access_refresh_tokens_cache = Arc::new(AccessRefreshTokensCacheRedis::new(
-        Arc::clone(&redis_client),
-        common::CONFIG.clone(),
-    ));
 
-    let auth_service = AuthService::new(users_repository, access_refresh_tokens_cache);
+    let auth_service = common::auth_service(users_repository, RecordingMailer::default());
 
     let app = App::new()
         .app_data(web::Data::new(common::CONFIG.clone()))
         .app_data(web::Data::new(auth_service.clone()))
-        .configure(auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>);
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
     let app = actix_web::test::init_service(app).await;
 
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/login")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
             "email": "bademail@test.com",
             "password": "badpassword"
         }))
@@ -43,31 +57,35 @@ async fn test_login_with_bad_credentials() {
 
 #[actix_web::test]
 async fn test_login_with_good_credentials() {
-    let redis_client = api_caches::redis::get_redis_client(&common::CONFIG);
-
     let users_repository = Arc::new(UsersRepository::new(
         api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
-    let access_refresh_tokens_cache = Arc::new(AccessRefreshTokensCacheRedis::new(
-        Arc::clone(&redis_client),
-        common::CONFIG.clone(),
-    ));
 
-    let auth_service = AuthService::new(Arc::clone(&users_repository), access_refresh_tokens_cache);
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
 
     let email = "tester@test.com";
     let password = "good_password";
     common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service.unlock_account(email).await.unwrap();
 
     let app = App::new()
         .app_data(web::Data::new(common::CONFIG.clone()))
         .app_data(web::Data::new(auth_service.clone()))
-        .configure(auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>);
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
     let app = actix_web::test::init_service(app).await;
 
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/login")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
             "email": email,
             "password": password
         }))
@@ -78,32 +96,160 @@ async fn test_login_with_good_credentials() {
 }
 
 #[actix_web::test]
-async fn test_register_with_email_already_exist() {
-    let redis_client = api_caches::redis::get_redis_client(&common::CONFIG);
+async fn test_login_responses_do_not_reveal_accounts() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
+    ));
+
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
 
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service
+        .unlock_account("tester@test.com")
+        .await
+        .unwrap();
+    auth_service
+        .unlock_account("unknown@test.com")
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let login = |email: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": email,
+                "password": "bad_password"
+            }))
+            .to_request()
+    };
+
+    let resp = actix_web::test::call_service(&app, login("tester@test.com")).await;
+    let known_status = resp.status();
+    let known_body = actix_web::test::read_body(resp).await;
+
+    let resp = actix_web::test::call_service(&app, login("unknown@test.com")).await;
+    let unknown_status = resp.status();
+    let unknown_body = actix_web::test::read_body(resp).await;
+
+    assert_eq!(known_status, StatusCode::UNAUTHORIZED);
+    assert_eq!(known_status, unknown_status);
+    assert_eq!(known_body, unknown_body);
+}
+
+#[actix_web::test]
+async fn test_register_responses_do_not_reveal_accounts() {
     let users_repository = Arc::new(UsersRepository::new(
         api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
-    let access_refresh_tokens_cache = Arc::new(AccessRefreshTokensCacheRedis::new(
-        Arc::clone(&redis_client),
-        common::CONFIG.clone(),
+
+    let mailer = RecordingMailer::default();
+    let auth_service = common::auth_service(Arc::clone(&users_repository), mailer.clone());
+
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let register = |email: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/register")
+            .set_json(serde_json::json!({
+                "email": email,
+                "password": "password"
+            }))
+            .to_request()
+    };
+
+    let resp = actix_web::test::call_service(&app, register("tester@test.com")).await;
+    let existing_status = resp.status();
+    let existing_body = actix_web::test::read_body(resp).await;
+
+    let resp = actix_web::test::call_service(&app, register("newcomer@test.com")).await;
+    let new_status = resp.status();
+    let new_body = actix_web::test::read_body(resp).await;
+
+    assert_eq!(existing_status, StatusCode::ACCEPTED);
+    assert_eq!(existing_status, new_status);
+    assert_eq!(existing_body, new_body);
+
+    // the difference is only told to the owners of the emails
+    let emails = mailer.emails.lock().unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].to, "tester@test.com");
+    assert_eq!(emails[0].subject, "You already have an account");
+    assert_eq!(emails[1].to, "newcomer@test.com");
+    assert_eq!(emails[1].subject, "Welcome");
+}
+
+#[actix_web::test]
+async fn test_register_with_email_already_exist() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
 
-    let auth_service = AuthService::new(Arc::clone(&users_repository), access_refresh_tokens_cache);
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
 
     let email = "tester@test.com";
-    let password = "good_password";
+    let password = "other_password";
     common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service.unlock_account(email).await.unwrap();
 
     let app = App::new()
         .app_data(web::Data::new(common::CONFIG.clone()))
         .app_data(web::Data::new(auth_service.clone()))
-        .configure(auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>);
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
     let app = actix_web::test::init_service(app).await;
 
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/register")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
+            "email": email,
+            "password": password
+        }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    // the existing account is left untouched
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/auth/login")
+        .set_json(serde_json::json!({
             "email": email,
             "password": password
         }))
@@ -115,29 +261,44 @@ async fn test_register_with_email_already_exist() {
 
 #[actix_web::test]
 async fn test_register_with_email_not_already_exist() {
-    let redis_client = api_caches::redis::get_redis_client(&common::CONFIG);
-
     let users_repository = Arc::new(UsersRepository::new(
         api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
-    let access_refresh_tokens_cache = Arc::new(AccessRefreshTokensCacheRedis::new(
-        Arc::clone(&redis_client),
-        common::CONFIG.clone(),
-    ));
 
-    let auth_service = AuthService::new(users_repository, access_refresh_tokens_cache);
+    let auth_service = common::auth_service(users_repository, RecordingMailer::default());
 
     let email = "mathieulebras_notexist@gmail.com";
+    auth_service.unlock_account(email).await.unwrap();
 
     let app = App::new()
         .app_data(web::Data::new(common::CONFIG.clone()))
         .app_data(web::Data::new(auth_service.clone()))
-        .configure(auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>);
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
     let app = actix_web::test::init_service(app).await;
 
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/register")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
+            "email": email,
+            "password": "password"
+        }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    // the new user can log in
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/auth/login")
+        .set_json(serde_json::json!({
             "email": email,
             "password": "password"
         }))
@@ -148,35 +309,99 @@ async fn test_register_with_email_not_already_exist() {
 }
 
 #[actix_web::test]
-async fn test_refresh_tokens() {
-    let redis_client = api_caches::redis::get_redis_client(&common::CONFIG);
-
+async fn test_register_adds_a_password_to_a_google_account() {
     let users_repository = Arc::new(UsersRepository::new(
         api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
-    let access_refresh_tokens_cache = Arc::new(AccessRefreshTokensCacheRedis::new(
-        Arc::clone(&redis_client),
-        common::CONFIG.clone(),
+
+    let mailer = RecordingMailer::default();
+    let auth_service = common::auth_service(Arc::clone(&users_repository), mailer.clone());
+
+    let email = "google_only@test.com";
+    users_repository
+        .create(&NewUser {
+            pseudo: "google_only".to_string(),
+            first_name: None,
+            last_name: None,
+            email: email.to_string(),
+            password: None,
+            google_id: Some("google_only_id".to_string()),
+        })
+        .await
+        .unwrap();
+    auth_service.unlock_account(email).await.unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let credentials = serde_json::json!({
+        "email": email,
+        "password": "new_password"
+    });
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/auth/register")
+        .set_json(&credentials)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    // same response as for a new account
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert_eq!(mailer.emails.lock().unwrap()[0].subject, "Password added");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/auth/login")
+        .set_json(&credentials)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_refresh_tokens() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
     ));
 
-    let auth_service = AuthService::new(Arc::clone(&users_repository), access_refresh_tokens_cache);
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
 
     let email = "tester@test.com";
     let password = "good_password";
 
     common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service.unlock_account(email).await.unwrap();
 
     let app = App::new()
         .app_data(web::Data::new(common::CONFIG.clone()))
         .app_data(web::Data::new(auth_service.clone()))
-        .configure(auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>);
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
 
     let app = actix_web::test::init_service(app).await;
 
     // login for get the tokens
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/login")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
             "email": email,
             "password": password
         }))
@@ -188,7 +413,7 @@ async fn test_refresh_tokens() {
 
     let req = actix_web::test::TestRequest::post()
         .uri("/v1/auth/refresh")
-        .set_json(&serde_json::json!({
+        .set_json(serde_json::json!({
             "email": email,
             "refresh_token": refresh_token
         }))
@@ -196,13 +421,381 @@ async fn test_refresh_tokens() {
 
     let resp = actix_web::test::call_service(&app, req).await;
 
-    redis_client.delete(&refresh_token).await.unwrap();
+    common::REDIS_CLIENT
+        .delete(&format!("refresh_tokens:{}", refresh_token))
+        .await
+        .unwrap();
 
     assert_eq!(resp.status(), StatusCode::OK);
 
     let new_tokens: Tokens = actix_web::test::read_body_json(resp).await;
-    redis_client
-        .delete(&new_tokens.refresh_token)
+    common::REDIS_CLIENT
+        .delete(&format!("refresh_tokens:{}", new_tokens.refresh_token))
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_login_is_locked_after_too_many_failed_attempts() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
+    ));
+
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
+
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service
+        .unlock_account("tester@test.com")
+        .await
+        .unwrap();
+
+    let mut config = common::CONFIG.clone();
+    config.access_control_info.admin_users = vec![1001];
+    let admin = create_valid_token(&config, 1001).unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    // the attempts are counted on the same account whatever the case of the email
+    let login = |password: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": if password == "good_password" { "TESTER@test.com" } else { "tester@test.com" },
+                "password": password
+            }))
+            .to_request()
+    };
+
+    for _ in 0..common::CONFIG.lockout_info.ip_account_threshold {
+        let resp = actix_web::test::call_service(&app, login("bad_password")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // even the good password is refused during the lockout
+    let resp = actix_web::test::call_service(&app, login("good_password")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // an administrator unlocks the account, the case of the email does not matter
+    let req = actix_web::test::TestRequest::delete()
+        .uri("/v1/admin/lockouts/Tester@test.com")
+        .append_header(("Authorization", format!("Bearer {}", admin)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = actix_web::test::call_service(&app, login("good_password")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_password_reset_unlocks_the_account() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
+    ));
+
+    let mailer = RecordingMailer::default();
+    let auth_service = common::auth_service(Arc::clone(&users_repository), mailer.clone());
+
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service
+        .unlock_account("tester@test.com")
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let login = |password: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": "tester@test.com",
+                "password": password
+            }))
+            .to_request()
+    };
+    let request_reset = |email: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/password-reset")
+            .set_json(serde_json::json!({ "email": email }))
+            .to_request()
+    };
+    let reset = |token: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/password-reset/confirm")
+            .set_json(serde_json::json!({ "token": token, "password": "new_password" }))
+            .to_request()
+    };
+
+    for _ in 0..common::CONFIG.lockout_info.ip_account_threshold {
+        actix_web::test::call_service(&app, login("bad_password")).await;
+    }
+    let resp = actix_web::test::call_service(&app, login("good_password")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // an unknown email gets the same response, without email
+    let resp = actix_web::test::call_service(&app, request_reset("unknown@test.com")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert!(mailer.emails.lock().unwrap().is_empty());
+
+    let resp = actix_web::test::call_service(&app, request_reset("Tester@test.com")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body = mailer.emails.lock().unwrap()[0].body.clone();
+    let token = body
+        .lines()
+        .find(|line| line.split('.').count() == 3)
+        .unwrap()
+        .to_string();
+
+    // the reset token is not an access token
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/auth/token/verify")
+        .append_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let status = match actix_web::test::try_call_service(&app, req).await {
+        Ok(resp) => resp.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let resp = actix_web::test::call_service(&app, reset(&token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // the reset lifted the lockout, and the token can not be used again
+    let resp = actix_web::test::call_service(&app, login("new_password")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = actix_web::test::call_service(&app, reset(&token)).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_login_during_a_database_outage_does_not_lock_the_account() {
+    // aucun serveur n'écoute sur ce port, chaque requête échoue
+    let unavailable = Arc::new(api_db::connection::DbPool::new(
+        diesel::r2d2::Pool::builder()
+            .max_size(1)
+            .min_idle(Some(0))
+            .connection_timeout(std::time::Duration::from_millis(100))
+            .build_unchecked(diesel::r2d2::ConnectionManager::new(
+                "postgres://localhost:1/none",
+            )),
+    ));
+    let users_repository = Arc::new(UsersRepository::new(unavailable));
+
+    let auth_service = common::auth_service(users_repository, RecordingMailer::default());
+    auth_service
+        .unlock_account("outage@test.com")
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    // the failures of the database are not failed attempts, the account is never locked
+    for _ in 0..=common::CONFIG.lockout_info.ip_account_threshold {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": "outage@test.com",
+                "password": "good_password"
+            }))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
+
+#[actix_web::test]
+async fn test_login_is_rate_limited_by_its_policy() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
+    ));
+
+    let auth_service =
+        common::auth_service(Arc::clone(&users_repository), RecordingMailer::default());
+
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service
+        .unlock_account("tester@test.com")
+        .await
+        .unwrap();
+
+    // les requêtes de test n'ont pas d'ip
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:")
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let login = || {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": "tester@test.com",
+                "password": "good_password"
+            }))
+            .to_request()
+    };
+
+    let capacity = common::CONFIG
+        .rate_limit_info
+        .get("login")
+        .unwrap()
+        .capacity;
+    for i in 1..=capacity {
+        let resp = actix_web::test::call_service(&app, login()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let header = |name| resp.headers().get(name).unwrap().to_str().unwrap();
+        assert_eq!(header("ratelimit-limit"), capacity.to_string());
+        assert_eq!(header("ratelimit-remaining"), (capacity - i).to_string());
+        assert!(header("ratelimit-reset").parse::<u64>().unwrap() > 0);
+    }
+
+    let resp = actix_web::test::try_call_service(&app, login()).await;
+    let resp = match resp {
+        Ok(resp) => resp.into_parts().1,
+        Err(err) => err.error_response(),
+    };
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let header = |name| resp.headers().get(name).unwrap().to_str().unwrap();
+    assert_eq!(header("ratelimit-remaining"), "0");
+    assert!(header("retry-after").parse::<u64>().unwrap() > 0);
+
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:")
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_login_with_a_slow_or_failing_redis() {
+    let users_repository = Arc::new(UsersRepository::new(
+        api_db::connection::establish_testing_connection(&common::CONFIG),
+    ));
+
+    let redis = ChaosRedis::new(Arc::clone(&common::REDIS_CLIENT));
+    let auth_service = AuthService::new(
+        Arc::clone(&users_repository),
+        Arc::new(AccessRefreshTokensCacheRedis::new(
+            redis.clone(),
+            common::CONFIG.clone(),
+        )),
+        Arc::new(LoginAttemptsCacheRedis::new(redis.clone())),
+        Arc::new(RecordingMailer::default()),
+    );
+
+    common::insert_test_user(Arc::clone(&users_repository)).await;
+    auth_service
+        .unlock_account("tester@test.com")
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(auth_service.clone()))
+        .configure(
+            auth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis<ChaosRedis>,
+                LoginAttemptsCacheRedis<ChaosRedis>,
+                RecordingMailer,
+                TokenBucketsCacheRedis,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let login = || {
+        actix_web::test::TestRequest::post()
+            .uri("/v1/auth/login")
+            .set_json(serde_json::json!({
+                "email": "tester@test.com",
+                "password": "good_password"
+            }))
+            .to_request()
+    };
+
+    // un Redis lent ralentit la connexion sans la refuser
+    redis.set_faults(Faults {
+        delay: std::time::Duration::from_millis(30),
+        ..Default::default()
+    });
+    let resp = actix_web::test::call_service(&app, login()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let tokens: Tokens = actix_web::test::read_body_json(resp).await;
+
+    // no token is issued when the lockouts can not be read
+    redis.set_faults(Faults {
+        error_rate: 1.0,
+        commands: vec!["hget_multiple"],
+        ..Default::default()
+    });
+    let resp = actix_web::test::call_service(&app, login()).await;
+    assert!(resp.status().is_server_error());
+
+    // nor when the refresh token can not be saved
+    redis.set_faults(Faults {
+        error_rate: 1.0,
+        commands: vec!["update_ttl"],
+        ..Default::default()
+    });
+    let resp = actix_web::test::call_service(&app, login()).await;
+    assert!(resp.status().is_server_error());
+
+    common::REDIS_CLIENT
+        .delete(&format!("refresh_tokens:{}", tokens.refresh_token))
         .await
         .unwrap();
 }
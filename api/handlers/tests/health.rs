@@ -1,4 +1,5 @@
 use actix_web::{http::StatusCode, web, App};
+use api_caches::{instrumented::InstrumentedRedis, redis::RedisRepository};
 use api_handlers::health;
 
 mod common;
@@ -30,3 +31,66 @@ async fn test_health_get_without_config() {
 
     assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
 }
+
+#[actix_web::test]
+async fn test_ready_when_redis_answers() {
+    let app = App::new()
+        .app_data(web::Data::new(common::REDIS_CLIENT.clone()))
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/ready")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["redis"]["ready"], true);
+    assert_eq!(body["redis"]["connected"], true);
+}
+
+#[actix_web::test]
+async fn test_ready_reports_the_metrics_of_the_commands() {
+    let instrumented_redis = InstrumentedRedis::new(common::REDIS_CLIENT.clone());
+    instrumented_redis.get("test_ready_metrics").await.unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::REDIS_CLIENT.clone()))
+        .app_data(web::Data::new(instrumented_redis))
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/ready")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["redis"]["commands"]["get"]["calls"], 1);
+    assert_eq!(body["redis"]["commands"]["get"]["errors"], 0);
+    assert!(body["redis"]["commands"]["get"]["max_latency_ms"].is_f64());
+}
+
+#[actix_web::test]
+async fn test_not_ready_without_redis() {
+    let mut config = common::CONFIG.clone();
+    // aucun serveur redis sur ce port
+    config.redis_info.port = "1".to_string();
+    let redis_client = api_caches::redis::get_redis_client(&config);
+
+    let app = App::new()
+        .app_data(web::Data::new(redis_client))
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/ready")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["redis"]["ready"], false);
+    assert_eq!(body["redis"]["connections"], 0);
+    assert!(body["redis"]["last_error"].is_string());
+}
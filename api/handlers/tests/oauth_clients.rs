@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, web, App};
+
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCacheRedis, login_attempts::LoginAttemptsCacheRedis,
+    user_profiles::UserProfilesCacheRedis,
+};
+use api_db::repositories::users_repository::UsersRepository;
+use api_handlers::{oauth, users};
+use api_services::mailer::LogMailer;
+use api_services::oauth_clients::OAuthClientsService;
+use api_types::oauth_client::{AccessTokenResponse, ClientRegistration};
+
+mod common;
+
+#[actix_web::test]
+async fn test_client_credentials_grant() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+    let user_service = common::user_service(Arc::clone(&pool));
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let client = oauth_clients_service
+        .register_client(ClientRegistration {
+            name: "worker".to_string(),
+            scopes: vec!["users:read".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .app_data(web::Data::new(user_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        )
+        .configure(users::service::<UsersRepository, UserProfilesCacheRedis>);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+        ])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let access_token: AccessTokenResponse = actix_web::test::read_body_json(resp).await;
+    assert_eq!(access_token.scope, "users:read");
+
+    // a client can read users with the granted scope
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/v1/users/{}", user.id))
+        .append_header((
+            "Authorization",
+            format!("Bearer {}", access_token.access_token),
+        ))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // but it is not a user and has no profile
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/users/profile")
+        .append_header((
+            "Authorization",
+            format!("Bearer {}", access_token.access_token),
+        ))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_client_credentials_with_bad_secret() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+
+    let client = oauth_clients_service
+        .register_client(ClientRegistration {
+            name: "worker".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client_id),
+            ("client_secret", "bad_secret"),
+        ])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_client");
+}
+
+#[actix_web::test]
+async fn test_client_credentials_with_scope_not_allowed() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+
+    let client = oauth_clients_service
+        .register_client(ClientRegistration {
+            name: "worker".to_string(),
+            scopes: vec!["users:read".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("scope", "users:read users:write"),
+        ])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_scope");
+}
+
+#[actix_web::test]
+async fn test_unsupported_grant_type() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        );
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([("grant_type", "password")])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["error"], "unsupported_grant_type");
+}
@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, web, App};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use oauth2::url::Url;
+use sha2::{Digest, Sha256};
+
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCacheRedis, login_attempts::LoginAttemptsCacheRedis,
+};
+use api_db::repositories::users_repository::UsersRepository;
+use api_handlers::{oauth, oidc};
+use api_services::mailer::LogMailer;
+use api_services::{
+    auth::services::create_valid_token,
+    oauth_clients::OAuthClientsService,
+    oidc::{claims::IdTokenClaims, keys::OidcSigningKey, services::OidcService},
+};
+use api_types::{
+    oauth_client::{AccessTokenResponse, ClientRegistration, RegisteredOAuthClient},
+    oidc::{AuthorizationRedirect, ConsentRequired},
+};
+
+mod common;
+
+const REDIRECT_URI: &str = "https://app.test/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU2p1r_wW1gFWFOEjXk";
+
+fn signing_key() -> OidcSigningKey {
+    OidcSigningKey::from_pem(include_str!("fixtures/oidc_private_key.pem")).unwrap()
+}
+
+fn authorization_request(client: &RegisteredOAuthClient) -> serde_json::Value {
+    serde_json::json!({
+        "response_type": "code",
+        "client_id": client.client_id,
+        "redirect_uri": REDIRECT_URI,
+        "scope": "openid email",
+        "state": "xyz",
+        "nonce": "n-0S6_WzA2Mj",
+        "code_challenge": URL_SAFE_NO_PAD.encode(Sha256::digest(CODE_VERIFIER.as_bytes())),
+        "code_challenge_method": "S256"
+    })
+}
+
+async fn register_relying_party(
+    oauth_clients_service: &OAuthClientsService,
+) -> RegisteredOAuthClient {
+    oauth_clients_service
+        .register_client(ClientRegistration {
+            name: "app".to_string(),
+            scopes: vec![
+                "openid".to_string(),
+                "profile".to_string(),
+                "email".to_string(),
+            ],
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            is_public: false,
+        })
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn test_authorization_code_flow() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+    let oidc_service = OidcService::new(Arc::clone(&pool), signing_key());
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+    let client = register_relying_party(&oauth_clients_service).await;
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .app_data(web::Data::new(oidc_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        )
+        .configure(oidc::service);
+    let app = actix_web::test::init_service(app).await;
+
+    // the user has never consented
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oidc/authorize")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(authorization_request(&client))
+        .to_request();
+    let consent: ConsentRequired = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert!(consent.consent_required);
+    assert_eq!(consent.client_name, "app");
+
+    let mut decision = authorization_request(&client);
+    decision["approved"] = serde_json::json!(true);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oidc/authorize")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(decision)
+        .to_request();
+    let redirect: AuthorizationRedirect = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    let redirect_to = Url::parse(&redirect.redirect_to).unwrap();
+    let params: std::collections::HashMap<_, _> = redirect_to.query_pairs().into_owned().collect();
+    assert!(redirect.redirect_to.starts_with(REDIRECT_URI));
+    assert_eq!(params["state"], "xyz");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "authorization_code"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("code", &params["code"]),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", CODE_VERIFIER),
+        ])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let tokens: AccessTokenResponse = actix_web::test::read_body_json(resp).await;
+    assert_eq!(tokens.scope, "openid email");
+
+    // the ID token is verified with the published key set
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/oidc/jwks")
+        .to_request();
+    let jwks: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    let jwk = &jwks["keys"][0];
+
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_audience(&[&client.client_id]);
+    validation.set_issuer(&[&common::CONFIG.oidc_info.issuer]);
+    let id_token = jsonwebtoken::decode::<IdTokenClaims>(
+        &tokens.id_token.unwrap(),
+        &DecodingKey::from_rsa_components(jwk["n"].as_str().unwrap(), jwk["e"].as_str().unwrap())
+            .unwrap(),
+        &validation,
+    )
+    .unwrap();
+
+    assert_eq!(id_token.header.kid.as_deref(), jwk["kid"].as_str());
+    assert_eq!(id_token.claims.user.sub, user.id.to_string());
+    assert_eq!(id_token.claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(
+        id_token.claims.user.email.as_deref(),
+        Some("tester@test.com")
+    );
+    // the profile scope was not requested
+    assert!(id_token.claims.user.preferred_username.is_none());
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/v1/oidc/userinfo")
+        .append_header(("Authorization", format!("Bearer {}", tokens.access_token)))
+        .to_request();
+    let userinfo: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(userinfo["sub"], user.id.to_string());
+    assert_eq!(userinfo["email"], "tester@test.com");
+
+    // a code can only be used once
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "authorization_code"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("code", &params["code"]),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", CODE_VERIFIER),
+        ])
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[actix_web::test]
+async fn test_authorization_code_with_bad_verifier() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+    let oidc_service = OidcService::new(Arc::clone(&pool), signing_key());
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+    let client = register_relying_party(&oauth_clients_service).await;
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oauth_clients_service))
+        .app_data(web::Data::new(oidc_service))
+        .configure(
+            oauth::service::<
+                UsersRepository,
+                AccessRefreshTokensCacheRedis,
+                LoginAttemptsCacheRedis,
+                LogMailer,
+            >,
+        )
+        .configure(oidc::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let mut decision = authorization_request(&client);
+    decision["approved"] = serde_json::json!(true);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oidc/authorize")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(decision)
+        .to_request();
+    let redirect: AuthorizationRedirect = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    let redirect_to = Url::parse(&redirect.redirect_to).unwrap();
+    let (_, code) = redirect_to
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .unwrap();
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oauth/token")
+        .set_form([
+            ("grant_type", "authorization_code"),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", "not-the-verifier"),
+        ])
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[actix_web::test]
+async fn test_authorize_with_unregistered_redirect_uri() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pool));
+    let oidc_service = OidcService::new(Arc::clone(&pool), signing_key());
+
+    let user = common::insert_test_user(Arc::clone(&users_repository)).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+    let client = register_relying_party(&oauth_clients_service).await;
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oidc_service))
+        .configure(oidc::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let mut request = authorization_request(&client);
+    request["redirect_uri"] = serde_json::json!("https://evil.test/callback");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/v1/oidc/authorize")
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(request)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    // the user agent must not be redirected to an unknown uri
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_discovery_document() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let oidc_service = OidcService::new(Arc::clone(&pool), signing_key());
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(oidc_service))
+        .configure(oidc::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/.well-known/openid-configuration")
+        .to_request();
+    let discovery: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(discovery["issuer"], common::CONFIG.oidc_info.issuer);
+    assert_eq!(discovery["code_challenge_methods_supported"][0], "S256");
+}
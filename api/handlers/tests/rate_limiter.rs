@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use actix_cors::Cors;
+use actix_web::{http::StatusCode, web, App, HttpResponse};
+
+use api_caches::{
+    chaos::{ChaosRedis, Faults},
+    clock::ManualClock,
+    redis::{get_redis_client, RedisRepository},
+    token_buckets::{TokenBucketsCacheMemory, TokenBucketsCacheRedis},
+};
+use api_configs::rate_limit::RateLimitDegradation;
+use api_handlers::{health, secure};
+use api_middlewares::{concurrency_limiter::ConcurrencyLimiter, rate_limiter::RateLimiter};
+use api_services::auth::{helpers::hash_secret, services::create_valid_token};
+
+mod common;
+
+#[actix_web::test]
+async fn test_users_behind_the_same_ip_have_their_own_buckets() {
+    for key in [
+        "ratelimit:login:user:1001",
+        "ratelimit:login:user:1002",
+        "ratelimit:login:ip:",
+    ] {
+        common::REDIS_CLIENT.delete(key).await.unwrap();
+    }
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .configure(secure::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let request = |token: &str| {
+        actix_web::test::TestRequest::get()
+            .uri("/v1/secure/test-jwt")
+            .append_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let status = |resp: Result<actix_web::dev::ServiceResponse<_>, actix_web::Error>| match resp {
+        Ok(resp) => resp.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+
+    // les deux utilisateurs ont la même ip (aucune dans les requêtes de test)
+    let first_user = create_valid_token(&common::CONFIG, 1001).unwrap();
+    let second_user = create_valid_token(&common::CONFIG, 1002).unwrap();
+
+    let capacity = common::CONFIG
+        .rate_limit_info
+        .get("login")
+        .unwrap()
+        .capacity;
+    for _ in 0..capacity {
+        let resp = actix_web::test::try_call_service(&app, request(&first_user)).await;
+        assert_eq!(status(resp), StatusCode::OK);
+    }
+
+    let resp = actix_web::test::try_call_service(&app, request(&first_user)).await;
+    assert_eq!(status(resp), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp = actix_web::test::try_call_service(&app, request(&second_user)).await;
+    assert_eq!(status(resp), StatusCode::OK);
+
+    // an invalid token uses the bucket of the ip, then is refused by the authentication
+    let resp = actix_web::test::try_call_service(&app, request("invalid")).await;
+    assert_eq!(status(resp), StatusCode::UNAUTHORIZED);
+
+    // la politique login compte les requêtes dans une fenêtre glissante
+    let ip_window = common::REDIS_CLIENT
+        .hget("ratelimit:login:ip:", "current")
+        .await
+        .unwrap();
+    assert_eq!(ip_window, Some("1".to_string()));
+
+    for key in [
+        "ratelimit:login:user:1001",
+        "ratelimit:login:user:1002",
+        "ratelimit:login:ip:",
+    ] {
+        common::REDIS_CLIENT.delete(key).await.unwrap();
+    }
+}
+
+#[actix_web::test]
+async fn test_api_keys_behind_the_same_ip_have_their_own_buckets() {
+    let ip = "192.0.2.34";
+    let (first_key, second_key) = ("efk_first_script", "efk_second_script");
+    let bucket = |key: &str| format!("ratelimit:login:api_key:{}", hash_secret(key));
+    for key in [
+        bucket(first_key),
+        bucket(second_key),
+        format!("ratelimit:login:ip:{}", ip),
+    ] {
+        common::REDIS_CLIENT.delete(&key).await.unwrap();
+    }
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .route("/", web::get().to(HttpResponse::Ok));
+    let app = actix_web::test::init_service(app).await;
+
+    // les deux scripts sont derrière la même ip, aucune clé n'est vérifiée en base par le limiteur
+    let request = |key: &str| {
+        actix_web::test::TestRequest::get()
+            .uri("/")
+            .peer_addr(format!("{}:1234", ip).parse().unwrap())
+            .append_header(("Authorization", format!("Bearer {}", key)))
+            .to_request()
+    };
+    let status = |resp: Result<actix_web::dev::ServiceResponse<_>, actix_web::Error>| match resp {
+        Ok(resp) => resp.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+
+    let capacity = common::CONFIG
+        .rate_limit_info
+        .get("login")
+        .unwrap()
+        .capacity;
+    for _ in 0..capacity {
+        let resp = actix_web::test::try_call_service(&app, request(first_key)).await;
+        assert_eq!(status(resp), StatusCode::OK);
+    }
+
+    let resp = actix_web::test::try_call_service(&app, request(first_key)).await;
+    assert_eq!(status(resp), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp = actix_web::test::try_call_service(&app, request(second_key)).await;
+    assert_eq!(status(resp), StatusCode::OK);
+
+    // the buckets are keyed by the hash of the key, never by the key itself
+    assert!(common::REDIS_CLIENT
+        .hget(&bucket(first_key), "current")
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        common::REDIS_CLIENT
+            .hget(&format!("ratelimit:login:ip:{}", ip), "current")
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[actix_web::test]
+async fn test_rate_limited_responses_carry_the_cors_headers() {
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:192.0.2.33")
+        .await
+        .unwrap();
+
+    // même ordre que le bootstrap : cors enveloppe le rate limiter
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .wrap(
+            Cors::default()
+                .allowed_origin("http://localhost:3000")
+                .expose_headers(["RateLimit-Limit", "RateLimit-Remaining", "Retry-After"]),
+        )
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let request = || {
+        actix_web::test::TestRequest::get()
+            .uri("/status")
+            .insert_header(("Origin", "http://localhost:3000"))
+            .peer_addr("192.0.2.33:1234".parse().unwrap())
+            .to_request()
+    };
+    let capacity = common::CONFIG
+        .rate_limit_info
+        .get("login")
+        .unwrap()
+        .capacity;
+    for _ in 0..capacity {
+        let resp = actix_web::test::call_service(&app, request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = actix_web::test::call_service(&app, request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("Retry-After"));
+    assert_eq!(
+        resp.headers().get("Access-Control-Allow-Origin").unwrap(),
+        "http://localhost:3000"
+    );
+    let exposed = resp
+        .headers()
+        .get("Access-Control-Expose-Headers")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_lowercase();
+    assert!(exposed.contains("retry-after"));
+    assert!(exposed.contains("ratelimit-remaining"));
+
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:192.0.2.33")
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_rate_limiter_runs_on_the_memory_cache() {
+    let clock = ManualClock::default();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheMemory::new(
+            Arc::new(clock.clone()),
+        ))))
+        .wrap(RateLimiter::policy("login").with_cache::<TokenBucketsCacheMemory>())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let status = |resp: Result<actix_web::dev::ServiceResponse<_>, actix_web::Error>| match resp {
+        Ok(resp) => resp.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+    let request = || {
+        actix_web::test::TestRequest::get()
+            .uri("/status")
+            .to_request()
+    };
+
+    let policy = common::CONFIG.rate_limit_info.get("login").unwrap();
+    for _ in 0..policy.capacity {
+        let resp = actix_web::test::try_call_service(&app, request()).await;
+        assert_eq!(status(resp), StatusCode::OK);
+    }
+
+    let resp = actix_web::test::try_call_service(&app, request()).await;
+    assert_eq!(status(resp), StatusCode::TOO_MANY_REQUESTS);
+
+    // après deux fenêtres, plus aucune requête ne compte
+    clock.advance(std::time::Duration::from_secs(
+        2 * policy.capacity * policy.refill_period / policy.refill_amount,
+    ));
+    let resp = actix_web::test::try_call_service(&app, request()).await;
+    assert_eq!(status(resp), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_forwarded_ip_is_only_trusted_from_trusted_proxies() {
+    let keys = [
+        "ratelimit:login:ip:8.8.8.8",
+        "ratelimit:login:ip:1.1.1.1",
+        "ratelimit:login:ip:2.2.2.2",
+    ];
+    for key in keys {
+        common::REDIS_CLIENT.delete(key).await.unwrap();
+    }
+
+    let mut config = common::CONFIG.clone();
+    config.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            Arc::clone(&common::REDIS_CLIENT),
+        ))))
+        .wrap(RateLimiter::policy("login"))
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let request = |peer: &str, forwarded_for: &str| {
+        actix_web::test::TestRequest::get()
+            .uri("/status")
+            .peer_addr(format!("{}:1234", peer).parse().unwrap())
+            .append_header(("X-Forwarded-For", forwarded_for.to_string()))
+            .to_request()
+    };
+
+    // un client qui se fait passer pour un autre
+    let resp = actix_web::test::call_service(&app, request("8.8.8.8", "1.1.1.1")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // a trusted proxy forwarding a client
+    let resp = actix_web::test::call_service(&app, request("10.0.0.1", "2.2.2.2")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let exists = |key: &'static str| common::REDIS_CLIENT.exists(key);
+    assert!(exists("ratelimit:login:ip:8.8.8.8").await.unwrap());
+    assert!(!exists("ratelimit:login:ip:1.1.1.1").await.unwrap());
+    assert!(exists("ratelimit:login:ip:2.2.2.2").await.unwrap());
+
+    for key in keys {
+        common::REDIS_CLIENT.delete(key).await.unwrap();
+    }
+}
+
+#[actix_web::test]
+async fn test_redis_outage_follows_the_degradation_policy() {
+    let status = |degradation: RateLimitDegradation| async move {
+        let mut config = common::CONFIG.clone();
+        config.rate_limit_info.degradation = degradation;
+        // aucun serveur redis sur ce port
+        config.redis_info.port = "1".to_string();
+        let unavailable_redis = get_redis_client(&config);
+
+        let app = App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+                unavailable_redis,
+            ))))
+            .wrap(RateLimiter::policy("login"))
+            .configure(health::service);
+        let app = actix_web::test::init_service(app).await;
+
+        let mut statuses = vec![];
+        for _ in 0..6 {
+            let request = actix_web::test::TestRequest::get()
+                .uri("/status")
+                .peer_addr("192.0.2.1:1234".parse().unwrap())
+                .to_request();
+            let status = match actix_web::test::try_call_service(&app, request).await {
+                Ok(resp) => resp.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            statuses.push(status);
+        }
+        statuses
+    };
+
+    // a redis outage is never reported as a rate limit
+    assert!(status(RateLimitDegradation::FailOpen)
+        .await
+        .iter()
+        .all(|status| *status == StatusCode::OK));
+    assert!(status(RateLimitDegradation::FailClosed)
+        .await
+        .iter()
+        .all(|status| *status == StatusCode::SERVICE_UNAVAILABLE));
+
+    // la politique login est appliquée en mémoire : 5 requêtes puis 429
+    let statuses = status(RateLimitDegradation::Local).await;
+    assert!(statuses[..5].iter().all(|status| *status == StatusCode::OK));
+    assert_eq!(statuses[5], StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn test_rate_limiter_with_a_failing_then_slow_redis() {
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:192.0.2.47")
+        .await
+        .unwrap();
+
+    let mut config = common::CONFIG.clone();
+    config.rate_limit_info.degradation = RateLimitDegradation::Local;
+    let redis = ChaosRedis::new(Arc::clone(&common::REDIS_CLIENT)).seed(47);
+
+    let app = App::new()
+        .app_data(web::Data::new(config))
+        .app_data(web::Data::new(Arc::new(TokenBucketsCacheRedis::new(
+            redis.clone(),
+        ))))
+        .wrap(RateLimiter::policy("login").with_cache::<TokenBucketsCacheRedis<ChaosRedis>>())
+        .configure(health::service);
+    let app = actix_web::test::init_service(app).await;
+
+    let request = || {
+        actix_web::test::TestRequest::get()
+            .uri("/status")
+            .peer_addr("192.0.2.47:1234".parse().unwrap())
+            .to_request()
+    };
+    let capacity = common::CONFIG
+        .rate_limit_info
+        .get("login")
+        .unwrap()
+        .capacity;
+
+    // les scripts échouent : le limiteur en mémoire prend le relais
+    redis.set_faults(Faults {
+        error_rate: 1.0,
+        commands: vec!["eval_ints"],
+        ..Default::default()
+    });
+    for _ in 0..capacity {
+        let resp = actix_web::test::call_service(&app, request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let resp = actix_web::test::call_service(&app, request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // a slow Redis is used again, its bucket was never consumed
+    redis.set_faults(Faults {
+        delay: std::time::Duration::from_millis(30),
+        ..Default::default()
+    });
+    let resp = actix_web::test::call_service(&app, request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("RateLimit-Remaining").unwrap(),
+        (capacity - 1).to_string().as_str()
+    );
+
+    common::REDIS_CLIENT
+        .delete("ratelimit:login:ip:192.0.2.47")
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_concurrency_limiter_refuses_requests_beyond_its_limit() {
+    let app = App::new().service(
+        web::resource("/slow")
+            .wrap(ConcurrencyLimiter::global("test_slow", 1).retry_after(2))
+            .to(|| async {
+                actix_web::rt::time::sleep(std::time::Duration::from_millis(200)).await;
+                HttpResponse::Ok().finish()
+            }),
+    );
+    let app = actix_web::test::init_service(app).await;
+
+    let request = || {
+        actix_web::test::TestRequest::get()
+            .uri("/slow")
+            .to_request()
+    };
+
+    // deux requêtes en même temps pour une seule place
+    let (first, second) = futures_util::future::join(
+        actix_web::test::try_call_service(&app, request()),
+        actix_web::test::try_call_service(&app, request()),
+    )
+    .await;
+
+    assert_eq!(first.unwrap().status(), StatusCode::OK);
+    let response = second.unwrap_err().error_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "2");
+
+    // the place is released at the end of the request
+    let resp = actix_web::test::call_service(&app, request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use actix_web::{http::StatusCode, web, App};
+
+use api_caches::{
+    clock::SystemClock,
+    invalidation::InvalidationBusRedis,
+    user_profiles::{UserProfilesCache, UserProfilesCacheMemory, UserProfilesCacheRedis},
+};
+use api_db::repositories::users_repository::UsersRepository;
+use api_handlers::users;
+use api_services::{auth::services::create_valid_token, users::ProfileCacheStats};
+use api_types::user::SafeUser;
+
+mod common;
+
+#[actix_web::test]
+async fn test_profile_is_cached_and_invalidated_on_update() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = UsersRepository::new(Arc::clone(&pool));
+    let user_service = common::user_service(Arc::clone(&pool));
+    let profiles_cache =
+        UserProfilesCacheRedis::new(Arc::clone(&common::REDIS_CLIENT), &common::CONFIG);
+
+    let user = common::insert_test_user(Arc::new(users_repository.clone())).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+
+    let app = App::new()
+        .app_data(web::Data::new(common::CONFIG.clone()))
+        .app_data(web::Data::new(users_repository))
+        .app_data(web::Data::new(user_service.clone()))
+        .configure(users::service::<UsersRepository, UserProfilesCacheRedis>);
+    let app = actix_web::test::init_service(app).await;
+
+    let profile = || {
+        actix_web::test::TestRequest::get()
+            .uri("/v1/users/profile")
+            .append_header(("Authorization", format!("Bearer {}", jwt)))
+            .to_request()
+    };
+
+    let first: SafeUser = actix_web::test::call_and_read_body_json(&app, profile()).await;
+    let second: SafeUser = actix_web::test::call_and_read_body_json(&app, profile()).await;
+    assert_eq!(first, second);
+    assert_eq!(
+        user_service.profile_cache_stats(),
+        ProfileCacheStats { hits: 1, misses: 1 }
+    );
+    assert_eq!(
+        profiles_cache.get_profile(user.id).await.unwrap(),
+        Some(first)
+    );
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/v1/users/{}", user.id))
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(serde_json::json!({ "pseudo": "renamed" }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(profiles_cache.get_profile(user.id).await.unwrap(), None);
+
+    let updated: SafeUser = actix_web::test::call_and_read_body_json(&app, profile()).await;
+    assert_eq!(updated.pseudo, "renamed");
+    assert_eq!(
+        user_service.profile_cache_stats(),
+        ProfileCacheStats { hits: 1, misses: 2 }
+    );
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/v1/users/{}", user.id))
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(profiles_cache.get_profile(user.id).await.unwrap(), None);
+}
+
+#[actix_web::test]
+async fn test_concurrent_misses_load_the_profile_once() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = Arc::new(UsersRepository::new(Arc::clone(&pool)));
+    let user_service = common::user_service(Arc::clone(&pool));
+
+    let user = common::insert_test_user(users_repository).await;
+
+    let profiles =
+        futures_util::future::join_all((0..10).map(|_| user_service.get_safe_user(user.id))).await;
+    assert!(profiles
+        .iter()
+        .all(|profile| profile.as_ref().unwrap().id == user.id));
+    assert_eq!(
+        user_service.profile_cache_stats(),
+        ProfileCacheStats { hits: 9, misses: 1 }
+    );
+
+    user_service.invalidate_profile(user.id).await;
+}
+
+#[actix_web::test]
+async fn test_profile_cached_in_memory_is_invalidated_on_every_instance() {
+    let pool = api_db::connection::establish_testing_connection(&common::CONFIG);
+    let users_repository = UsersRepository::new(Arc::clone(&pool));
+    let user = common::insert_test_user(Arc::new(users_repository.clone())).await;
+    let jwt = create_valid_token(&common::CONFIG, user.id).unwrap();
+
+    // deux instances de l'api, chacune avec ses profils en mémoire et son abonnement au bus
+    let instance = || async {
+        let bus = Arc::new(InvalidationBusRedis::new(Arc::clone(&common::REDIS_CLIENT)));
+        let profiles_cache = UserProfilesCacheMemory::new(&common::CONFIG, Arc::new(SystemClock))
+            .with_invalidation_bus(bus.clone());
+        bus.start();
+        for _ in 0..100 {
+            if bus.is_subscribed() {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(bus.is_subscribed());
+
+        let user_service =
+            api_services::users::UsersService::new(Arc::clone(&pool), Arc::new(profiles_cache));
+        let app = App::new()
+            .app_data(web::Data::new(common::CONFIG.clone()))
+            .app_data(web::Data::new(users_repository.clone()))
+            .app_data(web::Data::new(user_service))
+            .configure(users::service::<UsersRepository, UserProfilesCacheMemory>);
+        actix_web::test::init_service(app).await
+    };
+    let first = instance().await;
+    let second = instance().await;
+
+    let profile = || {
+        actix_web::test::TestRequest::get()
+            .uri("/v1/users/profile")
+            .append_header(("Authorization", format!("Bearer {}", jwt)))
+            .to_request()
+    };
+    let cached: SafeUser = actix_web::test::call_and_read_body_json(&second, profile()).await;
+
+    let pseudo = format!("renamed_{}", user.id);
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/v1/users/{}", user.id))
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .set_json(serde_json::json!({ "pseudo": pseudo }))
+        .to_request();
+    let resp = actix_web::test::call_service(&first, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let mut read = cached;
+    for _ in 0..100 {
+        read = actix_web::test::call_and_read_body_json(&second, profile()).await;
+        if read.pseudo == pseudo {
+            break;
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(read.pseudo, pseudo);
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/v1/users/{}", user.id))
+        .append_header(("Authorization", format!("Bearer {}", jwt)))
+        .to_request();
+    let resp = actix_web::test::call_service(&first, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -4,6 +4,8 @@ use tokio_cron_scheduler::{Job as JobBuilder, JobScheduler, JobSchedulerError};
 use uuid::Uuid;
 
 // declare the modules here
+pub mod oauth_authorization_codes;
+pub mod rate_limit_buckets;
 
 pub trait Job {
     fn schedule(&self) -> String;
@@ -0,0 +1,55 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use tokio_cron_scheduler::JobScheduler;
+use uuid::Uuid;
+
+use api_db::repository::OAuthAuthorizationCodeRepository;
+
+use crate::Job;
+
+/// Deletes the oauth authorization codes which expired without being exchanged.
+/// The codes exchanged for tokens are deleted when they are consumed.
+pub struct OAuthAuthorizationCodesCleanupJob<R: OAuthAuthorizationCodeRepository> {
+    schedule: String,
+    authorization_codes_repository: Arc<R>,
+}
+
+impl<R: OAuthAuthorizationCodeRepository> OAuthAuthorizationCodesCleanupJob<R> {
+    /// Creates the job, every hour by default.
+    pub fn new(authorization_codes_repository: Arc<R>, schedule: Option<String>) -> Self {
+        Self {
+            schedule: schedule.unwrap_or_else(|| "0 30 * * * *".to_string()),
+            authorization_codes_repository,
+        }
+    }
+}
+
+impl<R: OAuthAuthorizationCodeRepository> Job for OAuthAuthorizationCodesCleanupJob<R> {
+    fn schedule(&self) -> String {
+        self.schedule.clone()
+    }
+
+    fn run(
+        &self,
+        mut job_scheduler_lock: JobScheduler,
+        uuid: Uuid,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let authorization_codes_repository = Arc::clone(&self.authorization_codes_repository);
+
+        Box::pin(async move {
+            let now = chrono::Utc::now().naive_utc();
+            match authorization_codes_repository
+                .delete_expired_codes(now)
+                .await
+            {
+                Ok(0) => log::debug!("No expired oauth authorization code"),
+                Ok(deleted) => log::info!("Deleted {} expired oauth authorization codes", deleted),
+                Err(err) => {
+                    log::error!("Failed to clean up the oauth authorization codes: {}", err)
+                }
+            }
+
+            let _next_tick = job_scheduler_lock.next_tick_for_job(uuid).await;
+        })
+    }
+}
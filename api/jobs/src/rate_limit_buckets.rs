@@ -0,0 +1,49 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use tokio_cron_scheduler::JobScheduler;
+use uuid::Uuid;
+
+use api_caches::token_buckets::TokenBucketsCache;
+
+use crate::Job;
+
+/// Deletes the rate limit buckets written without a ttl, by the versions of the api
+/// before the buckets expired. The new buckets expire by themselves.
+pub struct RateLimitBucketsCleanupJob<T: TokenBucketsCache> {
+    schedule: String,
+    token_buckets_cache: Arc<T>,
+}
+
+impl<T: TokenBucketsCache> RateLimitBucketsCleanupJob<T> {
+    /// Creates the job, every hour by default.
+    pub fn new(token_buckets_cache: Arc<T>, schedule: Option<String>) -> Self {
+        Self {
+            schedule: schedule.unwrap_or_else(|| "0 0 * * * *".to_string()),
+            token_buckets_cache,
+        }
+    }
+}
+
+impl<T: TokenBucketsCache> Job for RateLimitBucketsCleanupJob<T> {
+    fn schedule(&self) -> String {
+        self.schedule.clone()
+    }
+
+    fn run(
+        &self,
+        mut job_scheduler_lock: JobScheduler,
+        uuid: Uuid,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let token_buckets_cache = Arc::clone(&self.token_buckets_cache);
+
+        Box::pin(async move {
+            match token_buckets_cache.delete_buckets_without_ttl().await {
+                Ok(0) => log::debug!("No rate limit bucket without ttl"),
+                Ok(deleted) => log::warn!("Deleted {} rate limit buckets without ttl", deleted),
+                Err(err) => log::error!("Failed to clean up the rate limit buckets: {}", err),
+            }
+
+            let _next_tick = job_scheduler_lock.next_tick_for_job(uuid).await;
+        })
+    }
+}
@@ -0,0 +1,208 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::{
+        header::{HeaderValue, RETRY_AFTER},
+        StatusCode,
+    },
+    web, Error, HttpMessage, ResponseError,
+};
+use api_caches::bans::{BansCache, BansCacheRedis};
+use api_configs::config::Config;
+use api_errors::{ServiceError, ServiceErrorType};
+use chrono::Utc;
+use futures_util::future::LocalBoxFuture;
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    client_ip::client_ip,
+    rate_limiter::keys::{BearerKeyExtractor, RateLimitKeyExtractor},
+};
+
+/// Marker inserted in the request extensions for the allowlisted ips and callers,
+/// which are not rate limited.
+#[derive(Clone, Copy, Debug)]
+pub struct Allowlisted;
+
+/// Access control middleware, it must wrap the rate limiters.
+/// The allowlisted ips and callers of the config bypass every check, the denied ones
+/// and the ones banned in Redis are refused with a 403.
+/// When enabled, repeated 401 and 429 responses ban the caller, or its ip if anonymous.
+pub struct AccessControl {
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        AccessControl {
+            key_extractor: Rc::new(BearerKeyExtractor),
+        }
+    }
+}
+
+impl AccessControl {
+    /// Replace the extractor of the identity of the callers.
+    pub fn key_extractor(mut self, key_extractor: impl RateLimitKeyExtractor) -> Self {
+        self.key_extractor = Rc::new(key_extractor);
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessControl
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessControlMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessControlMiddleware {
+            service: Rc::new(service),
+            key_extractor: Rc::clone(&self.key_extractor),
+        }))
+    }
+}
+
+pub struct AccessControlMiddleware<S> {
+    service: Rc<S>,
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessControlMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let key_extractor = Rc::clone(&self.key_extractor);
+
+        Box::pin(async move {
+            let Some(config) = req.app_data::<web::Data<Config>>().cloned() else {
+                return service.call(req).await;
+            };
+            let access_control = &config.access_control_info;
+
+            let ip = client_ip(req.request());
+            let caller = key_extractor.extract(&req).await;
+
+            if access_control.is_allowed(ip, caller.as_deref()) {
+                req.extensions_mut().insert(Allowlisted);
+                return service.call(req).await;
+            }
+
+            if access_control.is_denied(ip, caller.as_deref()) {
+                log::warn!("Denied request from {:?} {:?}", ip, caller);
+                return Err(forbidden("Access denied".to_string()).into());
+            }
+
+            let targets: Vec<String> = caller
+                .iter()
+                .cloned()
+                .chain(ip.map(|ip| format!("ip:{}", ip)))
+                .collect();
+
+            let bans_cache = req.app_data::<web::Data<Arc<BansCacheRedis>>>().cloned();
+            let Some(bans_cache) = bans_cache else {
+                log::error!("BansCacheRedis not found, make sure it is set in your app data");
+                return service.call(req).await;
+            };
+
+            // une panne de redis ne bloque pas les requêtes, le rate limiter a sa propre politique
+            match bans_cache.find_ban(&targets).await {
+                Ok(Some(ban)) => {
+                    let error = forbidden(format!("Access denied until {}", ban.expires_at));
+                    let mut response = error.error_response();
+                    response.headers_mut().insert(
+                        RETRY_AFTER,
+                        HeaderValue::from((ban.expires_at - Utc::now()).num_seconds().max(1)),
+                    );
+                    return Err(InternalError::from_response(error, response).into());
+                }
+                Ok(None) => {}
+                Err(err) => log::error!("Failed to read the bans: {}", err),
+            }
+
+            let res = service.call(req).await;
+
+            let status = match &res {
+                Ok(res) => res.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            let strike = matches!(
+                status,
+                StatusCode::UNAUTHORIZED | StatusCode::TOO_MANY_REQUESTS
+            );
+
+            if strike && access_control.auto_ban_threshold > 0 {
+                // the caller when authenticated, otherwise its ip
+                if let Some(target) = targets.first() {
+                    auto_ban(&bans_cache, target, status, &config).await;
+                }
+            }
+
+            res
+        })
+    }
+}
+
+/// Count a refused request of a target, and ban it once the threshold of the config is reached.
+async fn auto_ban(bans_cache: &BansCacheRedis, target: &str, status: StatusCode, config: &Config) {
+    let access_control = &config.access_control_info;
+
+    let strikes = match bans_cache
+        .record_strike(target, access_control.auto_ban_window)
+        .await
+    {
+        Ok(strikes) => strikes,
+        Err(err) => {
+            log::error!("Failed to record a strike of {}: {}", target, err);
+            return;
+        }
+    };
+
+    if strikes < access_control.auto_ban_threshold {
+        return;
+    }
+
+    let reason = format!(
+        "{} responses 401 or 429 in {} seconds",
+        strikes, access_control.auto_ban_window
+    );
+    match bans_cache
+        .ban(target, &reason, access_control.auto_ban_duration)
+        .await
+    {
+        Ok(ban) => log::warn!(
+            "Banned {} until {} after a response {}: {}",
+            target,
+            ban.expires_at,
+            status.as_u16(),
+            reason
+        ),
+        Err(err) => log::error!("Failed to ban {}: {}", target, err),
+    }
+}
+
+fn forbidden(message: String) -> ServiceError {
+    ServiceError {
+        message: Some(message),
+        error_type: ServiceErrorType::Forbidden,
+    }
+}
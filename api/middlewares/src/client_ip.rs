@@ -0,0 +1,169 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::{
+    http::header::{HeaderMap, FORWARDED, X_FORWARDED_FOR},
+    web, HttpRequest,
+};
+use ipnet::IpNet;
+
+use api_configs::config::Config;
+
+/// Resolve the ip of the client of a request.
+/// The forwarded headers are only read when the peer is a trusted proxy of the config.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let trusted_proxies = req
+        .app_data::<web::Data<Config>>()
+        .map(|config| config.trusted_proxies.as_slice())
+        .unwrap_or_default();
+
+    resolve(
+        req.peer_addr().map(|addr| addr.ip()),
+        req.headers(),
+        trusted_proxies,
+    )
+}
+
+/// Walk the chain of proxies from the peer back to the client, through the trusted hops only.
+/// The first untrusted hop is the client, every hop before it could have been forged by him.
+///
+/// # Arguments
+/// * `peer` - The address of the socket.
+/// * `headers` - The headers of the request, `Forwarded` is preferred over `X-Forwarded-For`.
+/// * `trusted_proxies` - The addresses of the trusted proxies.
+pub fn resolve(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    let mut client = peer?;
+    if !is_trusted(&client) {
+        return Some(client);
+    }
+
+    // du proxy le plus proche au plus lointain
+    for hop in forwarded_chain(headers).into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !is_trusted(&client) {
+                    break;
+                }
+            }
+            // identifiant masqué ou invalide, on s'arrête au dernier proxy connu
+            None => break,
+        }
+    }
+
+    Some(client)
+}
+
+/// Addresses of the forwarded headers, from the client to the last proxy.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded = header_values(headers, &FORWARDED);
+
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| parse_node(value))
+            })
+            .collect();
+    }
+
+    header_values(headers, &X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+fn header_values<'a>(
+    headers: &'a HeaderMap,
+    name: &actix_web::http::header::HeaderName,
+) -> Vec<&'a str> {
+    headers
+        .get_all(name)
+        .filter_map(|value| value.to_str().ok())
+        .collect()
+}
+
+/// Parse an address which may be quoted, in brackets or with a port.
+fn parse_node(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            value
+                .strip_prefix('[')
+                .and_then(|value| value.strip_suffix(']'))
+                .and_then(|value| value.parse::<IpAddr>().ok())
+        })
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use actix_web::http::header::HeaderValue;
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    fn headers(name: actix_web::http::header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[allow(dead_code)] // bug pas important avec l'éditeur
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_forwarded_headers_of_untrusted_peer_are_ignored() {
+        let headers = headers(X_FORWARDED_FOR, "1.1.1.1");
+
+        assert_eq!(resolve(ip("8.8.8.8"), &headers, &[]), ip("8.8.8.8"));
+    }
+
+    #[test]
+    fn test_client_is_the_first_untrusted_hop() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        // the client forged the first address
+        let headers = headers(X_FORWARDED_FOR, "6.6.6.6, 2.2.2.2, 10.0.0.2");
+
+        assert_eq!(resolve(ip("10.0.0.1"), &headers, &trusted), ip("2.2.2.2"));
+    }
+
+    #[test]
+    fn test_forwarded_header_is_preferred() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let mut headers = headers(
+            FORWARDED,
+            r#"for=3.3.3.3;proto=https, for="[2001:db8::1]:4711""#,
+        );
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("4.4.4.4"));
+
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted),
+            ip("2001:db8::1")
+        );
+    }
+
+    #[test]
+    fn test_obfuscated_hop_stops_the_chain() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let headers = headers(FORWARDED, "for=5.5.5.5, for=_hidden, for=10.0.0.3");
+
+        assert_eq!(resolve(ip("10.0.0.1"), &headers, &trusted), ip("10.0.0.3"));
+    }
+}
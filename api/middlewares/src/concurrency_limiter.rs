@@ -0,0 +1,208 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderValue, RETRY_AFTER},
+    Error, ResponseError,
+};
+use api_errors::{ServiceError, ServiceErrorType};
+use futures_util::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Mutex,
+};
+
+use crate::{
+    client_ip::client_ip,
+    rate_limiter::keys::{BearerKeyExtractor, RateLimitKeyExtractor},
+};
+
+/// Requests in progress by limiter, shared by the workers of the instance.
+static IN_FLIGHT: Lazy<Mutex<HashMap<String, usize>>> = Lazy::new(Default::default);
+
+/// Concurrency limiter middleware, for the expensive routes.
+/// A limiter counts the requests in progress for the whole instance, or for each caller
+/// (or its ip if anonymous), and refuses the requests beyond its limit with a 503 and `Retry-After`.
+pub struct ConcurrencyLimiter {
+    name: &'static str,
+    limit: usize,
+    per_identity: bool,
+    retry_after: u64,
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+}
+
+impl ConcurrencyLimiter {
+    /// At most `limit` requests in progress for every caller, the name identifies the counter.
+    pub fn global(name: &'static str, limit: usize) -> Self {
+        ConcurrencyLimiter {
+            name,
+            limit,
+            per_identity: false,
+            retry_after: 1,
+            key_extractor: Rc::new(BearerKeyExtractor),
+        }
+    }
+
+    /// At most `limit` requests in progress for each caller.
+    pub fn per_identity(name: &'static str, limit: usize) -> Self {
+        ConcurrencyLimiter {
+            per_identity: true,
+            ..Self::global(name, limit)
+        }
+    }
+
+    /// Seconds sent in the `Retry-After` header of the refused requests.
+    pub fn retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = seconds;
+        self
+    }
+
+    /// Replace the extractor of the identity of the callers.
+    pub fn key_extractor(mut self, key_extractor: impl RateLimitKeyExtractor) -> Self {
+        self.key_extractor = Rc::new(key_extractor);
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ConcurrencyLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ConcurrencyLimiterMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ConcurrencyLimiterMiddleware {
+            service: Rc::new(service),
+            name: self.name,
+            limit: self.limit,
+            per_identity: self.per_identity,
+            retry_after: self.retry_after,
+            key_extractor: Rc::clone(&self.key_extractor),
+        }))
+    }
+}
+
+pub struct ConcurrencyLimiterMiddleware<S> {
+    service: Rc<S>,
+    name: &'static str,
+    limit: usize,
+    per_identity: bool,
+    retry_after: u64,
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+}
+
+impl<S, B> Service<ServiceRequest> for ConcurrencyLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let key_extractor = Rc::clone(&self.key_extractor);
+        let (name, limit, per_identity, retry_after) =
+            (self.name, self.limit, self.per_identity, self.retry_after);
+
+        Box::pin(async move {
+            let key = if per_identity {
+                let identity = match key_extractor.extract(&req).await {
+                    Some(key) => key,
+                    None => format!(
+                        "ip:{}",
+                        client_ip(req.request())
+                            .map(|ip| ip.to_string())
+                            .unwrap_or_default()
+                    ),
+                };
+                format!("{}:{}", name, identity)
+            } else {
+                name.to_string()
+            };
+
+            let Some(_slot) = InFlightSlot::acquire(key, limit) else {
+                log::warn!("Concurrency limit of {} reached", name);
+
+                let error = ServiceError {
+                    message: Some("Too many requests in progress, retry later".to_string()),
+                    error_type: ServiceErrorType::ServiceUnavailable,
+                };
+                let mut response = error.error_response();
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+
+                return Err(InternalError::from_response(error, response).into());
+            };
+
+            service.call(req).await
+        })
+    }
+}
+
+/// A request in progress, released when the request ends or is dropped.
+struct InFlightSlot {
+    key: String,
+}
+
+impl InFlightSlot {
+    fn acquire(key: String, limit: usize) -> Option<Self> {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|err| err.into_inner());
+        let count = in_flight.entry(key.clone()).or_default();
+
+        if *count >= limit {
+            return None;
+        }
+        *count += 1;
+
+        Some(InFlightSlot { key })
+    }
+}
+
+impl Drop for InFlightSlot {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(count) = in_flight.get_mut(&self.key) {
+            *count -= 1;
+            // pas de compteur conservé pour les appelants inactifs
+            if *count == 0 {
+                in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+
+    #[test]
+    fn test_slots_are_released() {
+        let first = InFlightSlot::acquire("test:slots".to_string(), 2);
+        let second = InFlightSlot::acquire("test:slots".to_string(), 2);
+        assert!(first.is_some() && second.is_some());
+        assert!(InFlightSlot::acquire("test:slots".to_string(), 2).is_none());
+        // les autres compteurs sont indépendants
+        assert!(InFlightSlot::acquire("test:other".to_string(), 2).is_some());
+
+        drop(first);
+        assert!(InFlightSlot::acquire("test:slots".to_string(), 2).is_some());
+
+        drop(second);
+        assert!(!IN_FLIGHT.lock().unwrap().contains_key("test:slots"));
+    }
+}
@@ -1,3 +1,7 @@
+#[allow(dead_code)]
 pub(crate) mod helpers;
 
+pub mod access_control;
+pub mod client_ip;
+pub mod concurrency_limiter;
 pub mod rate_limiter;
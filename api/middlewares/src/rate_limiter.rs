@@ -1,107 +1,261 @@
 use actix_web::{
+    body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    web, Error, HttpMessage,
+    http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    web, Error, HttpMessage, ResponseError,
+};
+use api_caches::{
+    errors::RateLimitError,
+    token_buckets::{BucketStatus, TokenBucketsCache, TokenBucketsCacheRedis},
+};
+use api_configs::{
+    config::Config,
+    rate_limit::{RateLimitDegradation, RateLimitPolicy},
 };
-use api_caches::token_buckets::{TokenBucketsCache, TokenBucketsCacheRedis};
 use api_errors::ServiceError;
 use futures_util::future::LocalBoxFuture;
 use std::{
     future::{ready, Ready},
+    marker::PhantomData,
+    rc::Rc,
     sync::Arc,
 };
 
-pub struct RateLimiter;
+pub mod fallback;
+pub mod keys;
+
+use crate::{access_control::Allowlisted, client_ip::client_ip};
+use fallback::LOCAL_RATE_LIMITER;
+use keys::{BearerKeyExtractor, RateLimitKeyExtractor};
+
+// en-têtes du draft IETF "RateLimit header fields for HTTP"
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Rate limiter middleware.
+/// Wrapping the app applies the first policy of the config matching the request,
+/// wrapping a route with `RateLimiter::policy` applies a named policy in addition.
+/// The buckets are per caller, given by the key extractor, or per ip for anonymous callers.
+/// The requests allowlisted by the `AccessControl` middleware are not limited.
+/// When Redis is unavailable, the degradation of the config lets the requests through,
+/// refuses them with a 503 or limits them in memory.
+/// The buckets are read from the `web::Data<Arc<T>>` of the app, `TokenBucketsCacheRedis`
+/// unless another cache is chosen with `RateLimiter::with_cache`.
+pub struct RateLimiter<T: TokenBucketsCache = TokenBucketsCacheRedis> {
+    policy: Option<&'static str>,
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+    cache: PhantomData<T>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter {
+            policy: None,
+            key_extractor: Rc::new(BearerKeyExtractor),
+            cache: PhantomData,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Opt into a policy of the config, by its name.
+    pub fn policy(name: &'static str) -> Self {
+        RateLimiter {
+            policy: Some(name),
+            ..Default::default()
+        }
+    }
+}
+
+impl<T: TokenBucketsCache> RateLimiter<T> {
+    /// Replace the extractor of the identity of the callers.
+    pub fn key_extractor(mut self, key_extractor: impl RateLimitKeyExtractor) -> Self {
+        self.key_extractor = Rc::new(key_extractor);
+        self
+    }
 
-impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+    /// Read the buckets from the `web::Data<Arc<U>>` of the app instead.
+    pub fn with_cache<U: TokenBucketsCache>(self) -> RateLimiter<U> {
+        RateLimiter {
+            policy: self.policy,
+            key_extractor: self.key_extractor,
+            cache: PhantomData,
+        }
+    }
+}
+
+impl<S, B, T> Transform<S, ServiceRequest> for RateLimiter<T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
+    T: TokenBucketsCache,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type InitError = ();
-    type Transform = RateLimiterMiddleware<S>;
+    type Transform = RateLimiterMiddleware<S, T>;
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(RateLimiterMiddleware { service }))
+        ready(Ok(RateLimiterMiddleware {
+            service: Rc::new(service),
+            policy: self.policy,
+            key_extractor: Rc::clone(&self.key_extractor),
+            cache: PhantomData,
+        }))
     }
 }
 
-pub struct RateLimiterMiddleware<S> {
-    service: S,
+pub struct RateLimiterMiddleware<S, T: TokenBucketsCache = TokenBucketsCacheRedis> {
+    service: Rc<S>,
+    policy: Option<&'static str>,
+    key_extractor: Rc<dyn RateLimitKeyExtractor>,
+    cache: PhantomData<T>,
 }
 
-impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+impl<S, B, T> Service<ServiceRequest> for RateLimiterMiddleware<S, T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
+    T: TokenBucketsCache,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Extraire les données nécessaires de la requête
-        // On peut obtenir soit l'Id utilisateur depuis le token JWT, sinon il faut utiliser l'IP
-        let ip = req
-            .connection_info()
-            .realip_remote_addr()
-            .unwrap_or_default()
-            .to_string();
+        let service = Rc::clone(&self.service);
+        let key_extractor = Rc::clone(&self.key_extractor);
+        let policy_name = self.policy;
 
-        let user_id = req
-            .extensions()
-            .get::<i32>()
-            .map(|el| el.to_string())
-            .unwrap_or(ip);
+        Box::pin(async move {
+            if req.extensions().contains::<Allowlisted>() {
+                return service.call(req).await.map(|res| res.map_into_left_body());
+            }
 
-        let bucket_cache = req
-            .app_data::<web::Data<Arc<TokenBucketsCacheRedis>>>()
-            .cloned();
+            // l'identité de l'appelant, sinon son ip
+            let key = key_extractor.extract(&req).await;
+            let authenticated = key.is_some();
+            let key = key.unwrap_or_else(|| {
+                format!(
+                    "ip:{}",
+                    client_ip(req.request())
+                        .map(|ip| ip.to_string())
+                        .unwrap_or_default()
+                )
+            });
 
-        let http_method = req.method().clone();
+            // la politique demandée par la route, sinon la première qui correspond à la requête
+            let policy = req.app_data::<web::Data<Config>>().and_then(|config| {
+                let policies = &config.rate_limit_info;
+                match policy_name {
+                    Some(name) => policies.get(name),
+                    None => policies.find(req.path(), req.method().as_str(), authenticated),
+                }
+                .cloned()
+            });
+            let degradation = req
+                .app_data::<web::Data<Config>>()
+                .map(|config| config.rate_limit_info.degradation)
+                .unwrap_or_default();
 
-        let fut = self.service.call(req);
+            if policy.is_none() {
+                if let Some(name) = policy_name {
+                    log::error!("Rate limit policy {} not found in the config", name);
+                }
+            }
+
+            let bucket_cache = req.app_data::<web::Data<Arc<T>>>().cloned();
 
-        Box::pin(async move {
             if let Some(bucket_cache) = bucket_cache {
-                let exists = bucket_cache
-                    .bucket_exists(&user_id)
-                    .await
-                    .map_err(ServiceError::from)?;
-
-                if !exists {
-                    // we create a new bucket for the user
-                    bucket_cache
-                        .create_bucket(&user_id)
-                        .await
-                        .map_err(ServiceError::from)?;
-                }
+                if let Some(policy) = policy {
+                    let id = format!("{}:{}", policy.name, key);
+
+                    // refill and consume in a single atomic call to the cache
+                    let status = match bucket_cache.consume_tokens(&id, &policy).await {
+                        Ok(status) => status,
+                        Err(err) => {
+                            log::error!(
+                                "Rate limiter unavailable for the policy {}, {:?} applied: {}",
+                                policy.name,
+                                degradation,
+                                err
+                            );
+
+                            match degradation {
+                                RateLimitDegradation::FailOpen => {
+                                    return service
+                                        .call(req)
+                                        .await
+                                        .map(|res| res.map_into_left_body())
+                                }
+                                RateLimitDegradation::FailClosed => {
+                                    let response = ServiceError::from(err).error_response();
+                                    return Ok(req.into_response(response).map_into_right_body());
+                                }
+                                RateLimitDegradation::Local => {
+                                    LOCAL_RATE_LIMITER.consume_tokens(&id, &policy)
+                                }
+                            }
+                        }
+                    };
 
-                bucket_cache
-                    .refill_bucket(&user_id)
-                    .await
-                    .map_err(ServiceError::from)?;
+                    if !status.allowed {
+                        // same body as the error, with the headers telling when to retry
+                        let error = ServiceError::from(RateLimitError::RateLimitExceeded);
+                        let mut response = error.error_response();
+                        response.headers_mut().insert(
+                            RETRY_AFTER,
+                            HeaderValue::from(status.retry_after_ms.div_ceil(1000)),
+                        );
+                        insert_headers(response.headers_mut(), &status, &policy);
 
-                // can consume tokens or return a rate limit exceeded error
-                bucket_cache
-                    .consume_tokens(&user_id, &http_method)
-                    .await
-                    .map_err(ServiceError::from)?;
+                        // une réponse et non une erreur, pour que les middlewares qui
+                        // l'enveloppent (cors, logger) la traitent comme les autres
+                        return Ok(req.into_response(response).map_into_right_body());
+                    }
+
+                    let mut res = service.call(req).await?;
+                    insert_headers(res.headers_mut(), &status, &policy);
+
+                    return Ok(res.map_into_left_body());
+                }
             } else {
                 // Handle the case when bucket_cache is None
                 // For example, you can log an error or return a default value
                 log::error!(
-                    "TokenBucketsCacheRedis not found, make sure it is set in your app data"
+                    "{} not found, make sure it is set in your app data",
+                    std::any::type_name::<T>()
                 );
             }
 
-            fut.await
+            service.call(req).await.map(|res| res.map_into_left_body())
         })
     }
 }
+
+/// Add the RateLimit headers of the bucket to a response.
+/// When several limiters apply to a route, the headers of the most restrictive one are kept.
+fn insert_headers(headers: &mut HeaderMap, status: &BucketStatus, policy: &RateLimitPolicy) {
+    let more_restrictive = headers
+        .get(RATELIMIT_REMAINING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|remaining| remaining <= status.remaining);
+
+    if more_restrictive {
+        return;
+    }
+
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(policy.capacity));
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from(status.remaining));
+    headers.insert(
+        RATELIMIT_RESET,
+        HeaderValue::from(status.reset_after_ms.div_ceil(1000)),
+    );
+}
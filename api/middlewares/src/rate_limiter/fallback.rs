@@ -0,0 +1,116 @@
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use api_caches::token_buckets::BucketStatus;
+use api_configs::rate_limit::RateLimitPolicy;
+use once_cell::sync::Lazy;
+
+/// Above this number of buckets, the full ones are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// The in-process limiter of the instance, shared by the workers.
+pub static LOCAL_RATE_LIMITER: Lazy<LocalRateLimiter> = Lazy::new(LocalRateLimiter::default);
+
+struct LocalBucket {
+    tokens: f64,
+    last_refill_time: Instant,
+}
+
+/// Token buckets in memory, used while Redis is unavailable.
+/// Every policy is limited as a token bucket whatever its algorithm, and the limits
+/// apply per instance of the api rather than globally.
+#[derive(Default)]
+pub struct LocalRateLimiter {
+    buckets: Mutex<HashMap<String, LocalBucket>>,
+}
+
+impl LocalRateLimiter {
+    /// Refill the bucket of a key, then consume the cost of the policy if there are enough tokens.
+    pub fn consume_tokens(&self, id: &str, policy: &RateLimitPolicy) -> BucketStatus {
+        self.consume_tokens_at(id, policy, Instant::now())
+    }
+
+    fn consume_tokens_at(&self, id: &str, policy: &RateLimitPolicy, now: Instant) -> BucketStatus {
+        let capacity = policy.capacity as f64;
+        // tokens par milliseconde
+        let rate = policy.refill_amount as f64 / (policy.refill_period.max(1) * 1000) as f64;
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.last_refill_time).as_millis() as f64;
+                bucket.tokens + elapsed * rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(id.to_string()).or_insert(LocalBucket {
+            tokens: capacity,
+            last_refill_time: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill_time).as_millis() as f64;
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.last_refill_time = now;
+
+        let cost = policy.cost as f64;
+        let allowed = bucket.tokens >= cost;
+        let retry_after_ms = if allowed {
+            bucket.tokens -= cost;
+            0
+        } else {
+            ((cost - bucket.tokens) / rate).ceil() as u64
+        };
+
+        BucketStatus {
+            allowed,
+            remaining: bucket.tokens.floor() as u64,
+            retry_after_ms,
+            reset_after_ms: ((capacity - bucket.tokens) / rate).ceil() as u64,
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::time::Duration;
+
+    #[allow(dead_code)]
+    fn policy() -> RateLimitPolicy {
+        // 2 requests, then one every 30 seconds
+        RateLimitPolicy {
+            name: "test".to_string(),
+            path_prefix: String::new(),
+            methods: vec![],
+            caller: Default::default(),
+            algorithm: Default::default(),
+            capacity: 2,
+            refill_amount: 2,
+            refill_period: 60,
+            cost: 1,
+            opt_in: false,
+        }
+    }
+
+    #[test]
+    fn test_local_buckets_are_refilled() {
+        let limiter = LocalRateLimiter::default();
+        let policy = policy();
+        let now = Instant::now();
+
+        assert!(limiter.consume_tokens_at("ip:1", &policy, now).allowed);
+        assert!(limiter.consume_tokens_at("ip:1", &policy, now).allowed);
+
+        let status = limiter.consume_tokens_at("ip:1", &policy, now);
+        assert!(!status.allowed);
+        assert_eq!(status.retry_after_ms, 30_000);
+        assert_eq!(status.reset_after_ms, 60_000);
+
+        // les autres clés ont leur propre bucket
+        assert!(limiter.consume_tokens_at("ip:2", &policy, now).allowed);
+
+        let later = now + Duration::from_secs(30);
+        assert!(limiter.consume_tokens_at("ip:1", &policy, later).allowed);
+    }
+}
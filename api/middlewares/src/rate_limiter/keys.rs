@@ -0,0 +1,68 @@
+use actix_web::{dev::ServiceRequest, http::header::AUTHORIZATION, web, HttpMessage};
+
+use api_configs::config::Config;
+use api_services::{
+    api_keys::API_KEY_PREFIX,
+    auth::{
+        helpers::hash_secret,
+        middleware::insert_principal,
+        services::validate_token,
+        types::{Principal, Subject},
+    },
+};
+
+/// Extracts the identity of the caller, used as key of the rate limit buckets.
+#[async_trait::async_trait(?Send)]
+pub trait RateLimitKeyExtractor: 'static {
+    /// Returns the key of an authenticated caller, `None` for an anonymous caller, keyed by its ip.
+    async fn extract(&self, req: &ServiceRequest) -> Option<String>;
+}
+
+/// Default key extractor, the limiter runs before the authentication of the scopes
+/// so the JWT is validated here, from its signature only, and the principal is kept in the
+/// request extensions for the authentication middleware.
+/// An API key can only be checked in the database, it is left to the authentication and its
+/// caller is keyed by the SHA-256 of the key: the scripts behind a shared ip have their own
+/// buckets, and the requests refused by the limiter never reach the database.
+/// An invalid JWT is treated as an anonymous caller.
+#[derive(Clone, Copy, Default)]
+pub struct BearerKeyExtractor;
+
+#[async_trait::async_trait(?Send)]
+impl RateLimitKeyExtractor for BearerKeyExtractor {
+    async fn extract(&self, req: &ServiceRequest) -> Option<String> {
+        // déjà authentifiée par un autre limiteur
+        if let Some(principal) = req.extensions().get::<Principal>() {
+            return Some(key(principal));
+        }
+
+        // la validation des JWT a besoin de la config
+        req.app_data::<web::Data<Config>>()?;
+
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?
+            .trim();
+
+        // la clé elle-même ne doit pas apparaître dans les clés de Redis
+        if token.starts_with(API_KEY_PREFIX) {
+            return Some(format!("api_key:{}", hash_secret(token)));
+        }
+
+        let principal = Principal::from(validate_token(req, token).ok()?.claims);
+        let key = key(&principal);
+        insert_principal(req, principal);
+
+        Some(key)
+    }
+}
+
+fn key(principal: &Principal) -> String {
+    match principal.subject {
+        Subject::User(id) => format!("user:{}", id),
+        Subject::Client(id) => format!("client:{}", id),
+    }
+}
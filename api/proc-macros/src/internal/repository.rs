@@ -1 +1,242 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, Path};
 
+/// The types named by `#[repository(name = ..., new = ..., insertable = ...)]`, and the table
+/// of the model named by `#[diesel(table_name = ...)]`.
+struct RepositoryAttributes {
+    name: Ident,
+    new: Path,
+    insertable: Path,
+    table: Path,
+}
+
+impl RepositoryAttributes {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let attr = input
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("repository"))
+            .ok_or_else(|| {
+                syn::Error::new(
+                    input.ident.span(),
+                    "missing `#[repository(name = ..., new = ..., insertable = ...)]`",
+                )
+            })?;
+
+        let (mut name, mut new, mut insertable) = (None, None, None);
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                if name.is_some() {
+                    return Err(meta.error("duplicate key"));
+                }
+                name = Some(meta.value()?.parse::<Ident>()?);
+                return Ok(());
+            }
+            let slot = if meta.path.is_ident("new") {
+                &mut new
+            } else if meta.path.is_ident("insertable") {
+                &mut insertable
+            } else {
+                return Err(meta.error("unknown key, expected `name`, `new` or `insertable`"));
+            };
+            if slot.is_some() {
+                return Err(meta.error("duplicate key"));
+            }
+            *slot = Some(meta.value()?.parse::<Path>()?);
+            Ok(())
+        })?;
+
+        let missing = |key: &str| {
+            syn::Error::new_spanned(attr, format!("missing `{}` in `#[repository(...)]`", key))
+        };
+        Ok(RepositoryAttributes {
+            name: name.ok_or_else(|| missing("name"))?,
+            new: new.ok_or_else(|| missing("new"))?,
+            insertable: insertable.ok_or_else(|| missing("insertable"))?,
+            table: Self::table_name(input)?,
+        })
+    }
+
+    /// The table of the model is the one of Diesel, the other keys of `#[diesel(...)]` are skipped.
+    fn table_name(input: &DeriveInput) -> syn::Result<Path> {
+        let mut table = None;
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("diesel"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("table_name") {
+                    table = Some(meta.value()?.parse::<Path>()?);
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<syn::Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    content.parse::<TokenStream>()?;
+                }
+                Ok(())
+            })?;
+        }
+
+        table.ok_or_else(|| {
+            syn::Error::new(
+                input.ident.span(),
+                "missing `#[diesel(table_name = ...)]`, the repository queries the table of the model",
+            )
+        })
+    }
+}
+
+/// Generates the repository of a Diesel model: a struct holding the pool in a `conn` field,
+/// its `new` constructor and its implementation of `api_db::repository::Repository`.
+/// The queries use the `id` column of the table, and `create` converts the new item with
+/// `From<&N>` for the insertable type.
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let has_id = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .any(|field| field.ident.as_ref().is_some_and(|ident| ident == "id")),
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`Repository` can only be derived for a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`Repository` can only be derived for a struct with named fields",
+            ))
+        }
+    };
+    if !has_id {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`Repository` needs an `id` field, the primary key of the table",
+        ));
+    }
+
+    let RepositoryAttributes {
+        name,
+        new,
+        insertable,
+        table,
+    } = RepositoryAttributes::parse(&input)?;
+    let model = &input.ident;
+    let vis = &input.vis;
+    let doc = format!(
+        "Repository of `{}`, generated by `#[derive(Repository)]`.",
+        model
+    );
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #name {
+            // visible dans la crate pour les requêtes écrites à la main dans les repositories
+            pub(crate) conn: ::api_db::connection::Pool,
+        }
+
+        impl #name {
+            pub fn new(conn: ::api_db::connection::Pool) -> Self {
+                Self { conn }
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl ::api_db::repository::Repository<#model, #new> for #name {
+            async fn get(&self, id: i32) -> ::api_db::repository::RepositoryResult<#model> {
+                use ::diesel::prelude::*;
+                self.conn
+                    .run(move |conn| {
+                        #table::table
+                            .filter(#table::id.eq(id))
+                            .select(#model::as_select())
+                            .first(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+
+            async fn get_all(&self) -> ::api_db::repository::RepositoryResult<Vec<#model>> {
+                use ::diesel::prelude::*;
+                self.conn
+                    .run(move |conn| {
+                        #table::table
+                            .select(#model::as_select())
+                            .load(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+
+            async fn get_page(
+                &self,
+                offset: i64,
+                limit: i64,
+            ) -> ::api_db::repository::RepositoryResult<Vec<#model>> {
+                use ::diesel::prelude::*;
+                self.conn
+                    .run(move |conn| {
+                        #table::table
+                            .order(#table::id.asc())
+                            .offset(offset)
+                            .limit(limit)
+                            .select(#model::as_select())
+                            .load(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+
+            async fn create(&self, item: &#new) -> ::api_db::repository::RepositoryResult<#model> {
+                use ::diesel::prelude::*;
+                let item = item.clone();
+                self.conn
+                    .run(move |conn| {
+                        ::diesel::insert_into(#table::table)
+                            .values(#insertable::from(&item))
+                            .returning(#model::as_returning())
+                            .get_result(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+
+            async fn update(
+                &self,
+                id: i32,
+                item: &#model,
+            ) -> ::api_db::repository::RepositoryResult<#model> {
+                use ::diesel::prelude::*;
+                let item = item.clone();
+                self.conn
+                    .run(move |conn| {
+                        ::diesel::update(#table::table)
+                            .filter(#table::id.eq(id))
+                            .set(item)
+                            .returning(#model::as_returning())
+                            .get_result(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+
+            async fn delete(&self, id: i32) -> ::api_db::repository::RepositoryResult<usize> {
+                use ::diesel::prelude::*;
+                self.conn
+                    .run(move |conn| {
+                        ::diesel::delete(#table::table.filter(#table::id.eq(id)))
+                            .execute(conn)
+                            .map_err(::api_errors::ServiceError::from)
+                    })
+                    .await
+            }
+        }
+    })
+}
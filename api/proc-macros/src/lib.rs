@@ -52,3 +52,14 @@ pub fn derive_updatable(input: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+/// Generates the repository of a Diesel model and its implementation of `Repository<T, N>`,
+/// see `internal::repository`.
+#[proc_macro_derive(Repository, attributes(repository, diesel))]
+pub fn derive_repository(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    internal::repository::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
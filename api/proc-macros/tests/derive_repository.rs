@@ -0,0 +1,6 @@
+#[test]
+fn test_derive_repository() {
+    let tests = trybuild::TestCases::new();
+    tests.pass("tests/repository/pass_*.rs");
+    tests.compile_fail("tests/repository/fail_*.rs");
+}
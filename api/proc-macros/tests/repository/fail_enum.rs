@@ -0,0 +1,8 @@
+use api_proc_macros::Repository;
+
+#[derive(Repository)]
+pub enum Book {
+    Empty,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use api_proc_macros::Repository;
+
+#[derive(Repository)]
+#[diesel(table_name = books)]
+pub struct Book {
+    pub id: i32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use api_proc_macros::Repository;
+
+#[derive(Repository)]
+#[diesel(table_name = books)]
+#[repository(name = BooksRepository, new = NewBook)]
+pub struct Book {
+    pub id: i32,
+}
+
+fn main() {}
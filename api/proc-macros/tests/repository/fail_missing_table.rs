@@ -0,0 +1,10 @@
+use api_proc_macros::Repository;
+
+#[derive(Repository)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = BooksRepository, new = NewBook, insertable = InsertableBook)]
+pub struct Book {
+    pub id: i32,
+}
+
+fn main() {}
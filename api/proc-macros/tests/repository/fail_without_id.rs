@@ -0,0 +1,10 @@
+use api_proc_macros::Repository;
+
+#[derive(Repository)]
+#[diesel(table_name = books)]
+#[repository(name = BooksRepository, new = NewBook, insertable = InsertableBook)]
+pub struct Book {
+    pub isbn: String,
+}
+
+fn main() {}
@@ -0,0 +1,42 @@
+use api_db::{connection::Pool, repository::Repository};
+use api_proc_macros::Repository;
+use diesel::prelude::*;
+
+diesel::table! {
+    books (id) {
+        id -> Int4,
+        title -> Text,
+    }
+}
+
+#[derive(Queryable, Selectable, AsChangeset, Clone, Repository)]
+#[diesel(table_name = books)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[repository(name = BooksRepository, new = NewBook, insertable = InsertableBook)]
+pub struct Book {
+    pub id: i32,
+    pub title: String,
+}
+
+#[derive(Clone)]
+pub struct NewBook {
+    pub title: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = books)]
+pub struct InsertableBook<'a> {
+    pub title: &'a str,
+}
+
+impl<'a> From<&'a NewBook> for InsertableBook<'a> {
+    fn from(book: &'a NewBook) -> Self {
+        InsertableBook { title: &book.title }
+    }
+}
+
+fn is_repository<R: Repository<Book, NewBook> + Clone>(_: fn(Pool) -> R) {}
+
+fn main() {
+    is_repository(BooksRepository::new);
+}
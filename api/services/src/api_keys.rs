@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use api_db::{
+    connection::Pool, repositories::api_keys_repository::ApiKeysRepository,
+    repository::ApiKeyRepository, repository::Repository,
+};
+use api_errors::{ServiceError, ServiceErrorType};
+use api_types::{
+    api_key::{CreatedApiKey, InputApiKey, NewApiKey, SafeApiKey},
+    scopes,
+};
+
+use crate::auth::{
+    helpers::hash_secret,
+    types::{Principal, Subject},
+};
+
+/// Prefix of every API key, used by the authentication middleware to recognize them.
+pub const API_KEY_PREFIX: &str = "efk_";
+
+/// Number of characters of the key kept in clear to help the user identify it.
+const DISPLAYED_PREFIX_LENGTH: usize = 12;
+
+/// `last_used_at` is only written when the last record is older than this interval (in seconds).
+const LAST_USED_AT_PRECISION: i64 = 60;
+
+#[derive(Clone)]
+pub struct ApiKeysService {
+    api_keys_repository: ApiKeysRepository,
+}
+
+impl ApiKeysService {
+    pub fn new(conn: Pool) -> Self {
+        Self {
+            api_keys_repository: ApiKeysRepository::new(Arc::clone(&conn)),
+        }
+    }
+
+    /// Create a new API key for a user
+    /// The secret is returned only once, only its hash is stored
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The id of the owner of the key
+    /// * `input` - The name, scopes and lifetime of the key
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CreatedApiKey` with the secret, or a `ServiceError` if a scope is unknown
+    pub async fn create_api_key(
+        &self,
+        user_id: i32,
+        input: InputApiKey,
+    ) -> Result<CreatedApiKey, ServiceError> {
+        if !scopes::are_valid(&input.scopes) {
+            return Err(ServiceError {
+                message: Some("Unknown scope".to_string()),
+                error_type: ServiceErrorType::UnprocessableEntityError,
+            });
+        }
+
+        let key = generate_api_key();
+
+        let api_key = self
+            .api_keys_repository
+            .create(&NewApiKey {
+                user_id,
+                name: input.name,
+                prefix: key[..DISPLAYED_PREFIX_LENGTH].to_string(),
+                key_hash: hash_secret(&key),
+                scopes: input.scopes,
+                expires_at: input
+                    .expires_in_days
+                    .map(|days| chrono::Utc::now().naive_utc() + chrono::Duration::days(days)),
+            })
+            .await?;
+
+        Ok(CreatedApiKey {
+            key,
+            api_key: SafeApiKey::from(api_key),
+        })
+    }
+
+    /// List the API keys of a user
+    pub async fn list_api_keys(&self, user_id: i32) -> Result<Vec<SafeApiKey>, ServiceError> {
+        self.api_keys_repository
+            .get_api_keys_by_user(user_id)
+            .await
+            .map(|api_keys| api_keys.into_iter().map(SafeApiKey::from).collect())
+    }
+
+    /// Revoke an API key of a user
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `()` if the key was revoked, or a `ServiceError` if the user has no key with this id
+    pub async fn revoke_api_key(&self, user_id: i32, id: i32) -> Result<(), ServiceError> {
+        let deleted = self
+            .api_keys_repository
+            .delete_api_key_of_user(id, user_id)
+            .await?;
+
+        if deleted == 0 {
+            return Err(ServiceError {
+                message: Some("Api key not found".to_string()),
+                error_type: ServiceErrorType::NotFound,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Authenticate a request with an API key and record its usage
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Principal` of the owner of the key, or a `ServiceError` if the key is unknown or expired
+    pub async fn authenticate(&self, key: &str) -> Result<Principal, ServiceError> {
+        let bad_authentification = || ServiceError {
+            message: Some("Invalid api key".to_string()),
+            error_type: ServiceErrorType::BadAuthentification,
+        };
+
+        let api_key = self
+            .api_keys_repository
+            .get_api_key_by_hash(&hash_secret(key))
+            .await
+            .map_err(|_| bad_authentification())?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        if api_key.is_expired(now) {
+            return Err(bad_authentification());
+        }
+
+        let should_touch = api_key.last_used_at.is_none_or(|last_used_at| {
+            (now - last_used_at).num_seconds() >= LAST_USED_AT_PRECISION
+        });
+
+        if should_touch {
+            self.api_keys_repository
+                .touch_api_key(api_key.id, now)
+                .await?;
+        }
+
+        Ok(Principal {
+            subject: Subject::User(api_key.user_id),
+            scopes: Some(api_key.scopes),
+        })
+    }
+}
+
+pub fn generate_api_key() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    let secret: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+
+    format!("{}{}", API_KEY_PREFIX, secret)
+}
@@ -1,5 +1,7 @@
 pub mod claims;
+pub mod emails;
 pub mod errors;
+pub mod events;
 pub mod helpers;
 pub mod middleware;
 pub mod services;
@@ -1,12 +1,31 @@
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
+/// Kind of identity carried by the `sub` claim.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubjectType {
+    /// A user authenticated with an interactive login.
+    #[default]
+    User,
+    /// A registered OAuth client authenticated with the client credentials grant.
+    Client,
+}
+
 /// TokenClaims is a default struct that holds the claims of a JWT token.
 /// It includes the subject ID, issued at, and expiration time.
 #[derive(Deserialize, Serialize)]
 pub struct TokenClaims {
     pub sub: i32,
 
+    /// tokens issued before the introduction of this claim are user tokens
+    #[serde(default)]
+    pub sub_type: SubjectType,
+
+    /// space separated scopes, absent on the tokens of an interactive login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+
     #[serde(with = "jwt_numeric_date")]
     pub iat: OffsetDateTime,
 
@@ -28,8 +47,51 @@ impl TokenClaims {
             .unwrap()
             .assume_utc();
 
-        Self { sub, iat, exp }
+        Self {
+            sub,
+            sub_type: SubjectType::User,
+            scope: None,
+            iat,
+            exp,
+        }
+    }
+
+    /// Token issued to a relying party on behalf of a user, limited to the consented scopes.
+    pub fn new_scoped(sub: i32, scope: String, iat: OffsetDateTime, exp: OffsetDateTime) -> Self {
+        Self {
+            scope: Some(scope),
+            ..Self::new(sub, iat, exp)
+        }
     }
+
+    pub fn new_client(sub: i32, scope: String, iat: OffsetDateTime, exp: OffsetDateTime) -> Self {
+        Self {
+            sub_type: SubjectType::Client,
+            scope: Some(scope),
+            ..Self::new(sub, iat, exp)
+        }
+    }
+}
+
+/// Audience of the password reset tokens, the authentication refuses a token with an audience.
+pub const PASSWORD_RESET_AUDIENCE: &str = "password_reset";
+
+/// Claims of the token of a password reset link.
+/// `pwd` is the SHA-256 of the current password hash: the token can not be used again
+/// once the password changed.
+#[derive(Deserialize, Serialize)]
+pub struct PasswordResetClaims {
+    pub sub: i32,
+
+    pub aud: String,
+
+    pub pwd: String,
+
+    #[serde(with = "jwt_numeric_date")]
+    pub iat: OffsetDateTime,
+
+    #[serde(with = "jwt_numeric_date")]
+    pub exp: OffsetDateTime,
 }
 
 mod jwt_numeric_date {
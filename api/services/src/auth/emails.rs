@@ -0,0 +1,77 @@
+//! Emails sent by the authentication, they replace the information the api must not give
+//! to an anonymous client (like the existence of an account).
+
+use api_db::models::user::User;
+
+use crate::mailer::Email;
+
+/// Sent after a successful registration.
+pub fn welcome(user: &User) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Welcome".to_string(),
+        body: format!(
+            "Hello {},\n\nYour account has been created, you can now log in with your email and password.",
+            user.pseudo
+        ),
+    }
+}
+
+/// Sent when someone tries to register with the email of an existing account.
+pub fn account_already_exists(user: &User) -> Email {
+    let login_method = if user.password.is_some() {
+        "your email and password"
+    } else {
+        "Google"
+    };
+
+    Email {
+        to: user.email.clone(),
+        subject: "You already have an account".to_string(),
+        body: format!(
+            "Hello {},\n\nSomeone tried to create an account with your email, but you already have one. \
+             Log in with {} instead.\n\nIf it was not you, you can ignore this email.",
+            user.pseudo, login_method
+        ),
+    }
+}
+
+/// Sent when a password is added to an account created with Google.
+pub fn password_added(user: &User) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Password added".to_string(),
+        body: format!(
+            "Hello {},\n\nA password has been added to your account, you can now log in with your email \
+             and password as well as with Google.\n\nIf it was not you, contact us.",
+            user.pseudo
+        ),
+    }
+}
+
+/// Sent when someone tries to log in with Google using the email of an account created with a password.
+pub fn google_account_not_linked(user: &User) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Google sign in".to_string(),
+        body: format!(
+            "Hello {},\n\nSomeone tried to sign in with Google using your email. \
+             Your account was created with a password, log in with it instead.\n\n\
+             If it was not you, you can ignore this email.",
+            user.pseudo
+        ),
+    }
+}
+
+/// Sent when the owner of an account asks for a password reset.
+pub fn password_reset(user: &User, token: &str) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hello {},\n\nUse this token to choose a new password, it also unlocks your account:\n\n{}\n\n\
+             If it was not you, you can ignore this email.",
+            user.pseudo, token
+        ),
+    }
+}
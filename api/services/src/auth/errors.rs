@@ -4,6 +4,8 @@ use api_errors::ServiceError;
 pub enum AuthentificationError {
     IncorrectPassword,
     Argon2Error(argon2::password_hash::Error),
+    /// the hashing pool is full
+    Busy,
 }
 
 impl From<argon2::password_hash::Error> for AuthentificationError {
@@ -13,10 +15,16 @@ impl From<argon2::password_hash::Error> for AuthentificationError {
 }
 
 impl From<AuthentificationError> for ServiceError {
-    fn from(_error: AuthentificationError) -> Self {
-        ServiceError {
-            message: Some("Authentification failed".to_string()),
-            error_type: api_errors::ServiceErrorType::BadAuthentification,
+    fn from(error: AuthentificationError) -> Self {
+        match error {
+            AuthentificationError::Busy => ServiceError {
+                message: Some("Too many requests in progress, retry later".to_string()),
+                error_type: api_errors::ServiceErrorType::ServiceUnavailable,
+            },
+            _ => ServiceError {
+                message: Some("Authentification failed".to_string()),
+                error_type: api_errors::ServiceErrorType::BadAuthentification,
+            },
         }
     }
 }
@@ -0,0 +1,49 @@
+use std::fmt;
+
+use api_caches::login_attempts::LockoutScope;
+
+/// Security events of the authentication, logged on the `auth_events` target
+/// so that they can be routed to an alerting system.
+#[derive(Debug)]
+pub enum AuthEvent<'a> {
+    LockoutStarted {
+        email: &'a str,
+        ip: &'a str,
+        scope: &'a LockoutScope,
+        duration: i64,
+    },
+    AccountUnlocked {
+        email: &'a str,
+    },
+}
+
+impl fmt::Display for AuthEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthEvent::LockoutStarted {
+                email,
+                ip,
+                scope,
+                duration,
+            } => {
+                let scope = match scope {
+                    LockoutScope::Account => "account",
+                    LockoutScope::IpAccount(_) => "ip_account",
+                };
+                write!(
+                    f,
+                    "event=lockout_started email={} ip={} scope={} duration={}s",
+                    email, ip, scope, duration
+                )
+            }
+            AuthEvent::AccountUnlocked { email } => {
+                write!(f, "event=account_unlocked email={}", email)
+            }
+        }
+    }
+}
+
+/// Emit a security event.
+pub fn emit(event: AuthEvent) {
+    log::warn!(target: "auth_events", "{}", event);
+}
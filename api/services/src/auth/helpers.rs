@@ -2,6 +2,18 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2, PasswordHash, PasswordVerifier,
 };
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+use crate::{auth::errors::AuthentificationError, blocking_pool::BlockingPool};
+
+/// Hash of a random password, with the same parameters than the real ones.
+static DUMMY_PASSWORD_HASH: Lazy<String> =
+    Lazy::new(|| hash_password(&uuid::Uuid::new_v4().to_string()).unwrap());
+
+/// Threads hashing the passwords, out of the actix workers.
+static HASHING_POOL: Lazy<BlockingPool> =
+    Lazy::new(|| BlockingPool::new(&api_configs::CONFIG.hashing_info));
 
 /// Hash a password using Argon2
 ///
@@ -51,3 +63,73 @@ pub fn verify_password(
 
     Ok(true)
 }
+
+/// Hash a password on the hashing pool
+///
+/// # Returns
+///
+/// The hashed password, or `Busy` if too many passwords are already being hashed
+pub async fn hash_password_in_pool(password: String) -> Result<String, AuthentificationError> {
+    HASHING_POOL
+        .run(move || hash_password(&password))
+        .await
+        .map_err(|_| AuthentificationError::Busy)?
+}
+
+/// Verify a password against a hash on the hashing pool
+///
+/// # Returns
+///
+/// `true` if the password is correct, or `Busy` if too many passwords are already being verified
+pub async fn verify_password_in_pool(
+    password: String,
+    hash: String,
+) -> Result<bool, AuthentificationError> {
+    HASHING_POOL
+        .run(move || verify_password(&password, &PasswordHash::new(&hash)?))
+        .await
+        .map_err(|_| AuthentificationError::Busy)?
+}
+
+/// Hash to verify a password against when the user is unknown
+///
+/// Verifying against it takes as long as a real verification and never succeeds.
+pub fn dummy_password_hash() -> &'static str {
+    &DUMMY_PASSWORD_HASH
+}
+
+/// Hash a generated secret (API key, client secret) using SHA-256
+///
+/// These secrets are long random strings, a fast hash is enough and allows to find them by their hash.
+///
+/// # Arguments
+///
+/// * `secret` - The secret to hash
+///
+/// # Returns
+///
+/// The hexadecimal representation of the hash
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Verify a generated secret against its SHA-256 hash in constant time
+///
+/// # Arguments
+///
+/// * `secret` - The secret to verify
+/// * `hash` - The hexadecimal hash to verify against
+///
+/// # Returns
+///
+/// `true` if the secret matches the hash
+pub fn verify_secret(secret: &str, hash: &str) -> bool {
+    let computed = hash_secret(secret);
+
+    computed.len() == hash.len()
+        && computed
+            .bytes()
+            .zip(hash.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
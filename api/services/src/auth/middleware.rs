@@ -1,20 +1,60 @@
-use actix_web::{dev::ServiceRequest, Error, HttpMessage};
+use actix_web::{dev::ServiceRequest, web, Error, HttpMessage};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 
-use super::services::validate_token;
+use api_errors::{ServiceError, ServiceErrorType};
 
-/// Validate the token and insert the user id in the request extensions.
+use crate::api_keys::{ApiKeysService, API_KEY_PREFIX};
+
+use super::{services::validate_token, types::Principal};
+
+/// Validate the token and insert the principal in the request extensions.
+/// The token can either be a JWT (user or client) or an API key.
 /// Used in the authentication middleware.
 pub async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    match validate_token(&req, credentials.token()) {
-        Ok(token_data) => {
-            // we give in the request extension the user id for use it in middleware
-            req.extensions_mut().insert(token_data.claims.sub);
+    // the rate limiter may have already authenticated the request
+    if req.extensions().contains::<Principal>() {
+        return Ok(req);
+    }
+
+    match authenticate(&req, credentials.token()).await {
+        Ok(principal) => {
+            insert_principal(&req, principal);
             Ok(req)
         }
         Err(err) => Err((err.into(), req)),
     }
 }
+
+/// Resolve the principal behind a bearer token, a JWT (user or client) or an API key.
+pub async fn authenticate(req: &ServiceRequest, token: &str) -> Result<Principal, ServiceError> {
+    if token.starts_with(API_KEY_PREFIX) {
+        authenticate_api_key(req, token).await
+    } else {
+        validate_token(req, token).map(|token_data| Principal::from(token_data.claims))
+    }
+}
+
+/// Insert the principal in the request extensions.
+pub fn insert_principal(req: &ServiceRequest, principal: Principal) {
+    // we give in the request extension the user id for use it in middleware
+    if let Some(user_id) = principal.user_id() {
+        req.extensions_mut().insert(user_id);
+    }
+    req.extensions_mut().insert(principal);
+}
+
+async fn authenticate_api_key(req: &ServiceRequest, key: &str) -> Result<Principal, ServiceError> {
+    match req.app_data::<web::Data<ApiKeysService>>() {
+        Some(api_keys_service) => api_keys_service.authenticate(key).await,
+        None => {
+            log::error!("ApiKeysService not found, make sure it is set in your app data");
+            Err(ServiceError {
+                message: Some("Api keys are not supported".to_string()),
+                error_type: ServiceErrorType::BadAuthentification,
+            })
+        }
+    }
+}
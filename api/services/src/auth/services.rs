@@ -2,128 +2,308 @@ use std::sync::Arc;
 
 use actix_web::{dev::ServiceRequest, web};
 
-use api_caches::access_refresh_tokens::{AccessRefreshTokensCache, UserMetaData};
+use api_caches::{
+    access_refresh_tokens::{AccessRefreshTokensCache, UserMetaData},
+    login_attempts::{LockoutScope, LoginAttemptsCache},
+};
 use api_configs::config::Config;
 use api_db::{models::user::User, repository::UserRepository};
 use api_errors::{ServiceError, ServiceErrorType};
-use api_types::user::{InputUser, NewUser, RefreshableUser};
+use api_types::user::{InputUser, NewUser, PasswordReset, RefreshableUser};
 
-use argon2::PasswordHash;
 use jsonwebtoken::{
     decode, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
 };
 use time::{Duration, OffsetDateTime};
 
-use crate::oauth::fetch_google_user_info;
+use crate::{mailer::Mailer, oauth::fetch_google_user_info};
 
 use super::{
-    claims::TokenClaims,
+    claims::{PasswordResetClaims, TokenClaims, PASSWORD_RESET_AUDIENCE},
+    emails,
     errors::AuthentificationError,
-    helpers::{hash_password, verify_password},
+    events::{self, AuthEvent},
+    helpers::{dummy_password_hash, hash_password_in_pool, hash_secret, verify_password_in_pool},
     types::Tokens,
 };
 
+/// Lifetime of the token of a password reset link.
+pub const PASSWORD_RESET_TOKEN_MINUTES: i64 = 30;
+
 #[derive(Clone)]
-pub struct AuthService<U: UserRepository, C: AccessRefreshTokensCache> {
+pub struct AuthService<
+    U: UserRepository,
+    C: AccessRefreshTokensCache,
+    L: LoginAttemptsCache,
+    M: Mailer,
+> {
     users_repository: Arc<U>,
     access_refresh_tokens_cache: Arc<C>,
+    login_attempts_cache: Arc<L>,
+    mailer: Arc<M>,
 }
 
-impl<U: UserRepository, C: AccessRefreshTokensCache> AuthService<U, C> {
-    pub fn new(users_repository: Arc<U>, access_refresh_tokens_cache: Arc<C>) -> Self {
+impl<U: UserRepository, C: AccessRefreshTokensCache, L: LoginAttemptsCache, M: Mailer>
+    AuthService<U, C, L, M>
+{
+    pub fn new(
+        users_repository: Arc<U>,
+        access_refresh_tokens_cache: Arc<C>,
+        login_attempts_cache: Arc<L>,
+        mailer: Arc<M>,
+    ) -> Self {
         Self {
             users_repository,
             access_refresh_tokens_cache,
+            login_attempts_cache,
+            mailer,
         }
     }
 
+    /// Log a user in with his email and password
+    /// The failed attempts are counted per account and per ip and account, whether the account exists or not,
+    /// too many of them lock the login for a duration growing exponentially.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_json` - The credentials of the user
+    /// * `ip` - The ip of the client
+    /// * `config` - The configuration of the api
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Tokens`, or a `ServiceError` if the credentials are wrong or the login is locked
     pub async fn login(
         &self,
         user_json: InputUser,
+        ip: &str,
         config: &Config,
     ) -> Result<Tokens, ServiceError> {
-        let user = self
-            .users_repository
-            .get_user_by_email(&user_json.email)
-            .await;
+        let email = normalize_email(&user_json.email);
 
-        if user.is_err() {
-            // si il y a une erreur avec la base de données
+        if self
+            .login_attempts_cache
+            .locked_until(&email, ip)
+            .await?
+            .is_some()
+        {
             return Err(ServiceError {
-                message: Some("Authentification failed".to_string()),
-                error_type: ServiceErrorType::BadAuthentification,
+                message: Some("Too many failed attempts, try again later".to_string()),
+                error_type: ServiceErrorType::RateLimitExceeded,
             });
         }
 
-        let user = user.unwrap();
-
-        if user.password.is_none() {
-            return Err(ServiceError {
-                message: Some("Authentification failed (consider using oauth)".to_string()),
-                error_type: ServiceErrorType::BadAuthentification,
-            });
+        match self.check_credentials(&email, &user_json.password).await {
+            Ok(user) => {
+                self.login_attempts_cache.clear_failures(&email).await?;
+                self.generate_and_save_tokens(&user, config).await
+            }
+            Err(err) => {
+                if err.error_type == ServiceErrorType::BadAuthentification {
+                    self.record_failed_attempt(&email, ip, config).await?;
+                }
+                Err(err)
+            }
         }
+    }
 
-        let password = user.password.as_ref().unwrap();
+    /// Remove every lockout of an account, to call once the user proved his identity
+    /// (password reset, or `DELETE /v1/admin/lockouts/{email}` by an administrator)
+    pub async fn unlock_account(&self, email: &str) -> Result<(), ServiceError> {
+        let email = normalize_email(email);
 
-        let parsed_hash = PasswordHash::new(password)
-            .map_err(AuthentificationError::from)
-            .map_err(ServiceError::from)?;
+        self.login_attempts_cache.unlock(&email).await?;
+        events::emit(AuthEvent::AccountUnlocked { email: &email });
+
+        Ok(())
+    }
+
+    /// Send a password reset link to the owner of an account
+    /// The response is the same whether the account exists or not.
+    pub async fn request_password_reset(
+        &self,
+        email: &str,
+        config: &Config,
+    ) -> Result<(), ServiceError> {
+        let email = normalize_email(email);
+
+        match self.users_repository.get_user_by_email(&email).await {
+            Ok(user) => {
+                let token = create_password_reset_token(config, &user)?;
+                self.mailer
+                    .send(emails::password_reset(&user, &token))
+                    .await
+            }
+            Err(err) if err.error_type == ServiceErrorType::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
 
-        if let Err(err) = verify_password(&user_json.password, &parsed_hash) {
-            return Err(ServiceError::from(err));
+    /// Set a new password with the token of a reset link
+    /// The owner proved his identity by receiving the email, the lockouts of the account are removed.
+    pub async fn reset_password(
+        &self,
+        reset: PasswordReset,
+        config: &Config,
+    ) -> Result<(), ServiceError> {
+        let invalid_token = || ServiceError {
+            message: Some("Invalid or expired reset token".to_string()),
+            error_type: ServiceErrorType::BadAuthentification,
+        };
+
+        let claims =
+            decode_password_reset_token(config, &reset.token).map_err(|_| invalid_token())?;
+        let user = match self.users_repository.get(claims.sub).await {
+            Ok(user) => user,
+            Err(err) if err.error_type == ServiceErrorType::NotFound => return Err(invalid_token()),
+            Err(err) => return Err(err),
+        };
+        // le token n'est plus valable une fois le mot de passe changé
+        if password_fingerprint(&user) != claims.pwd {
+            return Err(invalid_token());
         }
 
-        self.generate_and_save_tokens(&user, config).await
+        let hash = hash_password_in_pool(reset.password)
+            .await
+            .map_err(ServiceError::from)?;
+        let mut updated_user = user.clone();
+        updated_user.password = Some(hash);
+        self.users_repository.update(user.id, &updated_user).await?;
+
+        self.unlock_account(&user.email).await
     }
 
-    pub async fn register(
+    async fn record_failed_attempt(
         &self,
-        user_json: InputUser,
+        email: &str,
+        ip: &str,
         config: &Config,
-    ) -> Result<Tokens, ServiceError> {
-        let created_user = match self
+    ) -> Result<(), ServiceError> {
+        let lockout_info = &config.lockout_info;
+
+        let attempts = self
+            .login_attempts_cache
+            .record_failure(email, ip, lockout_info.attempts_window)
+            .await?;
+
+        let mut scopes = vec![];
+        if attempts.ip_account >= lockout_info.ip_account_threshold {
+            scopes.push(LockoutScope::IpAccount(ip.to_string()));
+        }
+        if attempts.account >= lockout_info.account_threshold {
+            scopes.push(LockoutScope::Account);
+        }
+
+        for scope in scopes {
+            let duration = self
+                .login_attempts_cache
+                .start_lockout(
+                    email,
+                    &scope,
+                    lockout_info.base_duration,
+                    lockout_info.max_duration,
+                )
+                .await?;
+
+            events::emit(AuthEvent::LockoutStarted {
+                email,
+                ip,
+                scope: &scope,
+                duration,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check the credentials of a user
+    /// An unknown email or an account without password goes through the same password
+    /// verification than a known one, so that the response time does not reveal the existence of the account.
+    /// A failure of the database is not a wrong password, it is returned as a `ServiceUnavailable`
+    /// error which does not count as a failed attempt.
+    async fn check_credentials(&self, email: &str, password: &str) -> Result<User, ServiceError> {
+        let user = match self.users_repository.get_user_by_email(email).await {
+            Ok(user) => Some(user),
+            Err(err) if err.error_type == ServiceErrorType::NotFound => None,
+            Err(err) => {
+                log::error!("Credentials not checked, the database failed: {}", err);
+                return Err(ServiceError {
+                    message: Some("Service temporarily unavailable".to_string()),
+                    error_type: ServiceErrorType::ServiceUnavailable,
+                });
+            }
+        };
+
+        let password_hash = user
+            .as_ref()
+            .and_then(|user| user.password.as_deref())
+            .unwrap_or(dummy_password_hash())
+            .to_string();
+
+        // verified out of the actix workers, a full pool refuses the login
+        let password_matches =
+            match verify_password_in_pool(password.to_string(), password_hash).await {
+                Ok(matches) => matches,
+                Err(AuthentificationError::Busy) => {
+                    return Err(ServiceError::from(AuthentificationError::Busy))
+                }
+                Err(_) => false,
+            };
+        let has_password = user.as_ref().is_some_and(|user| user.password.is_some());
+
+        match user {
+            Some(user) if password_matches && has_password => Ok(user),
+            _ => Err(ServiceError::from(AuthentificationError::IncorrectPassword)),
+        }
+    }
+
+    /// Register a new user with his email and password
+    /// The password is added to an existing Google account without one.
+    /// The response is the same whether the email is already used or not,
+    /// the owner of the email is informed by an email instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_json` - The credentials of the new user
+    ///
+    /// # Returns
+    ///
+    /// An empty `Result`, or a `ServiceError` if the database or the mailer fails
+    pub async fn register(&self, user_json: InputUser) -> Result<(), ServiceError> {
+        // the password is hashed on every path to keep the same response time
+        let hash = hash_password_in_pool(user_json.password.clone())
+            .await
+            .map_err(ServiceError::from)?;
+
+        match self
             .users_repository
             .get_user_by_email(&user_json.email)
             .await
         {
+            // a Google account without password can add one, its owner is told by email
+            Ok(db_user) if db_user.password.is_none() && db_user.google_id.is_some() => {
+                let mut updated_user = db_user.clone();
+                updated_user.password = Some(hash);
+                let updated_user = self
+                    .users_repository
+                    .update(db_user.id, &updated_user)
+                    .await?;
+
+                self.mailer
+                    .send(emails::password_added(&updated_user))
+                    .await
+            }
             Ok(db_user) => {
-                if db_user.password.is_some() {
-                    return Err(ServiceError {
-                        message: Some("User already exists".to_string()),
-                        error_type: ServiceErrorType::BadAuthentification,
-                    });
-                }
-
-                // Si l'utilisateur a un compte Google mais pas de mot de passe
-                if db_user.google_id.is_some() {
-                    let hash = hash_password(&user_json.password).map_err(ServiceError::from)?;
-
-                    let mut cloned_db_user = db_user.clone();
-                    cloned_db_user.password = Some(hash);
-
-                    self.users_repository
-                        .update(db_user.id, &cloned_db_user)
-                        .await?
-                } else {
-                    return Err(ServiceError {
-                        message: Some("Invalid user state".to_string()),
-                        error_type: ServiceErrorType::BadAuthentification,
-                    });
-                }
+                self.mailer
+                    .send(emails::account_already_exists(&db_user))
+                    .await
             }
-            Err(e) => {
-                if e.error_type == ServiceErrorType::DatabaseError {
-                    return Err(ServiceError {
-                        message: Some("Error getting user".to_string()),
-                        error_type: ServiceErrorType::InternalServerError,
-                    });
-                }
-
+            // toute autre erreur ne dit pas si l'utilisateur existe
+            Err(e) if e.error_type != ServiceErrorType::NotFound => Err(e),
+            Err(_) => {
                 // Créer un nouvel utilisateur
-                let hash = hash_password(&user_json.password).map_err(ServiceError::from)?;
-
-                self.users_repository
+                let created_user = self
+                    .users_repository
                     .create(&NewUser {
                         pseudo: generate_random_pseudo(),
                         first_name: None,
@@ -132,11 +312,11 @@ impl<U: UserRepository, C: AccessRefreshTokensCache> AuthService<U, C> {
                         password: Some(hash),
                         google_id: None,
                     })
-                    .await?
-            }
-        };
+                    .await?;
 
-        self.generate_and_save_tokens(&created_user, config).await
+                self.mailer.send(emails::welcome(&created_user)).await
+            }
+        }
     }
 
     pub async fn handle_oauth_connection(
@@ -162,8 +342,13 @@ impl<U: UserRepository, C: AccessRefreshTokensCache> AuthService<U, C> {
             .await
         {
             if user.google_id.is_none() {
+                // the reason is only given to the owner of the account
+                self.mailer
+                    .send(emails::google_account_not_linked(&user))
+                    .await?;
+
                 return Err(ServiceError {
-                    message: Some("An account already exist with this email. Please use the default auth way for link your google account.".to_string()),
+                    message: Some("Authentification failed".to_string()),
                     error_type: ServiceErrorType::BadAuthentification,
                 });
             }
@@ -281,6 +466,95 @@ pub fn create_valid_token(config: &Config, user_id: i32) -> Result<String, Servi
     }
 }
 
+/// Create an access token for a user limited to some scopes (authorization code grant).
+pub fn create_scoped_token(
+    config: &Config,
+    user_id: i32,
+    scope: &str,
+) -> Result<String, ServiceError> {
+    let iat = OffsetDateTime::now_utc();
+    let exp = iat + Duration::minutes(config.jwt_expired_in);
+
+    let claims = TokenClaims::new_scoped(user_id, scope.to_string(), iat, exp);
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
+    )
+    .map_err(|_| ServiceError {
+        message: Some("encode failed".to_string()),
+        error_type: ServiceErrorType::InternalServerError,
+    })
+}
+
+/// Create an access token for a registered OAuth client (client credentials grant).
+pub fn create_client_token(
+    config: &Config,
+    client_id: i32,
+    scope: &str,
+) -> Result<String, ServiceError> {
+    let iat = OffsetDateTime::now_utc();
+    let exp = iat + Duration::minutes(config.jwt_expired_in);
+
+    let claims = TokenClaims::new_client(client_id, scope.to_string(), iat, exp);
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
+    )
+    .map_err(|_| ServiceError {
+        message: Some("encode failed".to_string()),
+        error_type: ServiceErrorType::InternalServerError,
+    })
+}
+
+/// Create the token of a password reset link, valid for `PASSWORD_RESET_TOKEN_MINUTES` minutes.
+pub fn create_password_reset_token(config: &Config, user: &User) -> Result<String, ServiceError> {
+    let iat = OffsetDateTime::now_utc();
+    let exp = iat + Duration::minutes(PASSWORD_RESET_TOKEN_MINUTES);
+
+    let claims = PasswordResetClaims {
+        sub: user.id,
+        aud: PASSWORD_RESET_AUDIENCE.to_string(),
+        pwd: password_fingerprint(user),
+        iat,
+        exp,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
+    )
+    .map_err(|_| ServiceError {
+        message: Some("encode failed".to_string()),
+        error_type: ServiceErrorType::InternalServerError,
+    })
+}
+
+fn decode_password_reset_token(
+    config: &Config,
+    token: &str,
+) -> Result<PasswordResetClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 1;
+    validation.set_audience(&[PASSWORD_RESET_AUDIENCE]);
+
+    decode::<PasswordResetClaims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_ref()),
+        &validation,
+    )
+    .map(|token_data| token_data.claims)
+}
+
+/// Changes with the password, an account without password has a fingerprint too.
+fn password_fingerprint(user: &User) -> String {
+    hash_secret(user.password.as_deref().unwrap_or_default())
+}
+
 pub fn decode_token(
     config: web::Data<Config>,
     token: &str,
@@ -307,6 +581,11 @@ pub fn generate_refresh_token() -> String {
         .collect()
 }
 
+/// Emails are case insensitive, the attempts of `A@x.com` and `a@x.com` are counted together
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
 pub fn generate_random_pseudo() -> String {
     format!("Newbie{}", uuid::Uuid::new_v4())
 }
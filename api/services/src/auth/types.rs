@@ -1,8 +1,98 @@
 use serde::{Deserialize, Serialize};
 
+use api_errors::{ServiceError, ServiceErrorType};
+
+use super::claims::{SubjectType, TokenClaims};
+
 /// Default struct for tokens in JWT authentication.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Tokens {
     pub access_token: String,
     pub refresh_token: String,
 }
+
+/// Authenticated entity behind a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject {
+    /// Id of a user.
+    User(i32),
+    /// Id of a registered OAuth client (machine identity).
+    Client(i32),
+}
+
+/// Identity resolved by the authentication middleware and stored in the request extensions.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub subject: Subject,
+    /// `None` when the request comes from an interactive login, which has every right.
+    pub scopes: Option<Vec<String>>,
+}
+
+impl From<TokenClaims> for Principal {
+    fn from(claims: TokenClaims) -> Self {
+        let split = |scope: String| -> Vec<String> {
+            scope.split_whitespace().map(str::to_string).collect()
+        };
+
+        match claims.sub_type {
+            // a user token only carries scopes when it was issued to a relying party
+            SubjectType::User => Principal {
+                subject: Subject::User(claims.sub),
+                scopes: claims.scope.map(split),
+            },
+            SubjectType::Client => Principal {
+                subject: Subject::Client(claims.sub),
+                scopes: Some(split(claims.scope.unwrap_or_default())),
+            },
+        }
+    }
+}
+
+impl Principal {
+    /// Id of the user behind the request, if the subject is a user.
+    pub fn user_id(&self) -> Option<i32> {
+        match self.subject {
+            Subject::User(id) => Some(id),
+            Subject::Client(_) => None,
+        }
+    }
+
+    /// Return the id of the user or a `Forbidden` error if the subject is a client.
+    pub fn require_user(&self) -> Result<i32, ServiceError> {
+        self.user_id().ok_or(ServiceError {
+            message: Some("This action is reserved to users".to_string()),
+            error_type: ServiceErrorType::Forbidden,
+        })
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.iter().any(|s| s == scope),
+            None => true,
+        }
+    }
+
+    /// Return a `Forbidden` error if the principal was not granted the scope.
+    pub fn require_scope(&self, scope: &str) -> Result<(), ServiceError> {
+        if self.has_scope(scope) {
+            return Ok(());
+        }
+
+        Err(ServiceError {
+            message: Some(format!("Missing scope: {}", scope)),
+            error_type: ServiceErrorType::Forbidden,
+        })
+    }
+
+    /// Return a `Forbidden` error if the principal does not come from an interactive login.
+    pub fn require_interactive(&self) -> Result<(), ServiceError> {
+        if self.scopes.is_none() {
+            return Ok(());
+        }
+
+        Err(ServiceError {
+            message: Some("This action requires an interactive login".to_string()),
+            error_type: ServiceErrorType::Forbidden,
+        })
+    }
+}
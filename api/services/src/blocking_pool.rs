@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::Semaphore;
+
+use api_configs::config::HashingInfo;
+
+/// The pool and its queue are full.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PoolBusy;
+
+/// Runs CPU heavy tasks out of the actix workers, on the blocking threads of tokio.
+/// At most `workers` tasks run at the same time and `queue_limit` wait for a worker,
+/// the next ones are refused instead of piling up.
+pub struct BlockingPool {
+    workers: Semaphore,
+    /// running and waiting tasks
+    pending: AtomicUsize,
+    capacity: usize,
+}
+
+/// Frees the place of a task in the pool, even when its future is dropped.
+struct PendingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl BlockingPool {
+    pub fn new(hashing_info: &HashingInfo) -> Self {
+        BlockingPool {
+            workers: Semaphore::new(hashing_info.workers),
+            pending: AtomicUsize::new(0),
+            capacity: hashing_info.workers + hashing_info.queue_limit,
+        }
+    }
+
+    /// Run a task on the pool, or return `PoolBusy` right away if the queue is full.
+    pub async fn run<T, F>(&self, task: F) -> Result<T, PoolBusy>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if self.pending.fetch_add(1, Ordering::SeqCst) >= self.capacity {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(PoolBusy);
+        }
+        let _pending = PendingGuard(&self.pending);
+
+        // le sémaphore n'est jamais fermé
+        let _worker = self.workers.acquire().await.map_err(|_| PoolBusy)?;
+
+        match tokio::task::spawn_blocking(task).await {
+            Ok(result) => Ok(result),
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use super::*;
+    #[allow(unused_imports)] // bug pas important avec l'éditeur
+    use std::{sync::Arc, time::Duration};
+
+    #[actix_rt::test]
+    async fn test_pool_refuses_tasks_beyond_its_queue() {
+        let pool = Arc::new(BlockingPool::new(&HashingInfo {
+            workers: 1,
+            queue_limit: 1,
+        }));
+
+        let slow = || std::thread::sleep(Duration::from_millis(300));
+        let running = actix_rt::spawn({
+            let pool = Arc::clone(&pool);
+            async move { pool.run(slow).await }
+        });
+        let queued = actix_rt::spawn({
+            let pool = Arc::clone(&pool);
+            async move { pool.run(slow).await }
+        });
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(pool.run(|| 1).await, Err(PoolBusy));
+
+        assert_eq!(running.await.unwrap(), Ok(()));
+        assert_eq!(queued.await.unwrap(), Ok(()));
+        assert_eq!(pool.run(|| 1).await, Ok(1));
+    }
+}
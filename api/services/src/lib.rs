@@ -1,3 +1,8 @@
+pub mod api_keys;
 pub mod auth;
+pub mod blocking_pool;
+pub mod mailer;
 pub mod oauth;
+pub mod oauth_clients;
+pub mod oidc;
 pub mod users;
@@ -0,0 +1,42 @@
+use api_errors::ServiceError;
+
+/// An email sent to a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Trait for sending emails to the users.
+#[async_trait::async_trait]
+pub trait Mailer: Clone + Send + Sync + 'static {
+    /// Sends an email.
+    ///
+    /// # Arguments
+    /// * `email` - The email to send.
+    ///
+    /// # Returns
+    /// A `Result` indicating the success or failure of the operation.
+    async fn send(&self, email: Email) -> Result<(), ServiceError>;
+}
+
+/// Implementation of `Mailer` writing the emails in the logs.
+/// Used as long as no SMTP transport is configured.
+#[derive(Clone, Default)]
+pub struct LogMailer;
+
+#[async_trait::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), ServiceError> {
+        log::info!(
+            target: "mailer",
+            "to={} subject={:?}\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt, sync::Arc};
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+
+use api_configs::config::Config;
+use api_db::{
+    connection::Pool, models::oauth_client::OAuthClient,
+    repositories::oauth_clients_repository::OAuthClientsRepository,
+    repository::OAuthClientRepository, repository::Repository,
+};
+use api_errors::{ServiceError, ServiceErrorType};
+use api_types::{
+    oauth_client::{
+        AccessTokenResponse, ClientRegistration, NewOAuthClient, RegisteredOAuthClient,
+    },
+    scopes,
+};
+
+use crate::auth::{
+    helpers::{hash_secret, verify_secret},
+    services::create_client_token,
+};
+
+/// Grant used for service-to-service calls.
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+/// Grant used by the relying parties of the OpenID Connect provider.
+pub const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+
+/// Errors of the token endpoint, rendered as described in RFC 6749 section 5.2.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OAuthTokenError {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnsupportedGrantType,
+    InvalidScope,
+    ServerError,
+}
+
+impl OAuthTokenError {
+    fn code(&self) -> &'static str {
+        match self {
+            OAuthTokenError::InvalidRequest => "invalid_request",
+            OAuthTokenError::InvalidClient => "invalid_client",
+            OAuthTokenError::InvalidGrant => "invalid_grant",
+            OAuthTokenError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthTokenError::InvalidScope => "invalid_scope",
+            OAuthTokenError::ServerError => "server_error",
+        }
+    }
+}
+
+impl fmt::Display for OAuthTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl ResponseError for OAuthTokenError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OAuthTokenError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthTokenError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+
+        if *self == OAuthTokenError::InvalidClient {
+            response.insert_header(("WWW-Authenticate", "Basic"));
+        }
+
+        response.json(serde_json::json!({ "error": self.code() }))
+    }
+}
+
+impl From<ServiceError> for OAuthTokenError {
+    fn from(_error: ServiceError) -> Self {
+        OAuthTokenError::ServerError
+    }
+}
+
+#[derive(Clone)]
+pub struct OAuthClientsService {
+    oauth_clients_repository: OAuthClientsRepository,
+}
+
+impl OAuthClientsService {
+    pub fn new(conn: Pool) -> Self {
+        Self {
+            oauth_clients_repository: OAuthClientsRepository::new(Arc::clone(&conn)),
+        }
+    }
+
+    /// Register a new OAuth client
+    /// The secret is returned only once, only its hash is stored
+    ///
+    /// # Arguments
+    ///
+    /// * `registration` - The name, allowed scopes and redirect uris of the client
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the credentials of the client, or a `ServiceError` if a scope is unknown
+    pub async fn register_client(
+        &self,
+        registration: ClientRegistration,
+    ) -> Result<RegisteredOAuthClient, ServiceError> {
+        if !scopes::are_valid_for_client(&registration.scopes) {
+            return Err(ServiceError {
+                message: Some("Unknown scope".to_string()),
+                error_type: ServiceErrorType::UnprocessableEntityError,
+            });
+        }
+
+        let client_id = uuid::Uuid::new_v4().to_string();
+        let client_secret = generate_client_secret();
+
+        self.oauth_clients_repository
+            .create(&NewOAuthClient {
+                client_id: client_id.clone(),
+                secret_hash: hash_secret(&client_secret),
+                name: registration.name,
+                scopes: registration.scopes,
+                redirect_uris: registration.redirect_uris,
+                is_public: registration.is_public,
+            })
+            .await?;
+
+        Ok(RegisteredOAuthClient {
+            client_id,
+            client_secret,
+        })
+    }
+
+    /// Issue an access token to a client with the client credentials grant
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The public identifier of the client
+    /// * `client_secret` - The secret of the client
+    /// * `scope` - The space separated scopes requested, every allowed scope when absent
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AccessTokenResponse`, or an `OAuthTokenError` as defined in RFC 6749
+    pub async fn client_credentials(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        scope: Option<&str>,
+        config: &Config,
+    ) -> Result<AccessTokenResponse, OAuthTokenError> {
+        let client = self
+            .authenticate_client(client_id, Some(client_secret))
+            .await?;
+
+        // a public client cannot keep its secret, so it cannot act on its own behalf
+        if client.is_public {
+            return Err(OAuthTokenError::InvalidClient);
+        }
+
+        let granted_scopes: Vec<String> = match scope {
+            Some(scope) => scope.split_whitespace().map(str::to_string).collect(),
+            None => client.scopes.clone(),
+        };
+
+        if granted_scopes
+            .iter()
+            .any(|scope| !client.scopes.contains(scope))
+        {
+            return Err(OAuthTokenError::InvalidScope);
+        }
+
+        let scope = granted_scopes.join(" ");
+
+        Ok(AccessTokenResponse {
+            access_token: create_client_token(config, client.id, &scope)?,
+            token_type: "Bearer".to_string(),
+            expires_in: config.jwt_expired_in * 60,
+            scope,
+            id_token: None,
+        })
+    }
+
+    /// Authenticate a client on the token endpoint
+    /// The secret is mandatory for every client except the public ones
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `OAuthClient`, or `OAuthTokenError::InvalidClient`
+    pub async fn authenticate_client(
+        &self,
+        client_id: &str,
+        client_secret: Option<&str>,
+    ) -> Result<OAuthClient, OAuthTokenError> {
+        let client = self
+            .oauth_clients_repository
+            .get_oauth_client_by_client_id(client_id)
+            .await
+            .map_err(|_| OAuthTokenError::InvalidClient)?;
+
+        match client_secret {
+            Some(client_secret) if verify_secret(client_secret, &client.secret_hash) => Ok(client),
+            None if client.is_public => Ok(client),
+            _ => Err(OAuthTokenError::InvalidClient),
+        }
+    }
+}
+
+pub fn generate_client_secret() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
@@ -0,0 +1,3 @@
+pub mod claims;
+pub mod keys;
+pub mod services;
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use api_db::models::user::User;
+use api_types::scopes;
+
+/// Standard claims about the user (OpenID Connect Core section 5.1).
+/// Only the claims of the consented scopes are filled.
+#[derive(Deserialize, Serialize)]
+pub struct UserClaims {
+    pub sub: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl UserClaims {
+    pub fn new(user: &User, granted_scopes: &[String]) -> Self {
+        let has_scope = |scope: &str| granted_scopes.iter().any(|s| s == scope);
+        let profile = has_scope(scopes::PROFILE);
+
+        Self {
+            sub: user.id.to_string(),
+            preferred_username: profile.then(|| user.pseudo.clone()),
+            given_name: user.first_name.clone().filter(|_| profile),
+            family_name: user.last_name.clone().filter(|_| profile),
+            email: has_scope(scopes::EMAIL).then(|| user.email.clone()),
+        }
+    }
+}
+
+/// Claims of the ID token (OpenID Connect Core section 2).
+#[derive(Deserialize, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+
+    #[serde(flatten)]
+    pub user: UserClaims,
+}
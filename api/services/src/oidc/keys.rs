@@ -0,0 +1,104 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::EncodingKey;
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey},
+    pkcs8::DecodePrivateKey,
+    traits::PublicKeyParts,
+    RsaPrivateKey,
+};
+use sha2::{Digest, Sha256};
+
+use api_configs::config::Config;
+use api_errors::{ServiceError, ServiceErrorType};
+
+/// RSA key signing the ID tokens (RS256), its public part is published in the JWKS.
+#[derive(Clone)]
+pub struct OidcSigningKey {
+    kid: String,
+    encoding_key: EncodingKey,
+    n: String,
+    e: String,
+}
+
+impl OidcSigningKey {
+    /// Load the key from a PEM, either PKCS#8 (`BEGIN PRIVATE KEY`) or PKCS#1 (`BEGIN RSA PRIVATE KEY`)
+    pub fn from_pem(pem: &str) -> Result<Self, ServiceError> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|err| ServiceError {
+                message: Some(format!("Invalid OIDC private key: {}", err)),
+                error_type: ServiceErrorType::InternalServerError,
+            })?;
+
+        Self::from_private_key(&private_key)
+    }
+
+    /// Generate an ephemeral key, the ID tokens it signs can't be verified after a restart
+    pub fn generate() -> Result<Self, ServiceError> {
+        let private_key =
+            RsaPrivateKey::new(&mut rand::thread_rng(), 2048).map_err(|err| ServiceError {
+                message: Some(format!("Failed to generate OIDC private key: {}", err)),
+                error_type: ServiceErrorType::InternalServerError,
+            })?;
+
+        Self::from_private_key(&private_key)
+    }
+
+    /// Load the key configured with `OIDC_PRIVATE_KEY_PATH`, or generate one
+    pub fn from_config(config: &Config) -> Result<Self, ServiceError> {
+        match &config.oidc_info.private_key_path {
+            Some(path) => {
+                let pem = std::fs::read_to_string(path).map_err(|err| ServiceError {
+                    message: Some(format!("Failed to read {}: {}", path, err)),
+                    error_type: ServiceErrorType::InternalServerError,
+                })?;
+
+                Self::from_pem(&pem)
+            }
+            None => {
+                log::warn!("OIDC_PRIVATE_KEY_PATH is not set, an ephemeral key is generated");
+                Self::generate()
+            }
+        }
+    }
+
+    fn from_private_key(private_key: &RsaPrivateKey) -> Result<Self, ServiceError> {
+        let der = private_key.to_pkcs1_der().map_err(|err| ServiceError {
+            message: Some(format!("Invalid OIDC private key: {}", err)),
+            error_type: ServiceErrorType::InternalServerError,
+        })?;
+
+        let n = private_key.n().to_bytes_be();
+        let e = private_key.e().to_bytes_be();
+
+        Ok(Self {
+            // the key id only has to change with the key, a hash of the modulus is enough
+            kid: URL_SAFE_NO_PAD.encode(&Sha256::digest(&n)[..12]),
+            encoding_key: EncodingKey::from_rsa_der(der.as_bytes()),
+            n: URL_SAFE_NO_PAD.encode(n),
+            e: URL_SAFE_NO_PAD.encode(e),
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    /// JSON Web Key Set (RFC 7517) published on the `jwks_uri`
+    pub fn jwks(&self) -> serde_json::Value {
+        serde_json::json!({
+            "keys": [{
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": self.kid,
+                "n": self.n,
+                "e": self.e,
+            }]
+        })
+    }
+}
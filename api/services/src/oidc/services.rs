@@ -0,0 +1,384 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, Algorithm, Header};
+use oauth2::url::Url;
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+
+use api_configs::config::Config;
+use api_db::{
+    connection::Pool,
+    models::{oauth_client::OAuthClient, user::User},
+    repositories::{
+        oauth_authorization_codes_repository::OAuthAuthorizationCodesRepository,
+        oauth_clients_repository::OAuthClientsRepository,
+        oauth_consents_repository::OAuthConsentsRepository, users_repository::UsersRepository,
+    },
+    repository::{
+        OAuthAuthorizationCodeRepository, OAuthClientRepository, OAuthConsentRepository, Repository,
+    },
+};
+use api_errors::{ServiceError, ServiceErrorType};
+use api_types::{
+    oauth_client::{AccessTokenResponse, TokenRequest},
+    oidc::{
+        AuthorizationDecision, AuthorizationRedirect, AuthorizationRequest, ConsentRequired,
+        NewOAuthAuthorizationCode, NewOAuthConsent,
+    },
+    scopes,
+};
+
+use crate::{
+    auth::{
+        helpers::{hash_secret, verify_secret},
+        services::create_scoped_token,
+    },
+    oauth_clients::{OAuthClientsService, OAuthTokenError},
+};
+
+use super::{
+    claims::{IdTokenClaims, UserClaims},
+    keys::OidcSigningKey,
+};
+
+/// Lifetime of an authorization code in seconds, RFC 6749 recommends 10 minutes at most.
+const AUTHORIZATION_CODE_TTL: i64 = 60;
+
+/// Answer of the authorization endpoint.
+pub enum AuthorizationOutcome {
+    /// The user agent must be redirected to the client, with a code or an error.
+    Redirect(AuthorizationRedirect),
+    /// The user has to consent to the scopes before a code is issued.
+    ConsentRequired(ConsentRequired),
+}
+
+#[derive(Clone)]
+pub struct OidcService {
+    oauth_clients_repository: OAuthClientsRepository,
+    oauth_consents_repository: OAuthConsentsRepository,
+    oauth_authorization_codes_repository: OAuthAuthorizationCodesRepository,
+    users_repository: UsersRepository,
+    oauth_clients_service: OAuthClientsService,
+    signing_key: Arc<OidcSigningKey>,
+}
+
+impl OidcService {
+    pub fn new(conn: Pool, signing_key: OidcSigningKey) -> Self {
+        Self {
+            oauth_clients_repository: OAuthClientsRepository::new(Arc::clone(&conn)),
+            oauth_consents_repository: OAuthConsentsRepository::new(Arc::clone(&conn)),
+            oauth_authorization_codes_repository: OAuthAuthorizationCodesRepository::new(
+                Arc::clone(&conn),
+            ),
+            users_repository: UsersRepository::new(Arc::clone(&conn)),
+            oauth_clients_service: OAuthClientsService::new(Arc::clone(&conn)),
+            signing_key: Arc::new(signing_key),
+        }
+    }
+
+    /// Handle an authentication request for a logged in user (authorization code flow with PKCE)
+    /// An unknown client or redirect uri is an error, the user agent must not be redirected.
+    /// Every other error is sent back to the client in the redirect uri.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The id of the logged in user
+    /// * `decision` - The authentication request and the answer of the user to the consent screen
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AuthorizationOutcome`, or a `ServiceError` if the client or the redirect uri is invalid
+    pub async fn authorize(
+        &self,
+        user_id: i32,
+        decision: AuthorizationDecision,
+    ) -> Result<AuthorizationOutcome, ServiceError> {
+        let request = decision.request;
+
+        let client = self
+            .oauth_clients_repository
+            .get_oauth_client_by_client_id(&request.client_id)
+            .await
+            .map_err(|_| ServiceError {
+                message: Some("Unknown client".to_string()),
+                error_type: ServiceErrorType::BadDeserialization,
+            })?;
+
+        if !client.redirect_uris.contains(&request.redirect_uri) {
+            return Err(ServiceError {
+                message: Some("Invalid redirect uri".to_string()),
+                error_type: ServiceErrorType::BadDeserialization,
+            });
+        }
+
+        if request.response_type != "code" {
+            return redirect_with_error(&request, "unsupported_response_type");
+        }
+
+        let requested_scopes: Vec<String> = request
+            .scope
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+
+        if !requested_scopes.iter().any(|scope| scope == scopes::OPENID)
+            || requested_scopes
+                .iter()
+                .any(|scope| !client.scopes.contains(scope))
+        {
+            return redirect_with_error(&request, "invalid_scope");
+        }
+
+        // PKCE is mandatory for every client, only with the S256 method
+        if request.code_challenge.is_none()
+            || request.code_challenge_method.as_deref() != Some("S256")
+        {
+            return redirect_with_error(&request, "invalid_request");
+        }
+
+        match decision.approved {
+            Some(false) => redirect_with_error(&request, "access_denied"),
+            Some(true) => {
+                self.oauth_consents_repository
+                    .upsert_consent(&NewOAuthConsent {
+                        user_id,
+                        client_id: client.id,
+                        scopes: requested_scopes.clone(),
+                    })
+                    .await?;
+
+                self.issue_code(user_id, &client, &request, requested_scopes)
+                    .await
+            }
+            None => {
+                let consent = self
+                    .oauth_consents_repository
+                    .get_consent(user_id, client.id)
+                    .await?;
+
+                if consent.is_some_and(|consent| consent.covers(&requested_scopes)) {
+                    return self
+                        .issue_code(user_id, &client, &request, requested_scopes)
+                        .await;
+                }
+
+                Ok(AuthorizationOutcome::ConsentRequired(ConsentRequired {
+                    consent_required: true,
+                    client_name: client.name,
+                    scopes: requested_scopes,
+                }))
+            }
+        }
+    }
+
+    /// Exchange an authorization code for an access token and an ID token
+    ///
+    /// # Arguments
+    ///
+    /// * `form` - The form of the token endpoint
+    /// * `client_id` - The public identifier of the client
+    /// * `client_secret` - The secret of the client, absent for a public client
+    /// * `config` - The configuration of the api
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AccessTokenResponse`, or an `OAuthTokenError` as defined in RFC 6749
+    pub async fn exchange_code(
+        &self,
+        form: &TokenRequest,
+        client_id: &str,
+        client_secret: Option<&str>,
+        config: &Config,
+    ) -> Result<AccessTokenResponse, OAuthTokenError> {
+        let client = self
+            .oauth_clients_service
+            .authenticate_client(client_id, client_secret)
+            .await?;
+
+        let (Some(code), Some(redirect_uri), Some(code_verifier)) = (
+            form.code.as_deref(),
+            form.redirect_uri.as_deref(),
+            form.code_verifier.as_deref(),
+        ) else {
+            return Err(OAuthTokenError::InvalidRequest);
+        };
+
+        let authorization_code = self
+            .oauth_authorization_codes_repository
+            .consume_code(&hash_secret(code))
+            .await?
+            .ok_or(OAuthTokenError::InvalidGrant)?;
+
+        if authorization_code.expires_at < chrono::Utc::now().naive_utc()
+            || authorization_code.client_id != client.id
+            || authorization_code.redirect_uri != redirect_uri
+            || !verify_code_challenge(code_verifier, &authorization_code.code_challenge)
+        {
+            return Err(OAuthTokenError::InvalidGrant);
+        }
+
+        let user = self
+            .users_repository
+            .get(authorization_code.user_id)
+            .await
+            .map_err(|_| OAuthTokenError::InvalidGrant)?;
+
+        let scope = authorization_code.scopes.join(" ");
+        let id_token = self.create_id_token(
+            &user,
+            &client,
+            &authorization_code.scopes,
+            authorization_code.nonce,
+            config,
+        )?;
+
+        Ok(AccessTokenResponse {
+            access_token: create_scoped_token(config, user.id, &scope)?,
+            token_type: "Bearer".to_string(),
+            expires_in: config.jwt_expired_in * 60,
+            scope,
+            id_token: Some(id_token),
+        })
+    }
+
+    /// Return the claims of the user allowed by the scopes of the access token
+    pub async fn userinfo(
+        &self,
+        user_id: i32,
+        granted_scopes: &[String],
+    ) -> Result<UserClaims, ServiceError> {
+        let user = self.users_repository.get(user_id).await?;
+
+        Ok(UserClaims::new(&user, granted_scopes))
+    }
+
+    /// JSON Web Key Set used by the clients to verify the ID tokens
+    pub fn jwks(&self) -> serde_json::Value {
+        self.signing_key.jwks()
+    }
+
+    /// Provider metadata (OpenID Connect Discovery section 3)
+    pub fn discovery(&self, config: &Config) -> serde_json::Value {
+        let issuer = config.oidc_info.issuer.trim_end_matches('/');
+
+        serde_json::json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/v1/oidc/authorize", issuer),
+            "token_endpoint": format!("{}/v1/oauth/token", issuer),
+            "userinfo_endpoint": format!("{}/v1/oidc/userinfo", issuer),
+            "jwks_uri": format!("{}/v1/oidc/jwks", issuer),
+            "scopes_supported": scopes::OIDC,
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", "client_credentials"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "code_challenge_methods_supported": ["S256"],
+            "claims_supported": ["sub", "preferred_username", "given_name", "family_name", "email"],
+        })
+    }
+
+    async fn issue_code(
+        &self,
+        user_id: i32,
+        client: &OAuthClient,
+        request: &AuthorizationRequest,
+        granted_scopes: Vec<String>,
+    ) -> Result<AuthorizationOutcome, ServiceError> {
+        let code = generate_authorization_code();
+
+        self.oauth_authorization_codes_repository
+            .create(&NewOAuthAuthorizationCode {
+                code_hash: hash_secret(&code),
+                client_id: client.id,
+                user_id,
+                redirect_uri: request.redirect_uri.clone(),
+                scopes: granted_scopes,
+                code_challenge: request.code_challenge.clone().unwrap_or_default(),
+                nonce: request.nonce.clone(),
+                expires_at: chrono::Utc::now().naive_utc()
+                    + chrono::Duration::seconds(AUTHORIZATION_CODE_TTL),
+            })
+            .await?;
+
+        redirect_with(request, &[("code", &code)])
+    }
+
+    fn create_id_token(
+        &self,
+        user: &User,
+        client: &OAuthClient,
+        granted_scopes: &[String],
+        nonce: Option<String>,
+        config: &Config,
+    ) -> Result<String, ServiceError> {
+        let iat = OffsetDateTime::now_utc();
+        let exp = iat + Duration::minutes(config.jwt_expired_in);
+
+        let claims = IdTokenClaims {
+            iss: config.oidc_info.issuer.trim_end_matches('/').to_string(),
+            aud: client.client_id.clone(),
+            iat: iat.unix_timestamp(),
+            exp: exp.unix_timestamp(),
+            nonce,
+            user: UserClaims::new(user, granted_scopes),
+        };
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.signing_key.kid().to_string());
+
+        encode(&header, &claims, self.signing_key.encoding_key()).map_err(|_| ServiceError {
+            message: Some("encode failed".to_string()),
+            error_type: ServiceErrorType::InternalServerError,
+        })
+    }
+}
+
+fn generate_authorization_code() -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Check the PKCE code verifier against the challenge (RFC 7636 section 4.6)
+fn verify_code_challenge(code_verifier: &str, code_challenge: &str) -> bool {
+    let computed = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    // the challenge is compared like a secret, through its hash
+    verify_secret(&computed, &hash_secret(code_challenge))
+}
+
+fn redirect_with_error(
+    request: &AuthorizationRequest,
+    error: &str,
+) -> Result<AuthorizationOutcome, ServiceError> {
+    redirect_with(request, &[("error", error)])
+}
+
+fn redirect_with(
+    request: &AuthorizationRequest,
+    params: &[(&str, &str)],
+) -> Result<AuthorizationOutcome, ServiceError> {
+    let mut redirect_uri = Url::parse(&request.redirect_uri).map_err(|_| ServiceError {
+        message: Some("Invalid redirect uri".to_string()),
+        error_type: ServiceErrorType::BadDeserialization,
+    })?;
+
+    {
+        let mut query = redirect_uri.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = &request.state {
+            query.append_pair("state", state);
+        }
+    }
+
+    Ok(AuthorizationOutcome::Redirect(AuthorizationRedirect {
+        redirect_to: redirect_uri.to_string(),
+    }))
+}
@@ -1,24 +1,52 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
+use api_caches::{
+    single_flight::SingleFlight,
+    user_profiles::{UserProfilesCache, UserProfilesCacheRedis},
+};
 use api_db::{
     connection::Pool, repositories::users_repository::UsersRepository, repository::Repository,
 };
 use api_errors::ServiceError;
 use api_types::user::SafeUser;
 
+/// Number of profiles read from the cache and from the database since the start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProfileCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct ProfileCacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
 #[derive(Clone)]
-pub struct UsersService {
+pub struct UsersService<P: UserProfilesCache = UserProfilesCacheRedis> {
     user_repository: UsersRepository,
+    /// Profiles read before the database (cache-aside).
+    profiles_cache: Arc<P>,
+    /// Only one request per user loads a missing profile, the others wait for the cache.
+    loading_profiles: Arc<SingleFlight<i32>>,
+    counters: Arc<ProfileCacheCounters>,
 }
 
-impl UsersService {
-    pub fn new(conn: Pool) -> Self {
+impl<P: UserProfilesCache> UsersService<P> {
+    pub fn new(conn: Pool, profiles_cache: Arc<P>) -> Self {
         Self {
             user_repository: UsersRepository::new(Arc::clone(&conn)),
+            profiles_cache,
+            loading_profiles: Arc::new(SingleFlight::default()),
+            counters: Arc::new(ProfileCacheCounters::default()),
         }
     }
 
-    /// Get a safe user by id
+    /// Get a safe user by id, from the cache if possible
     /// SafeUser does not contain sensitive information like password
     ///
     /// # Arguments
@@ -29,7 +57,61 @@ impl UsersService {
     ///
     /// A `Result` containing a `SafeUser` if the user was found, or a `ServiceError` if the user was not found
     pub async fn get_safe_user(&self, id: i32) -> Result<SafeUser, ServiceError> {
-        self.user_repository.get(id).await.map(SafeUser::from)
+        if let Some(user) = self.cached_profile(id).await {
+            return Ok(user);
+        }
+
+        let _loading = self.loading_profiles.lock(id).await;
+        // chargé par la requête qui avait le verrou
+        if let Some(user) = self.cached_profile(id).await {
+            return Ok(user);
+        }
+
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        let user = SafeUser::from(self.user_repository.get(id).await?);
+        if let Err(err) = self.profiles_cache.save_profile(&user).await {
+            log::warn!("Failed to cache the profile of the user {}: {}", id, err);
+        }
+
+        Ok(user)
+    }
+
+    /// The cached profile of a user, a failure of the cache is a miss.
+    async fn cached_profile(&self, id: i32) -> Option<SafeUser> {
+        match self.profiles_cache.get_profile(id).await {
+            Ok(Some(user)) => {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                Some(user)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                log::warn!("Failed to read the profile of the user {}: {}", id, err);
+                None
+            }
+        }
+    }
+
+    /// Removes the cached profile of a user, to call after each change of the user.
+    /// A profile being loaded is cached before the removal, so a stale profile can not
+    /// be cached again after it.
+    pub async fn invalidate_profile(&self, id: i32) {
+        let _loading = self.loading_profiles.lock(id).await;
+        if let Err(err) = self.profiles_cache.invalidate_profile(id).await {
+            // le profil en cache expirera avec son ttl
+            log::error!(
+                "Failed to invalidate the profile of the user {}: {}",
+                id,
+                err
+            );
+        }
+    }
+
+    /// The hits and misses of the profiles cache.
+    pub fn profile_cache_stats(&self) -> ProfileCacheStats {
+        ProfileCacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+        }
     }
 
     /// Destroy a user by id
@@ -43,6 +125,7 @@ impl UsersService {
     /// A `Result` containing `()` if the user was destroyed, or a `ServiceError` if the user was not found
     pub async fn destroy_user(&self, id_user: i32) -> Result<(), ServiceError> {
         self.user_repository.delete(id_user).await?;
+        self.invalidate_profile(id_user).await;
         Ok(())
     }
 }
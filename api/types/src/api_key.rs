@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use validator::Validate;
+
+#[derive(Deserialize, Validate)]
+pub struct InputApiKey {
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct NewApiKey {
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Public representation of an API key, the hash of the secret is never exposed.
+#[derive(Serialize, Deserialize)]
+pub struct SafeApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Response of the creation of an API key.
+/// `key` is the only moment where the secret is shown to the user.
+#[derive(Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub api_key: SafeApiKey,
+}
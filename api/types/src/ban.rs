@@ -0,0 +1,40 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use validator::{Validate, ValidationError};
+
+#[derive(Deserialize, Validate)]
+pub struct InputBan {
+    /// `ip:{address}`, `user:{id}` or `client:{id}`
+    #[validate(custom = "validate_target")]
+    pub target: String,
+    #[validate(length(min = 1, max = 255))]
+    pub reason: String,
+    /// duration of the ban in seconds, up to 30 days
+    #[validate(range(min = 1, max = 2592000))]
+    pub duration: i64,
+}
+
+/// A temporary ban, the target is refused until the ban expires.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    pub target: String,
+    pub reason: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Check that a target is an ip, a user or a client.
+pub fn validate_target(target: &str) -> Result<(), ValidationError> {
+    let valid = match target.split_once(':') {
+        Some(("ip", ip)) => ip.parse::<IpAddr>().is_ok(),
+        Some(("user" | "client", id)) => id.parse::<i32>().is_ok(),
+        _ => false,
+    };
+
+    if valid {
+        return Ok(());
+    }
+
+    Err(ValidationError::new("invalid_target"))
+}
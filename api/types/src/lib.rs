@@ -1,2 +1,7 @@
+pub mod api_key;
+pub mod ban;
+pub mod oauth_client;
+pub mod oidc;
 pub mod roles;
+pub mod scopes;
 pub mod user;
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+pub struct NewOAuthClient {
+    pub client_id: String,
+    pub secret_hash: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub redirect_uris: Vec<String>,
+    pub is_public: bool,
+}
+
+/// Registration of a new client.
+/// `redirect_uris` are only needed by the clients of the OpenID Connect provider.
+/// A public client (SPA, mobile app) cannot keep a secret and relies only on PKCE.
+#[derive(Default)]
+pub struct ClientRegistration {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub redirect_uris: Vec<String>,
+    pub is_public: bool,
+}
+
+/// Form of the token endpoint (RFC 6749 section 4.4.2).
+/// The client credentials can also be given with the HTTP Basic scheme.
+/// The `authorization_code` grant (RFC 6749 section 4.1.3) uses `code`, `redirect_uri` and the PKCE `code_verifier`.
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+/// Successful response of the token endpoint (RFC 6749 section 5.1).
+#[derive(Serialize, Deserialize)]
+pub struct AccessTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+    /// only present for the `authorization_code` grant with the `openid` scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+/// Credentials of a client, the secret is only shown at the registration.
+pub struct RegisteredOAuthClient {
+    pub client_id: String,
+    pub client_secret: String,
+}
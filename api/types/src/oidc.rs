@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone)]
+pub struct NewOAuthConsent {
+    pub user_id: i32,
+    pub client_id: i32,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct NewOAuthAuthorizationCode {
+    pub code_hash: String,
+    pub client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// Parameters of an authentication request (OpenID Connect Core section 3.1.2.1).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+/// Authentication request sent back by the front-end once the user is logged in.
+/// Without `approved`, a code is only issued if the user already consented to the scopes.
+#[derive(Deserialize)]
+pub struct AuthorizationDecision {
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+    pub approved: Option<bool>,
+}
+
+/// Response asking the front-end to display the consent screen.
+#[derive(Serialize, Deserialize)]
+pub struct ConsentRequired {
+    pub consent_required: bool,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Response giving the front-end the url where the user agent must be redirected.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorizationRedirect {
+    pub redirect_to: String,
+}
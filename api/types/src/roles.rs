@@ -1,14 +1,16 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub enum Role {
     Admin,
     User,
 }
 
-impl Role {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Role::Admin => "admin".to_string(),
-            Role::User => "user".to_string(),
+            Role::Admin => write!(f, "admin"),
+            Role::User => write!(f, "user"),
         }
     }
 }
@@ -0,0 +1,33 @@
+//! Scopes that can be granted to a token which is not issued by an interactive login.
+
+pub const USERS_READ: &str = "users:read";
+pub const USERS_WRITE: &str = "users:write";
+
+/// Every scope known by the API.
+pub const ALL: [&str; 2] = [USERS_READ, USERS_WRITE];
+
+pub const OPENID: &str = "openid";
+pub const PROFILE: &str = "profile";
+pub const EMAIL: &str = "email";
+
+/// Scopes of the OpenID Connect provider, only grantable to registered clients.
+pub const OIDC: [&str; 3] = [OPENID, PROFILE, EMAIL];
+
+pub const BANS_WRITE: &str = "bans:write";
+
+/// Administration scopes, only grantable to registered clients.
+pub const ADMIN: [&str; 1] = [BANS_WRITE];
+
+/// Check that every scope of the list is known by the API.
+pub fn are_valid(scopes: &[String]) -> bool {
+    scopes.iter().all(|scope| ALL.contains(&scope.as_str()))
+}
+
+/// Check that every scope of the list can be granted to an OAuth client.
+pub fn are_valid_for_client(scopes: &[String]) -> bool {
+    scopes.iter().all(|scope| {
+        ALL.contains(&scope.as_str())
+            || OIDC.contains(&scope.as_str())
+            || ADMIN.contains(&scope.as_str())
+    })
+}
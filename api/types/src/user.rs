@@ -38,7 +38,22 @@ pub struct RefreshableUser {
     pub refresh_token: String,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Asks for a password reset link sent by email.
+#[derive(Deserialize, Validate)]
+pub struct PasswordResetRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+/// Sets a new password with the token of a reset link.
+#[derive(Deserialize, Validate)]
+pub struct PasswordReset {
+    #[validate(length(min = 1))]
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct NewUser {
     pub pseudo: String,
     pub first_name: Option<String>,
@@ -53,7 +68,7 @@ pub struct NewUserWithId {
     pub user: UserPayload,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SafeUser {
     pub id: i32,
     pub pseudo: String,
@@ -0,0 +1,59 @@
+//! Register an OAuth client.
+//!
+//! Usage: `cargo run --bin register_oauth_client -- <name> [--public] [--redirect-uri <uri>]... [scope ...]`
+//!
+//! A client with a redirect uri can be used as an OpenID Connect relying party.
+
+use std::sync::Arc;
+
+use api_services::oauth_clients::OAuthClientsService;
+use api_types::oauth_client::ClientRegistration;
+
+const USAGE: &str =
+    "Usage: register_oauth_client <name> [--public] [--redirect-uri <uri>]... [scope ...]";
+
+#[actix_web::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let Some(name) = args.next() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    };
+
+    let mut registration = ClientRegistration {
+        name: name.clone(),
+        ..Default::default()
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--public" => registration.is_public = true,
+            "--redirect-uri" => match args.next() {
+                Some(uri) => registration.redirect_uris.push(uri),
+                None => {
+                    eprintln!("{}", USAGE);
+                    std::process::exit(1);
+                }
+            },
+            _ => registration.scopes.push(arg),
+        }
+    }
+
+    let config = api_configs::config::Config::init();
+    let pg_connection = api_db::connection::establish_connection(&config);
+
+    let oauth_clients_service = OAuthClientsService::new(Arc::clone(&pg_connection));
+
+    match oauth_clients_service.register_client(registration).await {
+        Ok(client) => {
+            println!("✅ Client \"{}\" enregistré.", name);
+            println!("client_id: {}", client.client_id);
+            println!("client_secret: {}", client.client_secret);
+            println!("⚠️ Le secret ne sera plus affiché, conservez-le.");
+        }
+        Err(err) => {
+            eprintln!("❌ Impossible d'enregistrer le client: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
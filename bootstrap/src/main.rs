@@ -3,9 +3,12 @@ use std::sync::Arc;
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 
+use api_caches::{instrumented::InstrumentedRedis, token_buckets::TokenBucketsCacheRedis};
 use api_db::connection::Pool;
 use api_errors::{ServiceError, ServiceErrorType};
-use api_middlewares::rate_limiter::RateLimiter;
+use api_middlewares::{
+    access_control::AccessControl, client_ip::client_ip, rate_limiter::RateLimiter,
+};
 
 mod routes;
 
@@ -23,32 +26,91 @@ async fn main() -> std::io::Result<()> {
 
     // instanciation des caches
     println!("⚙️ Instanciation des caches.");
+    // latence et erreurs par commande des caches de l'authentification et du rate limiter
+    let instrumented_redis = InstrumentedRedis::new(Arc::clone(&redis_client));
+
     let access_refresh_tokens_cache = Arc::new(
         api_caches::access_refresh_tokens::AccessRefreshTokensCacheRedis::new(
-            Arc::clone(&redis_client),
+            instrumented_redis.clone(),
             config.clone(),
         ),
     );
 
-    let rate_limiter_cache = Arc::new(api_caches::token_buckets::TokenBucketsCacheRedis::new(
+    let login_attempts_cache = Arc::new(api_caches::login_attempts::LoginAttemptsCacheRedis::new(
+        instrumented_redis.clone(),
+    ));
+
+    let rate_limiter_cache = Arc::new(TokenBucketsCacheRedis::new(instrumented_redis.clone()));
+
+    let bans_cache = Arc::new(api_caches::bans::BansCacheRedis::new(Arc::clone(
+        &redis_client,
+    )));
+
+    // invalidations des caches en mémoire de chaque instance
+    let invalidation_bus = Arc::new(api_caches::invalidation::InvalidationBusRedis::new(
         Arc::clone(&redis_client),
     ));
+    invalidation_bus.start();
+
+    // profils en mémoire, invalidés sur toutes les instances par le bus
+    let user_profiles_cache = Arc::new(
+        api_caches::user_profiles::UserProfilesCacheMemory::new(
+            &config,
+            Arc::new(api_caches::clock::SystemClock),
+        )
+        .with_invalidation_bus(invalidation_bus.clone()),
+    );
 
     println!("⚙️ Création des répositories pour injection de dépendances.");
     let users_repository = Arc::new(
         api_db::repositories::users_repository::UsersRepository::new(Arc::clone(&pg_connection)),
     );
+    let authorization_codes_repository = Arc::new(
+        api_db::repositories::oauth_authorization_codes_repository::OAuthAuthorizationCodesRepository::new(
+            Arc::clone(&pg_connection),
+        ),
+    );
 
     // instanciation des services
     println!("⚙️ Instanciation des services.");
-    let user_service = api_services::users::UsersService::new(Arc::clone(&pg_connection));
+    let user_service =
+        api_services::users::UsersService::new(Arc::clone(&pg_connection), user_profiles_cache);
+    let api_keys_service = api_services::api_keys::ApiKeysService::new(Arc::clone(&pg_connection));
+    let oauth_clients_service =
+        api_services::oauth_clients::OAuthClientsService::new(Arc::clone(&pg_connection));
+    let oidc_signing_key = api_services::oidc::keys::OidcSigningKey::from_config(&config)
+        .expect("Failed to load the OIDC signing key");
+    let oidc_service = api_services::oidc::services::OidcService::new(
+        Arc::clone(&pg_connection),
+        oidc_signing_key,
+    );
     let auth_service = api_services::auth::services::AuthService::new(
         Arc::clone(&users_repository),
         access_refresh_tokens_cache,
+        login_attempts_cache,
+        Arc::new(api_services::mailer::LogMailer),
     );
 
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    println!("⚙️ Démarrage des jobs.");
+    api_jobs::start_jobs(vec![
+        Box::new(
+            api_jobs::rate_limit_buckets::RateLimitBucketsCleanupJob::new(
+                Arc::clone(&rate_limiter_cache),
+                None,
+            ),
+        ),
+        Box::new(
+            api_jobs::oauth_authorization_codes::OAuthAuthorizationCodesCleanupJob::new(
+                Arc::clone(&authorization_codes_repository),
+                None,
+            ),
+        ),
+    ])
+    .await
+    .expect("Failed to start jobs");
+
     println!("🚀 Démarrage du back-end.");
 
     HttpServer::new(move || {
@@ -56,6 +118,12 @@ async fn main() -> std::io::Result<()> {
             .allow_any_origin()
             .allow_any_method()
             .allow_any_header()
+            .expose_headers([
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Retry-After",
+            ])
             .max_age(3600);
 
         App::new()
@@ -69,12 +137,28 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(auth_service.clone()))
             .app_data(web::Data::new(user_service.clone()))
+            .app_data(web::Data::new(api_keys_service.clone()))
+            .app_data(web::Data::new(oauth_clients_service.clone()))
+            .app_data(web::Data::new(oidc_service.clone()))
             .app_data(web::Data::new(redis_client.clone()))
+            .app_data(web::Data::new(instrumented_redis.clone()))
             .app_data(web::Data::new(oauth_client.clone()))
             .app_data(web::Data::new(rate_limiter_cache.clone()))
+            .app_data(web::Data::new(bans_cache.clone()))
+            .wrap(RateLimiter::default().with_cache::<TokenBucketsCacheRedis<InstrumentedRedis>>())
+            // enveloppe le rate limiter pour l'exempter des requêtes autorisées et compter ses 429
+            .wrap(AccessControl::default())
+            .wrap(
+                // %a du format par défaut remplacé par l'ip résolue via les proxies de confiance
+                Logger::new(r#"%{client_ip}xi "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+                    .custom_request_replace("client_ip", |req| {
+                        client_ip(req.request())
+                            .map(|ip| ip.to_string())
+                            .unwrap_or_else(|| "-".to_string())
+                    }),
+            )
+            // le plus à l'extérieur, pour que les refus des middlewares portent aussi les en-têtes cors
             .wrap(cors)
-            .wrap(Logger::default())
-            .wrap(RateLimiter)
             .configure(routes::config)
     })
     .bind("127.0.0.1:8080")?
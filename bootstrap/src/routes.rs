@@ -1,19 +1,38 @@
 use actix_web::web;
 
-use api_caches::access_refresh_tokens::AccessRefreshTokensCacheRedis;
+use api_caches::{
+    access_refresh_tokens::AccessRefreshTokensCacheRedis, bans::BansCacheRedis,
+    instrumented::InstrumentedRedis, login_attempts::LoginAttemptsCacheRedis,
+    token_buckets::TokenBucketsCacheRedis, user_profiles::UserProfilesCacheMemory,
+};
 use api_db::repositories::users_repository::UsersRepository;
+use api_services::mailer::LogMailer;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .configure(api_handlers::health::service)
             .configure(
-                api_handlers::auth::service::<UsersRepository, AccessRefreshTokensCacheRedis>,
+                api_handlers::auth::service::<
+                    UsersRepository,
+                    AccessRefreshTokensCacheRedis<InstrumentedRedis>,
+                    LoginAttemptsCacheRedis<InstrumentedRedis>,
+                    LogMailer,
+                    TokenBucketsCacheRedis<InstrumentedRedis>,
+                >,
             )
             .configure(api_handlers::secure::service)
-            .configure(api_handlers::users::service::<UsersRepository>)
+            .configure(api_handlers::users::service::<UsersRepository, UserProfilesCacheMemory>)
+            .configure(api_handlers::api_keys::service)
             .configure(
-                api_handlers::oauth::service::<UsersRepository, AccessRefreshTokensCacheRedis>,
-            ),
+                api_handlers::oauth::service::<
+                    UsersRepository,
+                    AccessRefreshTokensCacheRedis<InstrumentedRedis>,
+                    LoginAttemptsCacheRedis<InstrumentedRedis>,
+                    LogMailer,
+                >,
+            )
+            .configure(api_handlers::oidc::service)
+            .configure(api_handlers::bans::service::<BansCacheRedis>),
     );
 }
@@ -15,9 +15,20 @@ class AuthenticationBehavior:
             "password": generate_random_password(),
         }
 
+        # the registration only answers 202, the tokens are given by the login
         response = self.user.client.post(
             "/api/v1/auth/register", json=self.credentials, headers=self.headers
         )
+        if response.status_code != 202:
+            print(f"Failed to register: {response.status_code}")
+            return
+
+        self.login()
+
+    def login(self):
+        response = self.user.client.post(
+            "/api/v1/auth/login", json=self.credentials, headers=self.headers
+        )
         if response.status_code == 200:
             token = response.json().get("access_token")
             if token:
@@ -25,4 +36,4 @@ class AuthenticationBehavior:
             else:
                 print(f"Error: No access token in response {response.text}")
         else:
-            print(f"Failed to register: {response.status_code}")
+            print(f"Failed to log in: {response.status_code}")